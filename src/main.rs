@@ -34,6 +34,6 @@ fn main() {
     
     // this is the window that allows navigation
     let window = MainWindow::new();
-    window.exit_on_close();
+    MainWindow::exit_on_close(&window);
     gtk::main();
 }
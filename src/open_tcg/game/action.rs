@@ -0,0 +1,310 @@
+// MIT License
+
+// Copyright (c) 2016 Johan Burke
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+extern crate sxd_document;
+
+use self::sxd_document::dom::{Document, Element};
+
+use super::random::Secret;
+use super::state::{CardId, GameError, GameState, Orientation, PlayerId, ZoneId};
+use open_tcg::util::xml;
+use open_tcg::util::error::OpenTcgResult;
+use open_tcg::util::files::XmlFile;
+
+/// What a counter change applies to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CounterTarget {
+    Card(CardId),
+    Player(PlayerId)
+}
+
+/// A single change to the state of a game.
+///
+/// Every change made during a game is recorded as an action so that the
+/// game can be saved, replayed and sent to the other player.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    /// The player moves the top card of their deck to their hand.
+    Draw{player : PlayerId},
+
+    /// A card is moved to a zone, on top unless a position is given.
+    Move{card : CardId, to : ZoneId, position : Option<usize>},
+
+    /// A card is turned face up or face down.
+    Flip{card : CardId, face_up : bool},
+
+    /// A card is rotated, e.g. tapped or untapped.
+    Rotate{card : CardId, orientation : Orientation},
+
+    /// The cards in a zone are shuffled by the player whose secret seeds the
+    /// shuffle, with the secret the other player contributed to it. Since the
+    /// seed can't be known without the shuffling player's secret, this can be
+    /// sent to the other player as it is.
+    Shuffle{zone : ZoneId, player : PlayerId, contribution : Secret},
+
+    /// The player rolls a die with the given number of sides, using a seed both players
+    /// agreed on. A coin flip is a two-sided roll.
+    Roll{player : PlayerId, sides : u32, seed : u64},
+
+    /// A card is shown to every player.
+    Reveal{card : CardId},
+
+    /// The player looks at a card only they are shown.
+    Peek{player : PlayerId, card : CardId},
+
+    /// The player looks at the top `count` cards of a zone.
+    LookAtTop{player : PlayerId, zone : ZoneId, count : usize},
+
+    /// `delta` is added to the named counter.
+    ChangeCounter{target : CounterTarget, name : String, delta : i32},
+
+    /// A chat message or note, which doesn't change the state of the game.
+    Note{player : PlayerId, text : String},
+
+    /// The game ends with the given winner, or in a draw.
+    EndGame{winner : Option<PlayerId>}
+}
+
+impl Action {
+    /// Applies this action to the given game state.
+    pub fn apply(&self, state : &mut GameState) -> Result<(), GameError> {
+        match *self {
+            Action::Draw{player} => state.draw(player).map(|_| ()),
+            Action::Move{card, to, position} => state.move_card(card, to, position),
+            Action::Flip{card, face_up} => state.set_face_up(card, face_up),
+            Action::Rotate{card, orientation} => state.set_orientation(card, orientation),
+            Action::Shuffle{zone, player, ref contribution} => state.shuffle_zone(zone, player, contribution),
+            Action::Roll{player, sides, seed} => state.roll_die(player, sides, seed).map(|_| ()),
+            Action::Reveal{card} => state.reveal(card),
+            Action::Peek{player, card} => state.disclose(card, &[player]),
+            Action::LookAtTop{player, zone, count} => state.look_at_top(player, zone, count),
+            Action::ChangeCounter{ref target, ref name, delta} => {
+                match *target {
+                    CounterTarget::Card(card) => state.change_card_counter(card, name, delta),
+                    CounterTarget::Player(player) => state.change_player_counter(player, name, delta)
+                }
+            },
+            Action::Note{..} => Ok(()),
+            Action::EndGame{winner} => {
+                state.end(winner);
+                Ok(())
+            }
+        }
+    }
+
+    /// Creates an element describing this action, such as
+    /// `<Move><Card>3</Card><Zone>1</Zone></Move>`.
+    pub fn to_element<'d>(&self, doc : &Document<'d>) -> Element<'d> {
+        let element;
+        match *self {
+            Action::Draw{player} => {
+                element = doc.create_element("Draw");
+                xml::append_text_element(doc, &element, "Player", &player.to_string());
+            },
+            Action::Move{card, to, position} => {
+                element = doc.create_element("Move");
+                xml::append_text_element(doc, &element, "Card", &card.to_string());
+                xml::append_text_element(doc, &element, "Zone", &to.to_string());
+                if let Some(position) = position {
+                    xml::append_text_element(doc, &element, "Position", &position.to_string());
+                }
+            },
+            Action::Flip{card, face_up} => {
+                element = doc.create_element("Flip");
+                xml::append_text_element(doc, &element, "Card", &card.to_string());
+                xml::append_text_element(doc, &element, "FaceUp", &face_up.to_string());
+            },
+            Action::Rotate{card, orientation} => {
+                element = doc.create_element("Rotate");
+                xml::append_text_element(doc, &element, "Card", &card.to_string());
+                let orientation = match orientation {
+                    Orientation::Upright => "Upright",
+                    Orientation::Sideways => "Sideways"
+                };
+                xml::append_text_element(doc, &element, "Orientation", orientation);
+            },
+            Action::Shuffle{zone, player, ref contribution} => {
+                element = doc.create_element("Shuffle");
+                xml::append_text_element(doc, &element, "Zone", &zone.to_string());
+                xml::append_text_element(doc, &element, "Player", &player.to_string());
+                xml::append_text_element(doc, &element, "Contribution", &contribution.to_hex());
+            },
+            Action::Roll{player, sides, seed} => {
+                element = doc.create_element("Roll");
+                xml::append_text_element(doc, &element, "Player", &player.to_string());
+                xml::append_text_element(doc, &element, "Sides", &sides.to_string());
+                xml::append_text_element(doc, &element, "Seed", &seed.to_string());
+            },
+            Action::Reveal{card} => {
+                element = doc.create_element("Reveal");
+                xml::append_text_element(doc, &element, "Card", &card.to_string());
+            },
+            Action::Peek{player, card} => {
+                element = doc.create_element("Peek");
+                xml::append_text_element(doc, &element, "Player", &player.to_string());
+                xml::append_text_element(doc, &element, "Card", &card.to_string());
+            },
+            Action::LookAtTop{player, zone, count} => {
+                element = doc.create_element("LookAtTop");
+                xml::append_text_element(doc, &element, "Player", &player.to_string());
+                xml::append_text_element(doc, &element, "Zone", &zone.to_string());
+                xml::append_text_element(doc, &element, "Count", &count.to_string());
+            },
+            Action::ChangeCounter{ref target, ref name, delta} => {
+                element = doc.create_element("ChangeCounter");
+                match *target {
+                    CounterTarget::Card(card) => xml::append_text_element(doc, &element, "Card", &card.to_string()),
+                    CounterTarget::Player(player) => xml::append_text_element(doc, &element, "Player", &player.to_string())
+                };
+                xml::append_text_element(doc, &element, "Name", name);
+                xml::append_text_element(doc, &element, "Delta", &delta.to_string());
+            },
+            Action::Note{player, ref text} => {
+                element = doc.create_element("Note");
+                xml::append_text_element(doc, &element, "Player", &player.to_string());
+                xml::append_text_element(doc, &element, "Text", text);
+            },
+            Action::EndGame{winner} => {
+                element = doc.create_element("EndGame");
+                if let Some(winner) = winner {
+                    xml::append_text_element(doc, &element, "Winner", &winner.to_string());
+                }
+            }
+        }
+        element
+    }
+
+    /// Reads an action from an element created by `to_element`.
+    pub fn new_from_element(element : &Element, file : &XmlFile) -> OpenTcgResult<Action> {
+        let num = |name : &str| -> OpenTcgResult<u32> {
+            xml::read_num_from_element(&xml::required_child(element, name, file)?, file)
+        };
+
+        match element.name().local_part() {
+            "Draw" => Ok(Action::Draw{player : num("Player")? as PlayerId}),
+            "Move" => {
+                let position = match xml::find_child(element, "Position") {
+                    Some(p) => Some(xml::read_num_from_element(&p, file)? as usize),
+                    None => None
+                };
+                Ok(Action::Move{card : num("Card")?, to : num("Zone")? as ZoneId, position : position})
+            },
+            "Flip" => {
+                let face_up = xml::read_bool_from_element(&xml::required_child(element, "FaceUp", file)?, file)?;
+                Ok(Action::Flip{card : num("Card")?, face_up : face_up})
+            },
+            "Rotate" => {
+                let orientation_element = xml::required_child(element, "Orientation", file)?;
+                let orientation = match xml::read_text_from_element(&orientation_element).as_str() {
+                    "Upright" => Orientation::Upright,
+                    "Sideways" => Orientation::Sideways,
+                    _ => return Err(xml::invalid_value(&orientation_element, file))
+                };
+                Ok(Action::Rotate{card : num("Card")?, orientation : orientation})
+            },
+            "Shuffle" => {
+                let contribution_element = xml::required_child(element, "Contribution", file)?;
+                let contribution = Secret::from_hex(&xml::read_text_from_element(&contribution_element))
+                    .ok_or_else(|| xml::invalid_value(&contribution_element, file))?;
+                Ok(Action::Shuffle{zone : num("Zone")? as ZoneId, player : num("Player")? as PlayerId,
+                    contribution : contribution})
+            },
+            "Roll" => {
+                let seed = xml::read_parsed_from_element(&xml::required_child(element, "Seed", file)?, file)?;
+                Ok(Action::Roll{player : num("Player")? as PlayerId, sides : num("Sides")?, seed : seed})
+            },
+            "Reveal" => Ok(Action::Reveal{card : num("Card")?}),
+            "Peek" => Ok(Action::Peek{player : num("Player")? as PlayerId, card : num("Card")?}),
+            "LookAtTop" => Ok(Action::LookAtTop{player : num("Player")? as PlayerId, zone : num("Zone")? as ZoneId,
+                count : num("Count")? as usize}),
+            "ChangeCounter" => {
+                let target = match xml::find_child(element, "Card") {
+                    Some(_) => CounterTarget::Card(num("Card")?),
+                    None => CounterTarget::Player(num("Player")? as PlayerId)
+                };
+                let name = xml::read_text_from_element(&xml::required_child(element, "Name", file)?);
+                let delta = xml::read_int_from_element(&xml::required_child(element, "Delta", file)?, file)?;
+                Ok(Action::ChangeCounter{target : target, name : name, delta : delta})
+            },
+            "Note" => {
+                // notes are replayed as they were written, so their whitespace is kept
+                let text = xml::read_raw_text_from_element(&xml::required_child(element, "Text", file)?);
+                Ok(Action::Note{player : num("Player")? as PlayerId, text : text})
+            },
+            "EndGame" => {
+                let winner = match xml::find_child(element, "Winner") {
+                    Some(w) => Some(xml::read_num_from_element(&w, file)? as PlayerId),
+                    None => None
+                };
+                Ok(Action::EndGame{winner : winner})
+            },
+            _ => Err(xml::unexpected_element(element, file))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use self::sxd_document::Package;
+    use self::sxd_document::writer::format_document;
+    use super::super::random::SECRET_LEN;
+
+    #[test]
+    fn round_trip() {
+        let actions = vec![
+            Action::Draw{player : 1},
+            Action::Move{card : 3, to : 2, position : None},
+            Action::Move{card : 4, to : 0, position : Some(5)},
+            Action::Flip{card : 3, face_up : true},
+            Action::Rotate{card : 3, orientation : Orientation::Sideways},
+            Action::Rotate{card : 3, orientation : Orientation::Upright},
+            Action::Shuffle{zone : 1, player : 0, contribution : Secret([7; SECRET_LEN])},
+            Action::Roll{player : 0, sides : 6, seed : u64::max_value()},
+            Action::Reveal{card : 8},
+            Action::Peek{player : 1, card : 9},
+            Action::LookAtTop{player : 0, zone : 1, count : 3},
+            Action::ChangeCounter{target : CounterTarget::Card(3), name : "Damage".to_string(), delta : -2},
+            Action::ChangeCounter{target : CounterTarget::Player(1), name : "Life".to_string(), delta : 5},
+            Action::Note{player : 0, text : "  first line\n\tsecond & <third>  ".to_string()},
+            Action::EndGame{winner : Some(1)},
+            Action::EndGame{winner : None}];
+
+        let package = Package::new();
+        let doc = package.as_document();
+        let root = doc.create_element("Actions");
+        for action in actions.iter() {
+            root.append_child(action.to_element(&doc));
+        }
+        doc.root().append_child(root);
+        let mut source = Vec::new();
+        format_document(&doc, &mut source).unwrap();
+
+        let file = XmlFile::new_from_string(&PathBuf::from("actions.xml"), String::from_utf8(source).unwrap()).unwrap();
+        let read : Vec<Action> = file.root_element("Actions").unwrap().children().iter()
+            .filter_map(|c| c.element())
+            .map(|e| Action::new_from_element(&e, &file).unwrap())
+            .collect();
+        assert_eq!(read, actions);
+    }
+}
@@ -26,8 +26,11 @@ use std::collections::HashMap;
 use std::path::PathBuf;
 
 use self::sxd_document::QName;
+use self::sxd_document::dom::Element;
 
 use open_tcg::util::{files, xml};
+use open_tcg::util::files::XmlFile;
+use open_tcg::util::error::{OpenTcgError, OpenTcgResult};
 
 type ParamValues = HashMap<String, i32>;
 
@@ -42,8 +45,6 @@ pub struct CardInfo {
     pub set_name : String,
     pub set_code : String, // this is also used as an image handle
     pub text : String
-    // TODO: fill this in with card type, parameters, etc.
-    
 }
 
 impl CardInfo {
@@ -52,80 +53,402 @@ impl CardInfo {
             set_name : String::new(), set_code : String::new(), text : String::new()}
     }
 
-    pub fn new_from_file(filename : &PathBuf) -> CardInfo {
+    /// Reads a card from the given file.
+    ///
+    /// The card's `<Type>` is resolved against `card_types`, and its `<Parameters>` are
+    /// checked against that type's parameter names. A missing or unknown type, a parameter
+    /// without a name, or a missing or extra parameter doesn't prevent the card from
+    /// loading, but is added to `problems`.
+    pub fn new_from_file(filename : &PathBuf, card_types : &HashMap<String, CardType>,
+                         problems : &mut Vec<OpenTcgError>) -> OpenTcgResult<CardInfo> {
         let mut result = CardInfo::new();
 
-        let pkg = files::document_from_file(filename);
-        let doc = pkg.as_document();
-        let children = doc.root().children();
+        let file = files::document_from_file(filename)?;
+        let card_root = file.root()?;
 
         let name_name = QName::new("Name");
         let set_code_name = QName::new("SetCode");
         let set_name_name = QName::new("SetName");
         let text_name = QName::new("CardText");
+        let type_name = QName::new("Type");
+        let params_name = QName::new("Parameters");
+
+        let mut params = Vec::new();
+        let mut has_type = false;
 
-        if let Some(card_root) = children[0].element() {
-            for e in card_root.children() {
-                if let Some(element) = e.element() {
-                    let element_name = element.name();
-                    if element_name == name_name {
-                        result.name = xml::read_text_from_element(&element);
-                    } else if element_name == set_code_name {
-                        result.set_code = xml::read_text_from_element(&element);
-                    } else if element_name == set_name_name {
-                        result.set_name = xml::read_text_from_element(&element);
-                    } else if element_name == text_name {
-                        result.text = xml::read_text_from_element(&element);
+        for e in card_root.children() {
+            if let Some(element) = e.element() {
+                let element_name = element.name();
+                if element_name == name_name {
+                    result.name = xml::read_text_from_element(&element);
+                } else if element_name == set_code_name {
+                    result.set_code = xml::read_text_from_element(&element);
+                } else if element_name == set_name_name {
+                    result.set_name = xml::read_text_from_element(&element);
+                } else if element_name == text_name {
+                    result.text = xml::read_text_from_element(&element);
+                } else if element_name == type_name {
+                    has_type = true;
+                    let name = xml::read_text_from_element(&element);
+                    match find_card_type(card_types, &name) {
+                        Some(card_type) => result.card_type = card_type.clone(),
+                        None => problems.push(OpenTcgError::UnknownCardType{path : filename.clone(),
+                            card_type : name, line : file.line_of(&element)})
+                    }
+                } else if element_name == params_name {
+                    for param in element.children() {
+                        if let Some(param_elem) = param.element() {
+                            let (name, value, line) = CardInfo::read_param(&param_elem, &file)?;
+                            if name.is_empty() {
+                                problems.push(OpenTcgError::EmptyElement{path : filename.clone(),
+                                    element : "Name".to_string(), line : line});
+                            } else {
+                                params.push((name, value, line));
+                            }
+                        }
                     }
                 }
             }
         }
+        if !has_type {
+            problems.push(OpenTcgError::MissingElement{path : filename.clone(), element : "Type".to_string(),
+                line : file.line_of(&card_root)});
+        }
+
+        for (name, value, line) in params {
+            if result.card_type.param_names.contains(&name) {
+                result.param_values.insert(name, value);
+            } else {
+                problems.push(OpenTcgError::UnexpectedParameter{path : filename.clone(),
+                    parameter : name, line : line});
+            }
+        }
+        for name in result.card_type.param_names.iter() {
+            if !result.param_values.contains_key(name) {
+                problems.push(OpenTcgError::MissingParameter{path : filename.clone(),
+                    parameter : name.clone()});
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Reads a `<Parameter>` element holding a `<Name>` and a `<Value>`.
+    fn read_param(param_elem : &Element, file : &XmlFile) -> OpenTcgResult<(String, i32, Option<usize>)> {
+        let name_name = QName::new("Name");
+        let value_name = QName::new("Value");
+
+        let mut name = String::new();
+        let mut value = 0;
+        for e in param_elem.children() {
+            if let Some(element) = e.element() {
+                let element_name = element.name();
+                if element_name == name_name {
+                    name = xml::read_text_from_element(&element);
+                } else if element_name == value_name {
+                    value = xml::read_int_from_element(&element, file)?;
+                }
+            }
+        }
 
-        result
+        Ok((name, value, file.line_of(param_elem)))
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct CardType {
     pub name : String,
+
+    /// Name of the type this type is a subtype of, if any
+    pub parent : Option<String>,
+
+    /// Other names this type can be referred to by
+    pub aliases : Vec<String>,
+
+    /// Parameters of this type, including those inherited from its parents
+    /// once the type hierarchy has been resolved
     pub param_names : Vec<String>
 }
 
 impl CardType {
     pub fn new() -> CardType {
-        CardType{name : String::new(), param_names : Vec::new()}
+        CardType{name : String::new(), parent : None, aliases : Vec::new(), param_names : Vec::new()}
     }
 
-    pub fn new_from_file(filename : &PathBuf) -> CardType {
+    pub fn new_from_file(filename : &PathBuf) -> OpenTcgResult<CardType> {
         let mut result = CardType::new();
 
-        let pkg = files::document_from_file(filename);
-        let doc = pkg.as_document();
-        let children = doc.root().children();
+        let file = files::document_from_file(filename)?;
+        let type_root = file.root_element("CardType")?;
 
         let name_name = QName::new("Name");
         let params_name = QName::new("Parameters");
-        // TODO: figure out what to do with aliases/subtypes
-
-        if let Some(type_root) = children[0].element() {
-            if type_root.name() == QName::new("CardType") {
-                for e in type_root.children() {
-                    if let Some(element) = e.element() {
-                        let element_name = element.name();
-                        if element_name == name_name {
-                            result.name = xml::read_text_from_element(&element);
-                        } else if element_name == params_name {
-                            for param in element.children() {
-                                if let Some(param_elem) = param.element() {
-                                    result.param_names.push(xml::read_text_from_element(&param_elem));
-                                }
-                            }
+        let parent_name = QName::new("Parent");
+        let aliases_name = QName::new("Aliases");
+
+        for e in type_root.children() {
+            if let Some(element) = e.element() {
+                let element_name = element.name();
+                if element_name == name_name {
+                    result.name = xml::read_text_from_element(&element);
+                } else if element_name == parent_name {
+                    let parent = xml::read_text_from_element(&element);
+                    if !parent.is_empty() {
+                        result.parent = Some(parent);
+                    }
+                } else if element_name == aliases_name {
+                    for alias in element.children() {
+                        if let Some(alias_elem) = alias.element() {
+                            result.aliases.push(xml::read_text_from_element(&alias_elem));
+                        }
+                    }
+                } else if element_name == params_name {
+                    for param in element.children() {
+                        if let Some(param_elem) = param.element() {
+                            result.param_names.push(xml::read_text_from_element(&param_elem));
                         }
                     }
                 }
             }
         }
 
-        result
+        Ok(result)
+    }
+
+    /// Returns true if this type has the given name or alias.
+    pub fn is_named(&self, name : &str) -> bool {
+        self.name == name || self.aliases.iter().any(|a| a == name)
+    }
+
+    /// Returns true if this type is the given type or one of its (possibly indirect) subtypes.
+    pub fn is_subtype_of(&self, name : &str, card_types : &HashMap<String, CardType>) -> bool {
+        let mut current = Some(self);
+        let mut steps = 0;
+        while let Some(card_type) = current {
+            if card_type.is_named(name) {
+                return true;
+            }
+            // guard against cycles in unresolved hierarchies
+            steps += 1;
+            if steps > card_types.len() {
+                break;
+            }
+            current = match card_type.parent {
+                Some(ref parent) => card_types.get(parent),
+                None => None
+            };
+        }
+        false
+    }
+}
+
+/// Looks up a card type by its name or one of its aliases.
+pub fn find_card_type<'a>(card_types : &'a HashMap<String, CardType>, name : &str) -> Option<&'a CardType> {
+    match card_types.get(name) {
+        Some(card_type) => Some(card_type),
+        None => card_types.values().find(|t| t.is_named(name))
+    }
+}
+
+/// Resolves the hierarchy of a set of card types once all of them have been loaded.
+///
+/// Parents given by alias are replaced with their proper names, and each type's
+/// `param_names` are extended with those of its ancestors, starting from the root.
+/// Missing parents and cycles are added to `errors`; the affected type then
+/// inherits only from the ancestors that could be resolved. Both are reported
+/// only for the types they concern, not for the types that descend from those:
+/// a missing parent for the type that names it, and a cycle for each type in it.
+/// `paths` maps each type name to the file it was read from.
+pub fn resolve_card_types(card_types : &mut HashMap<String, CardType>, paths : &HashMap<String, PathBuf>,
+                          errors : &mut Vec<OpenTcgError>) {
+    let declared = card_types.clone();
+
+    for (name, card_type) in card_types.iter_mut() {
+        let path = paths.get(name).cloned().unwrap_or(PathBuf::new());
+
+        // collect the chain of ancestors, nearest first
+        let mut chain : Vec<&CardType> = Vec::new();
+        let mut visited = vec![name.clone()];
+        let mut parent = card_type.parent.clone();
+        while let Some(parent_name) = parent {
+            match find_card_type(&declared, &parent_name) {
+                Some(parent_type) => {
+                    if parent_type.name == *name {
+                        errors.push(OpenTcgError::CardTypeCycle{path : path.clone(), card_type : name.clone()});
+                        break;
+                    }
+                    if visited.contains(&parent_type.name) {
+                        // the cycle is above this type, and is reported for the types in it
+                        break;
+                    }
+                    visited.push(parent_type.name.clone());
+                    chain.push(parent_type);
+                    parent = parent_type.parent.clone();
+                },
+                None => {
+                    // missing grandparents are reported for the types that name them
+                    if chain.is_empty() {
+                        errors.push(OpenTcgError::MissingParentType{path : path.clone(),
+                            card_type : name.clone(), parent : parent_name});
+                    }
+                    break;
+                }
+            }
+        }
+
+        if let Some(parent_type) = chain.first() {
+            card_type.parent = Some(parent_type.name.clone());
+        }
+
+        let mut param_names : Vec<String> = Vec::new();
+        for ancestor in chain.iter().rev() {
+            for param in ancestor.param_names.iter() {
+                if !param_names.contains(param) {
+                    param_names.push(param.clone());
+                }
+            }
+        }
+        for param in card_type.param_names.iter() {
+            if !param_names.contains(param) {
+                param_names.push(param.clone());
+            }
+        }
+        card_type.param_names = param_names;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Write;
+    use std::process;
+
+    fn card_type(name : &str, parent : Option<&str>, aliases : &[&str], params : &[&str]) -> CardType {
+        CardType{name : name.to_string(), parent : parent.map(|p| p.to_string()),
+            aliases : aliases.iter().map(|a| a.to_string()).collect(),
+            param_names : params.iter().map(|p| p.to_string()).collect()}
+    }
+
+    fn resolve(types : Vec<CardType>) -> (HashMap<String, CardType>, Vec<String>) {
+        let mut card_types : HashMap<String, CardType> = types.into_iter().map(|t| (t.name.clone(), t)).collect();
+        let paths = card_types.keys().map(|name| (name.clone(), PathBuf::from(format!("{}.xml", name)))).collect();
+        let mut errors = Vec::new();
+        resolve_card_types(&mut card_types, &paths, &mut errors);
+        let mut messages : Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        messages.sort();
+        (card_types, messages)
+    }
+
+    #[test]
+    fn inherits_params_from_the_root() {
+        let (types, errors) = resolve(vec![
+            card_type("Card", None, &[], &["Cost"]),
+            card_type("Creature", Some("Card"), &["Monster"], &["Power", "Cost"]),
+            card_type("Legend", Some("Monster"), &[], &["Fame"])]);
+        assert!(errors.is_empty());
+        assert_eq!(types["Creature"].param_names, vec!["Cost", "Power"]);
+        assert_eq!(types["Legend"].param_names, vec!["Cost", "Power", "Fame"]);
+
+        // parents given by alias get their proper name
+        assert_eq!(types["Legend"].parent, Some("Creature".to_string()));
+        assert!(types["Legend"].is_subtype_of("Card", &types));
+        assert_eq!(find_card_type(&types, "Monster").map(|t| t.name.as_str()), Some("Creature"));
+    }
+
+    #[test]
+    fn missing_parent() {
+        let (types, errors) = resolve(vec![
+            card_type("Creature", Some("Card"), &[], &["Power"]),
+            card_type("Legend", Some("Creature"), &[], &["Fame"])]);
+        assert_eq!(errors, vec!["Creature.xml: parent \"Card\" of card type \"Creature\" is not defined"]);
+        assert_eq!(types["Creature"].param_names, vec!["Power"]);
+        assert_eq!(types["Legend"].param_names, vec!["Power", "Fame"]);
+    }
+
+    #[test]
+    fn cycles() {
+        // D descends from the cycle A -> B -> A but isn't part of it
+        let (types, errors) = resolve(vec![
+            card_type("A", Some("B"), &[], &["a"]),
+            card_type("B", Some("A"), &[], &["b"]),
+            card_type("D", Some("A"), &[], &["d"])]);
+        assert_eq!(errors, vec!["A.xml: the parents of card type \"A\" form a cycle",
+                                "B.xml: the parents of card type \"B\" form a cycle"]);
+        assert_eq!(types["A"].param_names, vec!["b", "a"]);
+        assert_eq!(types["D"].param_names, vec!["b", "a", "d"]);
+
+        let (_, errors) = resolve(vec![card_type("A", Some("A"), &[], &[])]);
+        assert_eq!(errors, vec!["A.xml: the parents of card type \"A\" form a cycle"]);
+    }
+
+    /// Reads a card from the given XML with the types `Creature` and `Spell`.
+    fn read_card(name : &str, xml : &str) -> (OpenTcgResult<CardInfo>, Vec<String>) {
+        let card_types : HashMap<String, CardType> = vec![
+            card_type("Creature", None, &["Monster"], &["Power", "Toughness"]),
+            card_type("Spell", None, &[], &[])].into_iter().map(|t| (t.name.clone(), t)).collect();
+        let path = env::temp_dir().join(format!("open_tcg_card_{}_{}.xml", name, process::id()));
+        File::create(&path).unwrap().write_all(xml.as_bytes()).unwrap();
+
+        let mut problems = Vec::new();
+        let result = CardInfo::new_from_file(&path, &card_types, &mut problems);
+        fs::remove_file(&path).unwrap();
+        let prefix = format!("{}", path.display());
+        (result, problems.iter().map(|p| p.to_string().replace(&prefix, "card")).collect())
+    }
+
+    #[test]
+    fn reads_card() {
+        let (card, problems) = read_card("goblin", r#"<?xml version="1.0"?>
+<Card>
+  <Name>Goblin</Name>
+  <SetName>Core</SetName>
+  <SetCode>C001</SetCode>
+  <CardText>Haste</CardText>
+  <Type>Monster</Type>
+  <Parameters>
+    <Parameter><Name>Power</Name><Value>2</Value></Parameter>
+    <Parameter><Name>Toughness</Name><Value>-1</Value></Parameter>
+  </Parameters>
+</Card>"#);
+        let card = card.unwrap();
+        assert!(problems.is_empty());
+        assert_eq!(card.name, "Goblin");
+        assert_eq!((card.set_name.as_str(), card.set_code.as_str()), ("Core", "C001"));
+        assert_eq!(card.text, "Haste");
+        assert_eq!(card.card_type.name, "Creature");
+        assert_eq!(card.param_values["Power"], 2);
+        assert_eq!(card.param_values["Toughness"], -1);
+    }
+
+    #[test]
+    fn reports_problems() {
+        let (card, problems) = read_card("untyped", r#"<?xml version="1.0"?>
+<Card>
+  <Name>Untyped</Name>
+</Card>"#);
+        assert_eq!(card.unwrap().card_type.name, "");
+        assert_eq!(problems, vec!["card:2: missing <Type>"]);
+
+        let (card, problems) = read_card("unknown", r#"<?xml version="1.0"?>
+<Card><Name>Unknown</Name><Type>Artifact</Type></Card>"#);
+        assert!(card.is_ok());
+        assert_eq!(problems, vec!["card:2: unknown card type \"Artifact\""]);
+
+        let (card, problems) = read_card("params", r#"<?xml version="1.0"?>
+<Card>
+  <Name>Goblin</Name>
+  <Type>Creature</Type>
+  <Parameters>
+    <Parameter><Name></Name><Value>1</Value></Parameter>
+    <Parameter><Name>Power</Name><Value>2</Value></Parameter>
+    <Parameter><Name>Speed</Name><Value>3</Value></Parameter>
+  </Parameters>
+</Card>"#);
+        assert_eq!(card.unwrap().param_values.len(), 1);
+        assert_eq!(problems, vec!["card:6: <Name> must not be empty",
+                                  "card:8: card type has no parameter \"Speed\"",
+                                  "card: missing value for parameter \"Toughness\""]);
     }
 }
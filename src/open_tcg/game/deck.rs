@@ -23,15 +23,27 @@
 extern crate sxd_document;
 
 use std::cell::RefCell;
+use std::fmt;
 
 use super::card::CardInfo;
+use super::tcg::TCG;
 use std::collections::HashMap;
-use std::fs::File;
+use std::fs::{self, File};
+use std::time::{SystemTime, UNIX_EPOCH};
 use std::path::{Path, PathBuf};
 
-use self::sxd_document::Package;
+use self::sxd_document::{Package, QName};
+use self::sxd_document::dom::{Document, Element};
 use self::sxd_document::writer::format_document;
 
+use open_tcg::util::{files, xml};
+use open_tcg::util::error::{OpenTcgError, OpenTcgResult};
+use open_tcg::util::files::XmlFile;
+
+/// Extension of deck files. Decks are XML documents, but their own extension keeps other
+/// XML files in the deck directory, such as TCG definitions, from being listed as decks.
+pub const DECK_EXTENSION : &'static str = "tcgdeck";
+
 /// This structure defines an abstraction of the information
 /// associated with a subsection of a deck, such as main, side, etc.
 #[derive(Debug, Clone)]
@@ -59,6 +71,7 @@ impl DeckSectionInfo {
     }
 }
 
+#[derive(Clone)]
 pub struct DeckSection {
     /// Descriptor of the meta data associated with this deck section.
     pub info : DeckSectionInfo,
@@ -72,15 +85,110 @@ impl DeckSection {
         DeckSection { info : DeckSectionInfo::new(), cards : RefCell::new(HashMap::new()) }
     }
 
-    pub fn new_from_file() -> DeckSection {
+    /// Reads the cards of a `<Section>` element written by `Deck::write_to_file`.
+    ///
+    /// Cards that are not part of the given TCG are left out of the section
+    /// and recorded in `problems` instead.
+    pub fn new_from_element(info : &DeckSectionInfo, section_element : &Element, file : &XmlFile,
+                            tcg : &TCG, problems : &mut Vec<DeckLoadProblem>) -> OpenTcgResult<DeckSection> {
         let mut result = DeckSection::new();
+        result.info = info.clone();
+
+        let cards_name = QName::new("Cards");
+        let card_name = QName::new("Card");
+        let name_name = QName::new("Name");
+        let copies_name = QName::new("NumCopies");
+
+        for e in section_element.children() {
+            if let Some(cards_element) = e.element() {
+                if cards_element.name() != cards_name {
+                    continue;
+                }
+                for c in cards_element.children() {
+                    if let Some(card_element) = c.element() {
+                        if card_element.name() != card_name {
+                            continue;
+                        }
+                        let mut name = String::new();
+                        let mut copies = 0;
+                        for card_info in card_element.children() {
+                            if let Some(info_element) = card_info.element() {
+                                let element_name = info_element.name();
+                                if element_name == name_name {
+                                    name = xml::read_text_from_element(&info_element);
+                                } else if element_name == copies_name {
+                                    copies = xml::read_num_from_element(&info_element, file)?;
+                                }
+                            }
+                        }
+
+                        if tcg.cards.contains_key(&name) {
+                            *result.cards.borrow_mut().entry(name).or_insert(0) += copies;
+                        } else {
+                            problems.push(DeckLoadProblem::UnknownCard{section : info.name.clone(),
+                                card : name});
+                        }
+                    }
+                }
+            }
+        }
 
-        // TODO: fill this in
+        Ok(result)
+    }
+
+    /// Number of copies of the named card in this section.
+    pub fn copies_of(&self, name : &str) -> u32 {
+        self.cards.borrow().get(name).cloned().unwrap_or(0)
+    }
 
-        result
+    /// Total number of cards in this section.
+    pub fn size(&self) -> u32 {
+        self.cards.borrow().values().sum()
+    }
+
+    /// Removes one copy of the named card, dropping the card from the section
+    /// once no copies are left. Returns false if the section has no copy of it.
+    pub fn remove_card(&self, name : &str) -> bool {
+        let mut cards = self.cards.borrow_mut();
+        match cards.get_mut(name) {
+            Some(copies) if *copies > 1 => {
+                *copies -= 1;
+                return true;
+            },
+            Some(_) => (),
+            None => return false
+        }
+        cards.remove(name);
+        true
     }
 }
 
+/// Describes part of a deck file that could not be matched against the current TCG.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeckLoadProblem {
+    /// The file contains a section that the TCG does not define.
+    UnknownSection(String),
+
+    /// The file lists a card that is not in the TCG's card pool.
+    UnknownCard{section : String, card : String},
+
+    /// The file contains the same section more than once. Only the first one is read.
+    DuplicateSection(String)
+}
+
+impl fmt::Display for DeckLoadProblem {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DeckLoadProblem::UnknownSection(ref name) => write!(f, "Unknown deck section \"{}\"", name),
+            DeckLoadProblem::UnknownCard{ref section, ref card} =>
+                write!(f, "Unknown card \"{}\" in section \"{}\"", card, section),
+            DeckLoadProblem::DuplicateSection(ref name) =>
+                write!(f, "Deck section \"{}\" appears more than once, only the first is used", name)
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct Deck {
     pub sections : Vec<DeckSection>,
     pub name : String
@@ -91,10 +199,117 @@ impl Deck {
         Deck{sections : Vec::new(), name : String::new()}
     }
 
-    pub fn write_to_file(&self, filename : &PathBuf) {
+    /// Finds the deck files in the given directory, most recently modified first.
+    /// A directory that can't be read holds no decks.
+    pub fn find_in_directory(directory : &Path) -> Vec<PathBuf> {
+        let entries = match fs::read_dir(directory) {
+            Ok(entries) => entries,
+            Err(_) => return Vec::new()
+        };
+
+        let mut decks : Vec<(SystemTime, PathBuf)> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file() && path.extension().map(|e| e == DECK_EXTENSION).unwrap_or(false))
+            .map(|path| {
+                let modified = fs::metadata(&path).and_then(|m| m.modified()).unwrap_or(UNIX_EPOCH);
+                (modified, path)
+            })
+            .collect();
+        decks.sort_by(|a, b| b.cmp(a));
+        decks.into_iter().map(|(_, path)| path).collect()
+    }
+
+    /// Number of copies of the named card in all sections of the given group.
+    pub fn copies_in_group(&self, group : u32, name : &str) -> u32 {
+        self.sections.iter().filter(|s| s.info.group == group).map(|s| s.copies_of(name)).sum()
+    }
+
+    /// Moves one copy of the named card from one section to another.
+    /// Returns false, leaving both sections unchanged, if the source section
+    /// has no copy of the card.
+    pub fn move_card(&self, from : usize, to : usize, name : &str) -> bool {
+        if from == to || !self.sections[from].remove_card(name) {
+            return false;
+        }
+        *self.sections[to].cards.borrow_mut().entry(name.to_string()).or_insert(0) += 1;
+        true
+    }
+
+    /// Reads a deck previously written by `write_to_file`.
+    ///
+    /// Each section in the file is matched by name to the corresponding
+    /// `DeckSectionInfo` of the given TCG. Sections of the TCG that are missing from the
+    /// file are left empty. Anything that doesn't match the TCG is reported
+    /// in the returned list of problems rather than being dropped silently.
+    pub fn read_from_file(filename : &PathBuf, tcg : &TCG) -> OpenTcgResult<(Deck, Vec<DeckLoadProblem>)> {
+        let mut result = tcg.new_deck();
+
+        if let Some(stem) = filename.file_stem() {
+            result.name = stem.to_string_lossy().into_owned();
+        }
+
+        let file = files::document_from_file(filename)?;
+        let sections_root = file.root_element("Sections")?;
+        let (sections, problems) = Deck::read_sections(&sections_root, &file, tcg)?;
+        result.sections = sections;
+
+        Ok((result, problems))
+    }
+
+    /// Reads the sections of a deck from a `<Sections>` element, such as one created by `to_element`.
+    pub fn read_sections(sections_root : &Element, file : &XmlFile, tcg : &TCG)
+        -> OpenTcgResult<(Vec<DeckSection>, Vec<DeckLoadProblem>)> {
+        let mut result = tcg.new_deck();
+        let mut problems = Vec::new();
+        let mut read = Vec::new();
+
+        let section_name = QName::new("Section");
+        let name_name = QName::new("Name");
+
+        for e in sections_root.children() {
+            if let Some(element) = e.element() {
+                if element.name() != section_name {
+                    continue;
+                }
+                let mut name = String::new();
+                for section_info in element.children() {
+                    if let Some(name_element) = section_info.element() {
+                        if name_element.name() == name_name {
+                            name = xml::read_text_from_element(&name_element);
+                        }
+                    }
+                }
+
+                match result.sections.iter().position(|s| s.info.name == name) {
+                    Some(index) if read.contains(&index) =>
+                        problems.push(DeckLoadProblem::DuplicateSection(name)),
+                    Some(index) => {
+                        read.push(index);
+                        let info = result.sections[index].info.clone();
+                        result.sections[index] = DeckSection::new_from_element(&info, &element,
+                            &file, tcg, &mut problems)?;
+                    },
+                    None => problems.push(DeckLoadProblem::UnknownSection(name))
+                }
+            }
+        }
+
+        Ok((result.sections, problems))
+    }
+
+    pub fn write_to_file(&self, filename : &PathBuf) -> OpenTcgResult<()> {
         let package = Package::new();
         let doc = package.as_document();
 
+        let sections = self.to_element(&doc);
+        doc.root().append_child(sections);
+        let mut file = File::create(filename).map_err(|e| OpenTcgError::Io{path : filename.clone(), error : e})?;
+        format_document(&doc, &mut file).map_err(|e| OpenTcgError::Io{path : filename.clone(), error : e})
+    }
+
+    /// Creates a `<Sections>` element describing the cards in this deck.
+    pub fn to_element<'d>(&self, doc : &Document<'d>) -> Element<'d> {
         let sections = doc.create_element("Sections");
 
         for deck_section in self.sections.iter() {
@@ -123,9 +338,135 @@ impl Deck {
             section.append_child(section_cards);
             sections.append_child(section);
         }
-        doc.root().append_child(sections);
-        let mut file = File::create(filename).expect("Error writing file");
-        format_document(&doc, &mut file).ok().expect("Error writing document");
- 
+
+        sections
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+    use std::io::Write;
+
+    fn test_tcg() -> TCG {
+        let mut tcg = TCG::new();
+        tcg.card_limit = 3;
+        for name in ["Main", "Side"].iter() {
+            let mut info = DeckSectionInfo::new();
+            info.name = name.to_string();
+            info.max_size = 60;
+            tcg.sections.push(info);
+        }
+        for name in ["Goblin", "Knight"].iter() {
+            let mut card = CardInfo::new();
+            card.name = name.to_string();
+            tcg.cards.insert(card.name.clone(), card);
+        }
+        tcg
+    }
+
+    fn temp_path(name : &str) -> PathBuf {
+        env::temp_dir().join(format!("open_tcg_{}_{}.{}", name, process::id(), DECK_EXTENSION))
+    }
+
+    #[test]
+    fn round_trip() {
+        let tcg = test_tcg();
+        let deck = tcg.new_deck();
+        deck.sections[0].cards.borrow_mut().insert("Goblin".to_string(), 3);
+        deck.sections[0].cards.borrow_mut().insert("Knight".to_string(), 1);
+        deck.sections[1].cards.borrow_mut().insert("Knight".to_string(), 2);
+
+        let path = temp_path("round_trip");
+        deck.write_to_file(&path).unwrap();
+        let (read, problems) = Deck::read_from_file(&path, &tcg).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(problems.is_empty());
+        assert_eq!(read.sections.len(), deck.sections.len());
+        for (read_section, section) in read.sections.iter().zip(deck.sections.iter()) {
+            assert_eq!(read_section.info.name, section.info.name);
+            assert_eq!(*read_section.cards.borrow(), *section.cards.borrow());
+        }
+    }
+
+    #[test]
+    fn unknown_section_and_card() {
+        let tcg = test_tcg();
+        let path = temp_path("unknown");
+        let mut file = File::create(&path).unwrap();
+        file.write_all(br#"<?xml version="1.0"?>
+<Sections>
+  <Section>
+    <Name>Main</Name>
+    <Cards>
+      <Card><Name>Goblin</Name><NumCopies>2</NumCopies></Card>
+      <Card><Name>Dragon</Name><NumCopies>1</NumCopies></Card>
+    </Cards>
+  </Section>
+  <Section>
+    <Name>Extra</Name>
+    <Cards>
+      <Card><Name>Knight</Name><NumCopies>1</NumCopies></Card>
+    </Cards>
+  </Section>
+</Sections>"#).unwrap();
+        drop(file);
+
+        let (deck, problems) = Deck::read_from_file(&path, &tcg).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(problems, vec![
+            DeckLoadProblem::UnknownCard{section : "Main".to_string(), card : "Dragon".to_string()},
+            DeckLoadProblem::UnknownSection("Extra".to_string())]);
+        assert_eq!(deck.sections[0].copies_of("Goblin"), 2);
+        assert_eq!(deck.sections[0].size(), 2);
+        assert_eq!(deck.sections[1].size(), 0);
+    }
+
+    #[test]
+    fn duplicate_section() {
+        let tcg = test_tcg();
+        let path = temp_path("duplicate");
+        let mut file = File::create(&path).unwrap();
+        file.write_all(br#"<?xml version="1.0"?>
+<Sections>
+  <Section>
+    <Name>Main</Name>
+    <Cards>
+      <Card><Name>Goblin</Name><NumCopies>2</NumCopies></Card>
+    </Cards>
+  </Section>
+  <Section>
+    <Name>Main</Name>
+    <Cards>
+      <Card><Name>Knight</Name><NumCopies>1</NumCopies></Card>
+    </Cards>
+  </Section>
+</Sections>"#).unwrap();
+        drop(file);
+
+        let (deck, problems) = Deck::read_from_file(&path, &tcg).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(problems, vec![DeckLoadProblem::DuplicateSection("Main".to_string())]);
+        assert_eq!(deck.sections[0].copies_of("Goblin"), 2);
+        assert_eq!(deck.sections[0].copies_of("Knight"), 0);
+    }
+
+    #[test]
+    fn finds_only_deck_files() {
+        let directory = env::temp_dir().join(format!("open_tcg_decks_{}", process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let deck = test_tcg().new_deck();
+        deck.write_to_file(&directory.join(format!("goblins.{}", DECK_EXTENSION))).unwrap();
+        File::create(directory.join("example.xml")).unwrap();
+
+        let found = Deck::find_in_directory(&directory);
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(found, vec![directory.join(format!("goblins.{}", DECK_EXTENSION))]);
     }
 }
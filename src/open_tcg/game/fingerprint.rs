@@ -0,0 +1,429 @@
+// MIT License
+
+// Copyright (c) 2016 Johan Burke
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+extern crate sha2;
+extern crate sxd_document;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use self::sha2::{Digest, Sha256};
+use self::sxd_document::dom::{Document, Element};
+
+use super::card::{CardInfo, CardType};
+use super::state::{ZoneOwner, ZoneRole, ZoneVisibility};
+use super::tcg::TCG;
+use open_tcg::util::{hex, xml};
+use open_tcg::util::error::OpenTcgResult;
+use open_tcg::util::files::XmlFile;
+
+/// A hash of the contents of a card, card type or TCG that only changes when
+/// something affecting play changes, so that two players can compare their
+/// definitions without sending them to each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Fingerprint(pub [u8; 32]);
+
+impl Fingerprint {
+    pub fn to_hex(&self) -> String {
+        hex::to_hex(&self.0)
+    }
+
+    pub fn from_hex(text : &str) -> Option<Fingerprint> {
+        match hex::from_hex(text) {
+            Some(ref bytes) if bytes.len() == 32 => {
+                let mut result = [0; 32];
+                result.copy_from_slice(bytes);
+                Some(Fingerprint(result))
+            },
+            _ => None
+        }
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_hex())
+    }
+}
+
+/// Feeds values into a hash so that different sequences of values never
+/// produce the same input, e.g. "ab", "c" and "a", "bc".
+struct FingerprintBuilder {
+    hasher : Sha256
+}
+
+impl FingerprintBuilder {
+    fn new(kind : &str) -> FingerprintBuilder {
+        let mut result = FingerprintBuilder{hasher : Sha256::default()};
+        result.text(kind);
+        result
+    }
+
+    fn num(&mut self, value : u64) {
+        let bytes : Vec<u8> = (0..8).rev().map(|i| (value >> (i * 8)) as u8).collect();
+        self.hasher.input(&bytes);
+    }
+
+    fn text(&mut self, value : &str) {
+        self.num(value.len() as u64);
+        self.hasher.input(value.as_bytes());
+    }
+
+    fn fingerprint(&mut self, value : &Fingerprint) {
+        self.hasher.input(&value.0);
+    }
+
+    fn finish(self) -> Fingerprint {
+        let mut result = [0; 32];
+        result.copy_from_slice(self.hasher.result().as_slice());
+        Fingerprint(result)
+    }
+}
+
+impl CardInfo {
+    /// Covers the card's name, type, set, text and parameter values.
+    pub fn fingerprint(&self) -> Fingerprint {
+        let mut builder = FingerprintBuilder::new("Card");
+        builder.text(&self.name);
+        builder.text(&self.card_type.name);
+        builder.text(&self.set_name);
+        builder.text(&self.set_code);
+        builder.text(&self.text);
+
+        let mut params : Vec<(&String, &i32)> = self.param_values.iter().collect();
+        params.sort();
+        builder.num(params.len() as u64);
+        for (name, &value) in params {
+            builder.text(name);
+            builder.num(value as i64 as u64);
+        }
+        builder.finish()
+    }
+}
+
+impl CardType {
+    /// Covers the type's name, parent, aliases and parameter names.
+    pub fn fingerprint(&self) -> Fingerprint {
+        let mut builder = FingerprintBuilder::new("CardType");
+        builder.text(&self.name);
+        match self.parent {
+            Some(ref parent) => {
+                builder.num(1);
+                builder.text(parent);
+            },
+            None => builder.num(0)
+        }
+
+        let mut aliases : Vec<&String> = self.aliases.iter().collect();
+        aliases.sort();
+        builder.num(aliases.len() as u64);
+        for alias in aliases {
+            builder.text(alias);
+        }
+
+        builder.num(self.param_names.len() as u64);
+        for name in self.param_names.iter() {
+            builder.text(name);
+        }
+        builder.finish()
+    }
+}
+
+/// Fingerprints of every part of a TCG, from which the differences between
+/// two players' definitions of the TCG can be found.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TcgFingerprint {
+    /// Covers everything below
+    pub whole : Fingerprint,
+
+    /// Covers the TCG's name, card limit, deck sections and zones
+    pub rules : Fingerprint,
+
+    pub card_types : BTreeMap<String, Fingerprint>,
+    pub cards : BTreeMap<String, Fingerprint>
+}
+
+impl TcgFingerprint {
+    pub fn new(tcg : &TCG) -> TcgFingerprint {
+        let mut builder = FingerprintBuilder::new("Rules");
+        builder.text(tcg.get_name());
+        builder.num(tcg.card_limit as u64);
+
+        builder.num(tcg.sections.len() as u64);
+        for section in tcg.sections.iter() {
+            builder.text(&section.name);
+            builder.num(section.group as u64);
+            builder.num(section.min_size as u64);
+            builder.num(section.max_size as u64);
+        }
+
+        builder.num(tcg.zones.len() as u64);
+        for zone in tcg.zones.iter() {
+            builder.text(&zone.name);
+            builder.num(match zone.owner {
+                ZoneOwner::Player => 0,
+                ZoneOwner::Shared => 1
+            });
+            builder.num(match zone.visibility {
+                ZoneVisibility::Public => 0,
+                ZoneVisibility::Private => 1,
+                ZoneVisibility::OwnerOnly => 2
+            });
+            builder.num(zone.ordered as u64);
+            builder.num(zone.capacity as u64);
+            builder.num(zone.starting_group.map_or(0, |g| g as u64 + 1));
+            builder.num(match zone.role {
+                None => 0,
+                Some(ZoneRole::DrawPile) => 1,
+                Some(ZoneRole::Hand) => 2
+            });
+        }
+
+        let card_types = tcg.card_types.values().map(|t| (t.name.clone(), t.fingerprint())).collect();
+        let cards = tcg.cards.values().map(|c| (c.name.clone(), c.fingerprint())).collect();
+        TcgFingerprint::new_from_parts(builder.finish(), card_types, cards)
+    }
+
+    fn new_from_parts(rules : Fingerprint, card_types : BTreeMap<String, Fingerprint>,
+                      cards : BTreeMap<String, Fingerprint>) -> TcgFingerprint {
+        let mut builder = FingerprintBuilder::new("TCG");
+        builder.fingerprint(&rules);
+        for map in [&card_types, &cards].iter() {
+            builder.num(map.len() as u64);
+            for (name, fingerprint) in map.iter() {
+                builder.text(name);
+                builder.fingerprint(fingerprint);
+            }
+        }
+        TcgFingerprint{whole : builder.finish(), rules : rules, card_types : card_types, cards : cards}
+    }
+
+    /// Lists the differences between our fingerprints and the other player's.
+    pub fn diff(&self, theirs : &TcgFingerprint) -> FingerprintDiff {
+        FingerprintDiff{rules_differ : self.rules != theirs.rules,
+            card_types : diff_maps(&self.card_types, &theirs.card_types),
+            cards : diff_maps(&self.cards, &theirs.cards)}
+    }
+
+    /// Creates an element listing every fingerprint, such as
+    /// `<Fingerprints><Rules>...</Rules><CardTypes>...</CardTypes><Cards>...</Cards></Fingerprints>`.
+    pub fn to_element<'d>(&self, doc : &Document<'d>) -> Element<'d> {
+        let element = doc.create_element("Fingerprints");
+        xml::append_text_element(doc, &element, "Rules", &self.rules.to_hex());
+        for &(list_name, entry_name, map) in [("CardTypes", "CardType", &self.card_types),
+                                              ("Cards", "Card", &self.cards)].iter() {
+            let list = doc.create_element(list_name);
+            for (name, fingerprint) in map.iter() {
+                let entry = doc.create_element(entry_name);
+                xml::append_text_element(doc, &entry, "Name", name);
+                xml::append_text_element(doc, &entry, "Hash", &fingerprint.to_hex());
+                list.append_child(entry);
+            }
+            element.append_child(list);
+        }
+        element
+    }
+
+    /// Reads the fingerprints from an element created by `to_element`.
+    pub fn new_from_element(element : &Element, file : &XmlFile) -> OpenTcgResult<TcgFingerprint> {
+        let read_hash = |hash_element : &Element| -> OpenTcgResult<Fingerprint> {
+            Fingerprint::from_hex(&xml::read_text_from_element(hash_element))
+                .ok_or_else(|| xml::invalid_value(hash_element, file))
+        };
+        let read_map = |list_name : &str| -> OpenTcgResult<BTreeMap<String, Fingerprint>> {
+            let mut result = BTreeMap::new();
+            for e in xml::required_child(element, list_name, file)?.children() {
+                if let Some(entry) = e.element() {
+                    let name = xml::read_text_from_element(&xml::required_child(&entry, "Name", file)?);
+                    let hash = read_hash(&xml::required_child(&entry, "Hash", file)?)?;
+                    result.insert(name, hash);
+                }
+            }
+            Ok(result)
+        };
+
+        let rules = read_hash(&xml::required_child(element, "Rules", file)?)?;
+        Ok(TcgFingerprint::new_from_parts(rules, read_map("CardTypes")?, read_map("Cards")?))
+    }
+}
+
+/// How a card or card type differs between two players.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Difference {
+    /// Only we have it
+    OnlyOurs,
+
+    /// Only the other player has it
+    OnlyTheirs,
+
+    /// Both have it, but with different contents
+    Changed
+}
+
+fn diff_maps(ours : &BTreeMap<String, Fingerprint>, theirs : &BTreeMap<String, Fingerprint>) -> Vec<(String, Difference)> {
+    let names : BTreeSet<&String> = ours.keys().chain(theirs.keys()).collect();
+    let mut result = Vec::new();
+    for name in names {
+        let difference = match (ours.get(name), theirs.get(name)) {
+            (Some(a), Some(b)) if a != b => Difference::Changed,
+            (Some(_), None) => Difference::OnlyOurs,
+            (None, Some(_)) => Difference::OnlyTheirs,
+            _ => continue
+        };
+        result.push((name.clone(), difference));
+    }
+    result
+}
+
+/// The differences between two players' definitions of a TCG, in order of name.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FingerprintDiff {
+    /// Whether the name, card limit, deck sections or zones differ
+    pub rules_differ : bool,
+
+    pub card_types : Vec<(String, Difference)>,
+    pub cards : Vec<(String, Difference)>
+}
+
+impl FingerprintDiff {
+    pub fn is_empty(&self) -> bool {
+        !self.rules_differ && self.card_types.is_empty() && self.cards.is_empty()
+    }
+}
+
+impl fmt::Display for FingerprintDiff {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "no differences");
+        }
+
+        let mut lines = Vec::new();
+        if self.rules_differ {
+            lines.push("the deck sections, card limit or zones differ".to_string());
+        }
+        for &(kind, entries) in [("card type", &self.card_types), ("card", &self.cards)].iter() {
+            for &(ref name, difference) in entries.iter() {
+                lines.push(match difference {
+                    Difference::OnlyOurs => format!("{} \"{}\" is missing from the other player's files", kind, name),
+                    Difference::OnlyTheirs => format!("{} \"{}\" is missing from your files", kind, name),
+                    Difference::Changed => format!("{} \"{}\" differs", kind, name)
+                });
+            }
+        }
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::deck::DeckSectionInfo;
+
+    fn card_type(name : &str, params : &[&str]) -> CardType {
+        let mut result = CardType::new();
+        result.name = name.to_string();
+        result.param_names = params.iter().map(|p| p.to_string()).collect();
+        result
+    }
+
+    fn card(name : &str, type_name : &str, params : &[(&str, i32)]) -> CardInfo {
+        let mut result = CardInfo::new();
+        result.name = name.to_string();
+        result.card_type = card_type(type_name, &[]);
+        result.text = format!("{} text", name);
+        for &(param, value) in params.iter() {
+            result.param_values.insert(param.to_string(), value);
+        }
+        result
+    }
+
+    /// A TCG with the given cards and card types, added in the order given.
+    fn test_tcg(card_types : Vec<CardType>, cards : Vec<CardInfo>) -> TCG {
+        let mut tcg = TCG::new();
+        tcg.card_limit = 3;
+        let mut section = DeckSectionInfo::new();
+        section.name = "Main".to_string();
+        section.max_size = 60;
+        tcg.sections.push(section);
+        for card_type in card_types {
+            tcg.card_types.insert(card_type.name.clone(), card_type);
+        }
+        for card in cards {
+            tcg.cards.insert(card.name.clone(), card);
+        }
+        tcg
+    }
+
+    fn cards() -> Vec<CardInfo> {
+        (0..20).map(|i| card(&format!("Card {}", i), "Creature",
+            &[("Cost", i), ("Power", i * 2), ("Toughness", -i), ("Speed", 1)])).collect()
+    }
+
+    #[test]
+    fn stable_across_orders() {
+        let types = vec![card_type("Creature", &["Cost", "Power"]), card_type("Spell", &["Cost"])];
+        let first = test_tcg(types.clone(), cards());
+        let second = test_tcg(types.into_iter().rev().collect(), cards().into_iter().rev().collect());
+        assert_eq!(TcgFingerprint::new(&first), TcgFingerprint::new(&second));
+
+        let ours = TcgFingerprint::new(&first);
+        assert!(ours.diff(&ours).is_empty());
+        assert_eq!(Fingerprint::from_hex(&ours.whole.to_hex()), Some(ours.whole));
+    }
+
+    #[test]
+    fn ignores_ui_fields() {
+        let tcg = test_tcg(vec![card_type("Creature", &["Cost"])], cards());
+        let ours = TcgFingerprint::new(&tcg);
+
+        let mut changed = test_tcg(vec![card_type("Creature", &["Cost"])], cards());
+        changed.sections[0].rows = 4;
+        changed.sections[0].columns = 9;
+        assert_eq!(TcgFingerprint::new(&changed), ours);
+
+        changed.card_limit = 4;
+        let theirs = TcgFingerprint::new(&changed);
+        assert!(theirs.whole != ours.whole);
+        assert_eq!(ours.diff(&theirs), FingerprintDiff{rules_differ : true, card_types : Vec::new(), cards : Vec::new()});
+    }
+
+    #[test]
+    fn reports_differences() {
+        let ours = TcgFingerprint::new(&test_tcg(
+            vec![card_type("Creature", &["Cost"]), card_type("Spell", &[]), card_type("Land", &[])],
+            vec![card("Goblin", "Creature", &[("Cost", 1)]), card("Knight", "Creature", &[("Cost", 2)]),
+                 card("Bolt", "Spell", &[])]));
+        let theirs = TcgFingerprint::new(&test_tcg(
+            vec![card_type("Creature", &["Cost", "Power"]), card_type("Spell", &[]), card_type("Artifact", &[])],
+            vec![card("Goblin", "Creature", &[("Cost", 1)]), card("Knight", "Creature", &[("Cost", 3)]),
+                 card("Wizard", "Creature", &[("Cost", 4)])]));
+
+        let diff = ours.diff(&theirs);
+        assert!(!diff.rules_differ);
+        assert_eq!(diff.card_types, vec![("Artifact".to_string(), Difference::OnlyTheirs),
+                                         ("Creature".to_string(), Difference::Changed),
+                                         ("Land".to_string(), Difference::OnlyOurs)]);
+        assert_eq!(diff.cards, vec![("Bolt".to_string(), Difference::OnlyOurs),
+                                    ("Knight".to_string(), Difference::Changed),
+                                    ("Wizard".to_string(), Difference::OnlyTheirs)]);
+        assert_eq!(diff.to_string().lines().next(), Some("card type \"Artifact\" is missing from your files"));
+    }
+}
@@ -0,0 +1,311 @@
+// MIT License
+
+// Copyright (c) 2016 Johan Burke
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+use super::deck::Deck;
+
+/// A single change to a deck that can be undone.
+///
+/// Edits only change the deck; checking them against the rules of the TCG
+/// is left to whoever performs them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeckEdit {
+    /// One copy of a card is added to a section.
+    Add{section : usize, card : String},
+
+    /// One copy of a card is removed from a section.
+    Remove{section : usize, card : String},
+
+    /// One copy of a card is moved from one section to another.
+    Move{from : usize, to : usize, card : String},
+
+    /// The deck is renamed.
+    Rename{from : String, to : String},
+
+    /// Several edits that are performed, undone and redone as a single step,
+    /// such as clearing a section.
+    Group(Vec<DeckEdit>)
+}
+
+impl DeckEdit {
+    /// Applies this edit to the deck. Returns false, leaving the deck unchanged,
+    /// if the edit can't be applied, e.g. because the card to remove isn't in the section.
+    pub fn apply(&self, deck : &mut Deck) -> bool {
+        match *self {
+            DeckEdit::Add{section, ref card} => {
+                match deck.sections.get(section) {
+                    Some(s) => {
+                        *s.cards.borrow_mut().entry(card.clone()).or_insert(0) += 1;
+                        true
+                    },
+                    None => false
+                }
+            },
+            DeckEdit::Remove{section, ref card} => {
+                deck.sections.get(section).map(|s| s.remove_card(card)).unwrap_or(false)
+            },
+            DeckEdit::Move{from, to, ref card} => {
+                from < deck.sections.len() && to < deck.sections.len() && deck.move_card(from, to, card)
+            },
+            DeckEdit::Rename{ref to, ..} => {
+                deck.name = to.clone();
+                true
+            },
+            DeckEdit::Group(ref edits) => {
+                for (i, edit) in edits.iter().enumerate() {
+                    if !edit.apply(deck) {
+                        // roll back so that the group is applied entirely or not at all
+                        for applied in edits[..i].iter().rev() {
+                            applied.inverse().apply(deck);
+                        }
+                        return false;
+                    }
+                }
+                true
+            }
+        }
+    }
+
+    /// The edit that reverts this one.
+    pub fn inverse(&self) -> DeckEdit {
+        match *self {
+            DeckEdit::Add{section, ref card} => DeckEdit::Remove{section : section, card : card.clone()},
+            DeckEdit::Remove{section, ref card} => DeckEdit::Add{section : section, card : card.clone()},
+            DeckEdit::Move{from, to, ref card} => DeckEdit::Move{from : to, to : from, card : card.clone()},
+            DeckEdit::Rename{ref from, ref to} => DeckEdit::Rename{from : to.clone(), to : from.clone()},
+            DeckEdit::Group(ref edits) => DeckEdit::Group(edits.iter().rev().map(|e| e.inverse()).collect())
+        }
+    }
+}
+
+/// Edits made to a deck, which can be undone and redone without limit.
+pub struct DeckHistory {
+    undo_stack : Vec<DeckEdit>,
+    redo_stack : Vec<DeckEdit>,
+
+    /// Number of edits applied when the deck was last saved, or None if
+    /// that state can no longer be reached by undoing or redoing
+    saved_at : Option<usize>
+}
+
+impl DeckHistory {
+    pub fn new() -> DeckHistory {
+        DeckHistory{undo_stack : Vec::new(), redo_stack : Vec::new(), saved_at : Some(0)}
+    }
+
+    /// Applies an edit to the deck and records it. Edits that can't be applied
+    /// aren't recorded. Performing an edit discards the edits that could be redone.
+    pub fn perform(&mut self, edit : DeckEdit, deck : &mut Deck) -> bool {
+        if !edit.apply(deck) {
+            return false;
+        }
+        // the saved state is lost along with the edits that could be redone
+        if self.saved_at.map(|saved| saved > self.undo_stack.len()).unwrap_or(false) {
+            self.saved_at = None;
+        }
+        self.undo_stack.push(edit);
+        self.redo_stack.clear();
+        true
+    }
+
+    /// Reverts the last edit, returning it, or returns None if there is nothing to undo.
+    /// An edit that can't be reverted, e.g. because the deck was changed without
+    /// going through the history, is left to be undone and None is returned.
+    pub fn undo(&mut self, deck : &mut Deck) -> Option<DeckEdit> {
+        let edit = match self.undo_stack.last() {
+            Some(edit) => edit.clone(),
+            None => return None
+        };
+        if !edit.inverse().apply(deck) {
+            return None;
+        }
+        self.undo_stack.pop();
+        self.redo_stack.push(edit.clone());
+        Some(edit)
+    }
+
+    /// Applies the last undone edit again, returning it, or returns None if there is nothing to redo.
+    /// An edit that can't be applied again is left to be redone and None is returned.
+    pub fn redo(&mut self, deck : &mut Deck) -> Option<DeckEdit> {
+        let edit = match self.redo_stack.last() {
+            Some(edit) => edit.clone(),
+            None => return None
+        };
+        if !edit.apply(deck) {
+            return None;
+        }
+        self.redo_stack.pop();
+        self.undo_stack.push(edit.clone());
+        Some(edit)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    /// Records that the deck was saved in its current state.
+    pub fn mark_saved(&mut self) {
+        self.saved_at = Some(self.undo_stack.len());
+    }
+
+    /// Whether the deck has changed since it was last saved.
+    pub fn is_modified(&self) -> bool {
+        self.saved_at != Some(self.undo_stack.len())
+    }
+
+    /// Forgets every edit, e.g. when another deck is opened. The deck counts as saved afterwards.
+    pub fn clear(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.saved_at = Some(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::deck::DeckSection;
+
+    fn test_deck() -> Deck {
+        let mut deck = Deck::new();
+        deck.sections.push(DeckSection::new());
+        deck.sections.push(DeckSection::new());
+        deck
+    }
+
+    fn copies(deck : &Deck, section : usize, card : &str) -> u32 {
+        deck.sections[section].cards.borrow().get(card).cloned().unwrap_or(0)
+    }
+
+    fn add(section : usize, card : &str) -> DeckEdit {
+        DeckEdit::Add{section : section, card : card.to_string()}
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let mut deck = test_deck();
+        let mut history = DeckHistory::new();
+        assert!(!history.can_undo() && !history.can_redo());
+        assert_eq!(history.undo(&mut deck), None);
+
+        assert!(history.perform(add(0, "Goblin"), &mut deck));
+        assert!(history.perform(DeckEdit::Move{from : 0, to : 1, card : "Goblin".to_string()}, &mut deck));
+        assert_eq!((copies(&deck, 0, "Goblin"), copies(&deck, 1, "Goblin")), (0, 1));
+
+        assert_eq!(history.undo(&mut deck), Some(DeckEdit::Move{from : 0, to : 1, card : "Goblin".to_string()}));
+        assert_eq!((copies(&deck, 0, "Goblin"), copies(&deck, 1, "Goblin")), (1, 0));
+        assert_eq!(history.undo(&mut deck), Some(add(0, "Goblin")));
+        assert_eq!(copies(&deck, 0, "Goblin"), 0);
+        assert!(!history.can_undo() && history.can_redo());
+
+        assert_eq!(history.redo(&mut deck), Some(add(0, "Goblin")));
+        assert_eq!(copies(&deck, 0, "Goblin"), 1);
+        assert!(history.can_undo() && history.can_redo());
+    }
+
+    #[test]
+    fn perform_clears_redo() {
+        let mut deck = test_deck();
+        let mut history = DeckHistory::new();
+        history.perform(add(0, "Goblin"), &mut deck);
+        history.undo(&mut deck);
+        assert!(history.can_redo());
+
+        history.perform(add(0, "Knight"), &mut deck);
+        assert!(!history.can_redo());
+        assert_eq!(history.redo(&mut deck), None);
+
+        // edits that can't be applied aren't recorded
+        assert!(!history.perform(DeckEdit::Remove{section : 0, card : "Goblin".to_string()}, &mut deck));
+        assert_eq!(history.undo(&mut deck), Some(add(0, "Knight")));
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn groups_are_atomic() {
+        let mut deck = test_deck();
+        let mut history = DeckHistory::new();
+        let group = DeckEdit::Group(vec![add(0, "Goblin"), add(1, "Knight"),
+            DeckEdit::Remove{section : 0, card : "Wizard".to_string()}]);
+        assert!(!history.perform(group, &mut deck));
+        assert_eq!((copies(&deck, 0, "Goblin"), copies(&deck, 1, "Knight")), (0, 0));
+
+        let group = DeckEdit::Group(vec![add(0, "Goblin"), add(1, "Knight")]);
+        assert!(history.perform(group, &mut deck));
+        history.undo(&mut deck);
+        assert_eq!((copies(&deck, 0, "Goblin"), copies(&deck, 1, "Knight")), (0, 0));
+        history.redo(&mut deck);
+        assert_eq!((copies(&deck, 0, "Goblin"), copies(&deck, 1, "Knight")), (1, 1));
+    }
+
+    #[test]
+    fn failed_undo_keeps_the_edit() {
+        let mut deck = test_deck();
+        let mut history = DeckHistory::new();
+        history.perform(add(0, "Goblin"), &mut deck);
+
+        // the card is removed behind the history's back
+        deck.sections[0].cards.borrow_mut().clear();
+        assert_eq!(history.undo(&mut deck), None);
+        assert!(history.can_undo() && !history.can_redo());
+
+        deck.sections[0].cards.borrow_mut().insert("Goblin".to_string(), 1);
+        assert_eq!(history.undo(&mut deck), Some(add(0, "Goblin")));
+
+        // and the same for redoing into a section that no longer exists
+        deck.sections.clear();
+        assert_eq!(history.redo(&mut deck), None);
+        assert!(history.can_redo() && !history.can_undo());
+    }
+
+    #[test]
+    fn modified_since_saved() {
+        let mut deck = test_deck();
+        let mut history = DeckHistory::new();
+        assert!(!history.is_modified());
+
+        history.perform(add(0, "Goblin"), &mut deck);
+        assert!(history.is_modified());
+        history.mark_saved();
+        assert!(!history.is_modified());
+
+        history.perform(add(0, "Knight"), &mut deck);
+        history.undo(&mut deck);
+        assert!(!history.is_modified());
+        history.undo(&mut deck);
+        assert!(history.is_modified());
+        history.redo(&mut deck);
+        assert!(!history.is_modified());
+
+        // undoing past the save and then editing makes the saved state unreachable
+        history.undo(&mut deck);
+        history.perform(add(1, "Wizard"), &mut deck);
+        assert!(history.is_modified());
+        history.undo(&mut deck);
+        assert!(history.is_modified());
+
+        history.clear();
+        assert!(!history.is_modified() && !history.can_undo());
+    }
+}
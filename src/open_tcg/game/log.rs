@@ -0,0 +1,525 @@
+// MIT License
+
+// Copyright (c) 2016 Johan Burke
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+extern crate sxd_document;
+
+use std::fmt;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use self::sxd_document::Package;
+use self::sxd_document::writer::format_document;
+
+use super::action::Action;
+use super::deck::{Deck, DeckLoadProblem};
+use super::random::{Commitment, RandomError, Secret, ShuffleRecord, JointRandom};
+use super::state::{CardId, GameError, GameState, PlayerId, ZoneId};
+use super::tcg::TCG;
+use open_tcg::util::{files, xml};
+use open_tcg::util::error::{OpenTcgError, OpenTcgResult};
+
+/// Extension of game log files, which are saved alongside deck files.
+pub const LOG_EXTENSION : &'static str = "tcglog";
+
+/// A record of everything that happened in a game.
+///
+/// Together with the TCG, a log holds everything needed to replay its game:
+/// the decks of both players, the secrets that seeded their shuffles and
+/// every action taken. It also holds what is needed to check the shuffles
+/// with `verify_shuffles`.
+///
+/// The secrets give away the order of the players' decks, so they're only
+/// written to the log file once the game has ended.
+#[derive(Clone)]
+pub struct GameLog {
+    /// Name of the TCG the game was played with
+    pub tcg_name : String,
+
+    /// Time the game started, in seconds since the Unix epoch
+    pub started : u64,
+
+    pub player_names : Vec<String>,
+    pub decks : Vec<Deck>,
+
+    /// What each player committed to before the game
+    pub commitments : Vec<Commitment>,
+
+    /// The secret of each player, which seeds the shuffles of their zones.
+    /// Players only reveal their secret to each other once the game is over,
+    /// and it is only written to the log file then.
+    pub secrets : Vec<Secret>,
+
+    pub actions : Vec<Action>,
+
+    /// Order of the cards after each `Shuffle` action, top card last
+    pub shuffle_orders : Vec<Vec<CardId>>
+}
+
+impl GameLog {
+    pub fn new(tcg : &TCG, started : u64, players : [(&str, &Deck); 2], secrets : [Secret; 2]) -> GameLog {
+        GameLog{tcg_name : tcg.get_name().clone(), started : started,
+            player_names : players.iter().map(|&(name, _)| name.to_string()).collect(),
+            decks : players.iter().map(|&(_, deck)| deck.clone()).collect(),
+            commitments : secrets.iter().map(|s| s.commitment()).collect(), secrets : secrets.to_vec(),
+            actions : Vec::new(), shuffle_orders : Vec::new()}
+    }
+
+    /// Default location for the log of a game started at the given time,
+    /// in the same directory as the deck files.
+    pub fn default_path(deck_directory : &Path, started : u64) -> PathBuf {
+        deck_directory.join(format!("game-{}.{}", started, LOG_EXTENSION))
+    }
+
+    /// Creates the state of the game before any action was taken.
+    pub fn initial_state(&self, tcg : &TCG) -> GameState {
+        let mut state = GameState::new_from_decks(tcg, &self.decks[0], &self.decks[1],
+            [self.secrets[0], self.secrets[1]]);
+        for (player, name) in state.players.iter_mut().zip(self.player_names.iter()) {
+            player.name = name.clone();
+        }
+        state
+    }
+
+    /// The winner of the game, if it has ended with one.
+    pub fn winner(&self) -> Option<PlayerId> {
+        for action in self.actions.iter().rev() {
+            if let Action::EndGame{winner} = *action {
+                return winner;
+            }
+        }
+        None
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.actions.iter().any(|a| match *a {
+            Action::EndGame{..} => true,
+            _ => false
+        })
+    }
+
+    /// Replays the game, checking every shuffle against the secrets the players
+    /// committed to: the shuffling player's secret must match their commitment,
+    /// and the logged order of the cards must be the one the agreed seed gives.
+    pub fn verify_shuffles(&self, tcg : &TCG) -> Result<(), VerifyError> {
+        let mut state = self.initial_state(tcg);
+        let mut shuffles = self.shuffle_orders.iter().enumerate();
+
+        for (index, action) in self.actions.iter().enumerate() {
+            let before = match *action {
+                Action::Shuffle{zone, ..} => state.zone(zone).map(|z| z.cards.clone()),
+                _ => None
+            };
+            action.apply(&mut state).map_err(|e| VerifyError::Action{index : index, error : e})?;
+
+            if let (Action::Shuffle{player, contribution, ..}, Some(before)) = (action, before) {
+                let (shuffle, after) = match shuffles.next() {
+                    Some(order) => order,
+                    None => return Err(VerifyError::Shuffle{index : self.shuffle_orders.len(),
+                        error : RandomError::OutOfOrder})
+                };
+                // the shuffle was applied, so the player exists
+                let mut record = ShuffleRecord{commitment : self.commitments[*player], contribution : *contribution,
+                    revealed : None};
+                record.reveal(self.secrets[*player])
+                    .and_then(|_| record.verify(&before, after))
+                    .map_err(|e| VerifyError::Shuffle{index : shuffle, error : e})?;
+            }
+        }
+        match shuffles.next() {
+            // an order was logged for a shuffle that never happened
+            Some((shuffle, _)) => Err(VerifyError::Shuffle{index : shuffle, error : RandomError::OutOfOrder}),
+            None => Ok(())
+        }
+    }
+
+    pub fn write_to_file(&self, filename : &PathBuf) -> OpenTcgResult<()> {
+        let package = Package::new();
+        let doc = package.as_document();
+
+        let root = doc.create_element("GameLog");
+        xml::append_text_element(&doc, &root, "TCG", &self.tcg_name);
+        xml::append_text_element(&doc, &root, "Started", &self.started.to_string());
+
+        let players = doc.create_element("Players");
+        for (i, (name, deck)) in self.player_names.iter().zip(self.decks.iter()).enumerate() {
+            let player = doc.create_element("Player");
+            xml::append_text_element(&doc, &player, "Name", name);
+            xml::append_text_element(&doc, &player, "DeckName", &deck.name);
+            xml::append_text_element(&doc, &player, "Commitment", &self.commitments[i].to_hex());
+            if self.is_finished() {
+                xml::append_text_element(&doc, &player, "Secret", &self.secrets[i].to_hex());
+            }
+            player.append_child(deck.to_element(&doc));
+            players.append_child(player);
+        }
+        root.append_child(players);
+
+        let actions = doc.create_element("Actions");
+        for action in self.actions.iter() {
+            actions.append_child(action.to_element(&doc));
+        }
+        root.append_child(actions);
+
+        let shuffles = doc.create_element("ShuffleOrders");
+        for order in self.shuffle_orders.iter() {
+            let cards : Vec<String> = order.iter().map(|card| card.to_string()).collect();
+            xml::append_text_element(&doc, &shuffles, "Order", &cards.join(" "));
+        }
+        root.append_child(shuffles);
+        doc.root().append_child(root);
+
+        let mut file = File::create(filename).map_err(|e| OpenTcgError::Io{path : filename.clone(), error : e})?;
+        format_document(&doc, &mut file).map_err(|e| OpenTcgError::Io{path : filename.clone(), error : e})
+    }
+
+    /// Reads a log written by `write_to_file`. The decks it contains are matched
+    /// against the given TCG in the same way as deck files. Logs of games that
+    /// hadn't ended when they were written lack the players' secrets, so they
+    /// can't be read.
+    pub fn read_from_file(filename : &PathBuf, tcg : &TCG) -> OpenTcgResult<(GameLog, Vec<DeckLoadProblem>)> {
+        let file = files::document_from_file(filename)?;
+        let root = file.root_element("GameLog")?;
+
+        let mut problems = Vec::new();
+        let mut result = GameLog{tcg_name : String::new(), started : 0, player_names : Vec::new(),
+            decks : Vec::new(), commitments : Vec::new(), secrets : Vec::new(), actions : Vec::new(),
+            shuffle_orders : Vec::new()};
+
+        result.tcg_name = xml::read_text_from_element(&xml::required_child(&root, "TCG", &file)?);
+        result.started = xml::read_parsed_from_element(&xml::required_child(&root, "Started", &file)?, &file)?;
+
+        let players = xml::required_child(&root, "Players", &file)?;
+        for p in players.children() {
+            if let Some(player) = p.element() {
+                let name = xml::read_text_from_element(&xml::required_child(&player, "Name", &file)?);
+                let commitment_element = xml::required_child(&player, "Commitment", &file)?;
+                let commitment = Commitment::from_hex(&xml::read_text_from_element(&commitment_element))
+                    .ok_or_else(|| xml::invalid_value(&commitment_element, &file))?;
+                let secret_element = xml::required_child(&player, "Secret", &file)?;
+                let secret = Secret::from_hex(&xml::read_text_from_element(&secret_element))
+                    .ok_or_else(|| xml::invalid_value(&secret_element, &file))?;
+                let sections = xml::required_child(&player, "Sections", &file)?;
+                let (sections, mut deck_problems) = Deck::read_sections(&sections, &file, tcg)?;
+
+                let mut deck = Deck::new();
+                if let Some(deck_name) = xml::find_child(&player, "DeckName") {
+                    deck.name = xml::read_text_from_element(&deck_name);
+                }
+                deck.sections = sections;
+
+                result.player_names.push(name);
+                result.decks.push(deck);
+                result.commitments.push(commitment);
+                result.secrets.push(secret);
+                problems.append(&mut deck_problems);
+            }
+        }
+        if result.decks.len() != 2 {
+            return Err(OpenTcgError::InvalidValue{path : filename.clone(), element : "Players".to_string(),
+                line : file.line_of(&players), value : format!("{} players", result.decks.len())});
+        }
+
+        let actions = xml::required_child(&root, "Actions", &file)?;
+        for a in actions.children() {
+            if let Some(action) = a.element() {
+                result.actions.push(Action::new_from_element(&action, &file)?);
+            }
+        }
+
+        let shuffles = xml::required_child(&root, "ShuffleOrders", &file)?;
+        for o in shuffles.children() {
+            if let Some(order) = o.element() {
+                let cards : Result<Vec<CardId>, _> = xml::read_text_from_element(&order)
+                    .split_whitespace().map(|card| card.parse()).collect();
+                result.shuffle_orders.push(cards.map_err(|_| xml::invalid_value(&order, &file))?);
+            }
+        }
+
+        Ok((result, problems))
+    }
+}
+
+/// Reasons a logged game fails `GameLog::verify_shuffles`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VerifyError {
+    /// The action with the given index can't be replayed
+    Action{index : usize, error : GameError},
+
+    /// The shuffle with the given index can't be trusted
+    Shuffle{index : usize, error : RandomError}
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            VerifyError::Action{index, ref error} => write!(f, "action {} can't be replayed: {}", index + 1, error),
+            VerifyError::Shuffle{index, ref error} => write!(f, "shuffle {}: {}", index + 1, error)
+        }
+    }
+}
+
+/// A game in progress, whose state changes are all recorded in its log.
+///
+/// Both players play at the same table, so the session takes the part of both
+/// players whenever they agree on something random.
+pub struct GameSession {
+    pub state : GameState,
+    pub log : GameLog
+}
+
+impl GameSession {
+    /// Starts a game between two players. Each player commits to a secret of
+    /// their own, which seeds the shuffles of their zones, and then the zones
+    /// cards start in, such as the decks, are shuffled.
+    pub fn start(tcg : &TCG, started : u64, players : [(&str, &Deck); 2]) -> Result<GameSession, RandomError> {
+        let log = GameLog::new(tcg, started, players, [Secret::generate()?, Secret::generate()?]);
+        let mut result = GameSession{state : log.initial_state(tcg), log : log};
+        for zone in result.state.starting_shuffles() {
+            let action = result.shuffle_action(zone, 0)?;
+            result.perform(action).expect("the zones cards start in can be shuffled");
+        }
+        Ok(result)
+    }
+
+    /// Creates the action of shuffling a zone. The owner of the zone, or the given
+    /// player if the zone is shared, shuffles with the secret they committed to,
+    /// and the other player contributes a new secret of their own. Neither
+    /// player can choose the order, but the shuffling player can work it out.
+    pub fn shuffle_action(&self, zone : ZoneId, player : PlayerId) -> Result<Action, RandomError> {
+        let player = self.state.zone(zone).and_then(|z| z.owner).unwrap_or(player);
+        Ok(Action::Shuffle{zone : zone, player : player, contribution : Secret::generate()?})
+    }
+
+    /// Creates the action of a player rolling a die, with a seed both players
+    /// agree on through a `JointRandom` so that neither can choose the result.
+    pub fn roll_action(&self, player : PlayerId, sides : u32) -> Result<Action, RandomError> {
+        let (mut first, mut second) = (JointRandom::new()?, JointRandom::new()?);
+        let (first_commitment, second_commitment) = (first.commitment(), second.commitment());
+        let first_secret = first.receive_commitment(second_commitment);
+        let second_secret = second.receive_commitment(first_commitment);
+        second.receive_secret(&first_secret, false)?;
+        let seed = first.receive_secret(&second_secret, true)?;
+        Ok(Action::Roll{player : player, sides : sides, seed : seed})
+    }
+
+    /// Applies an action to the game and records it, along with the order
+    /// the cards end up in if it is a shuffle.
+    /// Actions that can't be applied are not recorded.
+    pub fn perform(&mut self, action : Action) -> Result<(), GameError> {
+        action.apply(&mut self.state)?;
+        if let Action::Shuffle{zone, ..} = action {
+            self.log.shuffle_orders.push(self.state.zones[zone].cards.clone());
+        }
+        self.log.actions.push(action);
+        Ok(())
+    }
+}
+
+/// Steps through a logged game one action at a time.
+pub struct Replay<'a> {
+    tcg : &'a TCG,
+    log : &'a GameLog,
+    pub state : GameState,
+
+    /// Number of actions applied to `state`
+    position : usize
+}
+
+impl<'a> Replay<'a> {
+    pub fn new(tcg : &'a TCG, log : &'a GameLog) -> Replay<'a> {
+        Replay{tcg : tcg, log : log, state : log.initial_state(tcg), position : 0}
+    }
+
+    pub fn get_position(&self) -> usize {
+        self.position
+    }
+
+    pub fn len(&self) -> usize {
+        self.log.actions.len()
+    }
+
+    /// Applies the next action and returns it, or returns None at the end of the log.
+    pub fn step_forward(&mut self) -> Option<Result<&'a Action, GameError>> {
+        let log = self.log;
+        match log.actions.get(self.position) {
+            Some(action) => {
+                self.position += 1;
+                Some(action.apply(&mut self.state).map(|_| action))
+            },
+            None => None
+        }
+    }
+
+    /// Undoes the last applied action by replaying the game up to the action before it.
+    pub fn step_back(&mut self) -> Result<(), GameError> {
+        if self.position > 0 {
+            let target = self.position - 1;
+            self.seek(target)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Replays the game from the start until `position` actions have been applied.
+    pub fn seek(&mut self, position : usize) -> Result<(), GameError> {
+        self.state = self.log.initial_state(self.tcg);
+        self.position = 0;
+        while self.position < position {
+            match self.step_forward() {
+                Some(result) => { result?; },
+                None => break
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::io::Read;
+    use std::process;
+    use super::super::deck::DeckSectionInfo;
+    use super::super::random::SECRET_LEN;
+    use super::super::card::CardInfo;
+
+    fn test_tcg() -> TCG {
+        let mut tcg = TCG::new();
+        let mut section = DeckSectionInfo::new();
+        section.name = "Main".to_string();
+        section.max_size = 60;
+        tcg.sections.push(section);
+        for name in ["Goblin", "Knight", "Wizard"].iter() {
+            let mut card = CardInfo::new();
+            card.name = name.to_string();
+            tcg.cards.insert(card.name.clone(), card);
+        }
+        tcg
+    }
+
+    /// Starts a game between two in-process players with the same deck.
+    fn start(tcg : &TCG) -> GameSession {
+        let deck = tcg.new_deck();
+        for name in ["Goblin", "Knight", "Wizard"].iter() {
+            deck.sections[0].cards.borrow_mut().insert(name.to_string(), 4);
+        }
+        GameSession::start(tcg, 0, [("First", &deck), ("Second", &deck)]).unwrap()
+    }
+
+    #[test]
+    fn start_shuffles_decks() {
+        let tcg = test_tcg();
+        let session = start(&tcg);
+        let decks = session.log.initial_state(&tcg).starting_shuffles();
+        assert_eq!(decks.len(), 2);
+
+        let shufflers : Vec<PlayerId> = session.log.actions.iter().map(|a| match *a {
+            Action::Shuffle{player, ..} => player,
+            _ => panic!("only shuffles are taken when the game starts")
+        }).collect();
+        assert_eq!(shufflers, vec![0, 1]);
+        assert_eq!(session.log.shuffle_orders.len(), 2);
+        for (&zone, order) in decks.iter().zip(session.log.shuffle_orders.iter()) {
+            assert_eq!(session.state.zones[zone].cards, *order);
+        }
+        assert_eq!(session.log.verify_shuffles(&tcg), Ok(()));
+    }
+
+    #[test]
+    fn rolls_are_replayed() {
+        let tcg = test_tcg();
+        let mut session = start(&tcg);
+        let action = session.roll_action(1, 6).unwrap();
+        session.perform(action).unwrap();
+        let roll = session.state.last_roll.clone().unwrap();
+        assert!(roll.value >= 1 && roll.value <= 6);
+
+        let mut replay = Replay::new(&tcg, &session.log);
+        replay.seek(session.log.actions.len()).unwrap();
+        assert_eq!(replay.state.last_roll, Some(roll));
+    }
+
+    #[test]
+    fn round_trip() {
+        let tcg = test_tcg();
+        let mut session = start(&tcg);
+        let action = session.shuffle_action(0, 1).unwrap();
+        session.perform(action).unwrap();
+        session.perform(Action::EndGame{winner : Some(0)}).unwrap();
+
+        let path = env::temp_dir().join(format!("open_tcg_log_{}.{}", process::id(), LOG_EXTENSION));
+        session.log.write_to_file(&path).unwrap();
+        let (log, problems) = GameLog::read_from_file(&path, &tcg).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(problems.is_empty());
+        assert_eq!(log.player_names, session.log.player_names);
+        assert_eq!(log.commitments, session.log.commitments);
+        assert_eq!(log.secrets, session.log.secrets);
+        assert_eq!(log.actions, session.log.actions);
+        assert_eq!(log.shuffle_orders, session.log.shuffle_orders);
+        assert_eq!(log.verify_shuffles(&tcg), Ok(()));
+    }
+
+    #[test]
+    fn tampered_shuffles_fail() {
+        let tcg = test_tcg();
+        let session = start(&tcg);
+
+        let mut log = session.log.clone();
+        log.shuffle_orders[1].swap(0, 1);
+        assert_eq!(log.verify_shuffles(&tcg), Err(VerifyError::Shuffle{index : 1, error : RandomError::OrderMismatch}));
+
+        let mut log = session.log.clone();
+        log.secrets[0] = Secret([9; SECRET_LEN]);
+        assert_eq!(log.verify_shuffles(&tcg),
+            Err(VerifyError::Shuffle{index : 0, error : RandomError::CommitmentMismatch}));
+
+        let mut log = session.log.clone();
+        log.shuffle_orders.pop();
+        assert_eq!(log.verify_shuffles(&tcg), Err(VerifyError::Shuffle{index : 1, error : RandomError::OutOfOrder}));
+        log.shuffle_orders = session.log.shuffle_orders.clone();
+        log.shuffle_orders.push(Vec::new());
+        assert_eq!(log.verify_shuffles(&tcg), Err(VerifyError::Shuffle{index : 2, error : RandomError::OutOfOrder}));
+    }
+
+    #[test]
+    fn secrets_are_written_once_the_game_ends() {
+        let tcg = test_tcg();
+        let mut session = start(&tcg);
+        let path = env::temp_dir().join(format!("open_tcg_unfinished_{}.{}", process::id(), LOG_EXTENSION));
+
+        session.log.write_to_file(&path).unwrap();
+        let mut text = String::new();
+        File::open(&path).unwrap().read_to_string(&mut text).unwrap();
+        assert!(!text.contains("<Secret>"));
+        assert!(GameLog::read_from_file(&path, &tcg).is_err());
+
+        session.perform(Action::EndGame{winner : None}).unwrap();
+        session.log.write_to_file(&path).unwrap();
+        let (log, _) = GameLog::read_from_file(&path, &tcg).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(log.secrets, session.log.secrets);
+    }
+}
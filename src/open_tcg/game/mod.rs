@@ -23,3 +23,15 @@
 pub mod tcg;
 pub mod deck;
 pub mod card;
+pub mod validation;
+pub mod search;
+pub mod query_parser;
+pub mod state;
+pub mod action;
+pub mod log;
+pub mod random;
+pub mod fingerprint;
+pub mod view;
+pub mod profile;
+pub mod settings;
+pub mod history;
@@ -0,0 +1,477 @@
+// MIT License
+
+// Copyright (c) 2016 Johan Burke
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+extern crate sxd_document;
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use self::sxd_document::Package;
+use self::sxd_document::dom::Element;
+use self::sxd_document::writer::format_document;
+
+use super::log::GameLog;
+use super::state::PlayerId;
+use open_tcg::util::{files, xml};
+use open_tcg::util::error::{OpenTcgError, OpenTcgResult};
+
+/// Name of the profile file within the data directory.
+pub const PROFILE_FILE : &'static str = "profile.xml";
+
+/// Seconds since the Unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Formats a time in seconds since the Unix epoch as `YYYY-MM-DD HH:MM` (UTC).
+pub fn format_time(time : u64) -> String {
+    let days = time / 86400;
+    let seconds = time % 86400;
+
+    // converts days since 1970-01-01 to a civil date, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z / 146097;
+    let day_of_era = z - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02} {:02}:{:02}", year, month, day, seconds / 3600, seconds % 3600 / 60)
+}
+
+/// A deck the player has edited.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeckRecord {
+    pub path : PathBuf,
+    pub tcg_name : String,
+
+    /// Time the deck was last saved, in seconds since the Unix epoch
+    pub last_edited : u64
+}
+
+impl DeckRecord {
+    /// Name of the deck, taken from its file name as in `Deck::read_from_file`.
+    pub fn name(&self) -> String {
+        self.path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or(String::new())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MatchResult {
+    Win,
+    Loss,
+    Draw,
+
+    /// The game was left before it ended
+    Unfinished
+}
+
+impl MatchResult {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            MatchResult::Win => "Win",
+            MatchResult::Loss => "Loss",
+            MatchResult::Draw => "Draw",
+            MatchResult::Unfinished => "Unfinished"
+        }
+    }
+
+    fn from_name(name : &str) -> Option<MatchResult> {
+        match name {
+            "Win" => Some(MatchResult::Win),
+            "Loss" => Some(MatchResult::Loss),
+            "Draw" => Some(MatchResult::Draw),
+            "Unfinished" => Some(MatchResult::Unfinished),
+            _ => None
+        }
+    }
+}
+
+/// A game the player took part in.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchRecord {
+    pub tcg_name : String,
+
+    /// Time the game started, in seconds since the Unix epoch
+    pub started : u64,
+
+    pub opponent : String,
+
+    /// Name of the deck the player used
+    pub deck_name : String,
+
+    pub result : MatchResult,
+
+    /// Log of the game, from which the rest of the record was taken
+    pub log_path : PathBuf
+}
+
+impl MatchRecord {
+    /// Creates the record of a game from its log, as seen by the given player.
+    pub fn new_from_log(log : &GameLog, log_path : &PathBuf, player : PlayerId) -> MatchRecord {
+        let opponent = (player + 1) % log.player_names.len().max(1);
+        let result = if !log.is_finished() {
+            MatchResult::Unfinished
+        } else {
+            match log.winner() {
+                Some(winner) if winner == player => MatchResult::Win,
+                Some(_) => MatchResult::Loss,
+                None => MatchResult::Draw
+            }
+        };
+        MatchRecord{tcg_name : log.tcg_name.clone(), started : log.started,
+            opponent : log.player_names.get(opponent).cloned().unwrap_or(String::new()),
+            deck_name : log.decks.get(player).map(|d| d.name.clone()).unwrap_or(String::new()),
+            result : result, log_path : log_path.clone()}
+    }
+}
+
+/// Results of the player's games of one TCG.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TcgStatistics {
+    pub games : u32,
+    pub wins : u32,
+    pub losses : u32,
+    pub draws : u32
+}
+
+impl TcgStatistics {
+    /// Fraction of finished games that were won, or None if no game was finished.
+    pub fn win_rate(&self) -> Option<f64> {
+        let finished = self.wins + self.losses + self.draws;
+        if finished == 0 {
+            None
+        } else {
+            Some(self.wins as f64 / finished as f64)
+        }
+    }
+}
+
+/// The local player's profile.
+///
+/// Profiles are stored as XML in `profile.xml` in the data directory
+/// (see `files::data_directory`):
+///
+/// ```xml
+/// <Profile>
+///     <Name>Johan</Name>
+///     <Avatar>/home/johan/avatar.png</Avatar>
+///     <Decks>
+///         <Deck>
+///             <Path>/home/johan/decks/goblins.tcgdeck</Path>
+///             <TCG>Example TCG</TCG>
+///             <LastEdited>1480000000</LastEdited>
+///         </Deck>
+///     </Decks>
+///     <Matches>
+///         <Match>
+///             <TCG>Example TCG</TCG>
+///             <Started>1480000000</Started>
+///             <Opponent>Player 2</Opponent>
+///             <Deck>goblins</Deck>
+///             <Result>Win</Result>
+///             <Log>/home/johan/decks/game-1480000000.tcglog</Log>
+///         </Match>
+///     </Matches>
+/// </Profile>
+/// ```
+///
+/// Times are in seconds since the Unix epoch and `<Result>` is one of
+/// `Win`, `Loss`, `Draw` or `Unfinished`. `<Avatar>` is optional.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile {
+    pub name : String,
+
+    /// Image shown for the player, if they chose one
+    pub avatar : Option<PathBuf>,
+
+    /// Decks the player has edited, most recently edited first
+    pub decks : Vec<DeckRecord>,
+
+    /// Games the player took part in, in the order they were played
+    pub matches : Vec<MatchRecord>
+}
+
+impl Profile {
+    pub fn new() -> Profile {
+        Profile{name : "Player".to_string(), avatar : None, decks : Vec::new(), matches : Vec::new()}
+    }
+
+    pub fn default_path() -> PathBuf {
+        files::data_directory().join(PROFILE_FILE)
+    }
+
+    /// Reads the profile from its default location, or creates a new one if there is none yet.
+    pub fn read_or_new() -> OpenTcgResult<Profile> {
+        let path = Profile::default_path();
+        if path.exists() {
+            Profile::read_from_file(&path)
+        } else {
+            Ok(Profile::new())
+        }
+    }
+
+    /// Writes the profile to its default location, creating the data directory if needed.
+    pub fn write(&self) -> OpenTcgResult<()> {
+        let path = Profile::default_path();
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory).map_err(|e| OpenTcgError::Io{path : directory.to_path_buf(), error : e})?;
+        }
+        self.write_to_file(&path)
+    }
+
+    /// Records that a deck was saved now, moving it to the top of the deck list.
+    pub fn touch_deck(&mut self, path : &PathBuf, tcg_name : &str) {
+        self.decks.retain(|d| d.path != *path);
+        self.decks.insert(0, DeckRecord{path : path.clone(), tcg_name : tcg_name.to_string(), last_edited : now()});
+    }
+
+    /// Records the result of a game from its log. Recording the same log
+    /// again replaces its earlier record, e.g. once an unfinished game ends.
+    pub fn record_match(&mut self, log : &GameLog, log_path : &PathBuf, player : PlayerId) {
+        let record = MatchRecord::new_from_log(log, log_path, player);
+        match self.matches.iter().position(|m| m.log_path == *log_path) {
+            Some(index) => self.matches[index] = record,
+            None => self.matches.push(record)
+        }
+    }
+
+    /// Results of the player's games, by TCG name.
+    pub fn statistics(&self) -> BTreeMap<String, TcgStatistics> {
+        let mut result : BTreeMap<String, TcgStatistics> = BTreeMap::new();
+        for record in self.matches.iter() {
+            let stats = result.entry(record.tcg_name.clone()).or_insert_with(TcgStatistics::default);
+            stats.games += 1;
+            match record.result {
+                MatchResult::Win => stats.wins += 1,
+                MatchResult::Loss => stats.losses += 1,
+                MatchResult::Draw => stats.draws += 1,
+                MatchResult::Unfinished => ()
+            }
+        }
+        result
+    }
+
+    pub fn write_to_file(&self, filename : &PathBuf) -> OpenTcgResult<()> {
+        let package = Package::new();
+        let doc = package.as_document();
+
+        let root = doc.create_element("Profile");
+        xml::append_text_element(&doc, &root, "Name", &self.name);
+        if let Some(ref avatar) = self.avatar {
+            xml::append_text_element(&doc, &root, "Avatar", &avatar.to_string_lossy());
+        }
+
+        let decks = doc.create_element("Decks");
+        for deck in self.decks.iter() {
+            let element = doc.create_element("Deck");
+            xml::append_text_element(&doc, &element, "Path", &deck.path.to_string_lossy());
+            xml::append_text_element(&doc, &element, "TCG", &deck.tcg_name);
+            xml::append_text_element(&doc, &element, "LastEdited", &deck.last_edited.to_string());
+            decks.append_child(element);
+        }
+        root.append_child(decks);
+
+        let matches = doc.create_element("Matches");
+        for record in self.matches.iter() {
+            let element = doc.create_element("Match");
+            xml::append_text_element(&doc, &element, "TCG", &record.tcg_name);
+            xml::append_text_element(&doc, &element, "Started", &record.started.to_string());
+            xml::append_text_element(&doc, &element, "Opponent", &record.opponent);
+            xml::append_text_element(&doc, &element, "Deck", &record.deck_name);
+            xml::append_text_element(&doc, &element, "Result", record.result.name());
+            xml::append_text_element(&doc, &element, "Log", &record.log_path.to_string_lossy());
+            matches.append_child(element);
+        }
+        root.append_child(matches);
+        doc.root().append_child(root);
+
+        let mut file = File::create(filename).map_err(|e| OpenTcgError::Io{path : filename.clone(), error : e})?;
+        format_document(&doc, &mut file).map_err(|e| OpenTcgError::Io{path : filename.clone(), error : e})
+    }
+
+    pub fn read_from_file(filename : &PathBuf) -> OpenTcgResult<Profile> {
+        let file = files::document_from_file(filename)?;
+        let root = file.root_element("Profile")?;
+        let text = |element : &Element, name : &str| -> OpenTcgResult<String> {
+            Ok(xml::read_text_from_element(&xml::required_child(element, name, &file)?))
+        };
+
+        let mut result = Profile::new();
+        result.name = text(&root, "Name")?;
+        result.avatar = xml::find_child(&root, "Avatar").map(|a| PathBuf::from(xml::read_text_from_element(&a)));
+
+        if let Some(decks) = xml::find_child(&root, "Decks") {
+            for d in decks.children() {
+                if let Some(element) = d.element() {
+                    let last_edited = xml::read_parsed_from_element(&xml::required_child(&element, "LastEdited", &file)?, &file)?;
+                    result.decks.push(DeckRecord{path : PathBuf::from(text(&element, "Path")?),
+                        tcg_name : text(&element, "TCG")?, last_edited : last_edited});
+                }
+            }
+        }
+
+        if let Some(matches) = xml::find_child(&root, "Matches") {
+            for m in matches.children() {
+                if let Some(element) = m.element() {
+                    let result_element = xml::required_child(&element, "Result", &file)?;
+                    let match_result = MatchResult::from_name(&xml::read_text_from_element(&result_element))
+                        .ok_or_else(|| xml::invalid_value(&result_element, &file))?;
+                    let started = xml::read_parsed_from_element(&xml::required_child(&element, "Started", &file)?, &file)?;
+                    result.matches.push(MatchRecord{tcg_name : text(&element, "TCG")?, started : started,
+                        opponent : text(&element, "Opponent")?, deck_name : text(&element, "Deck")?,
+                        result : match_result, log_path : PathBuf::from(text(&element, "Log")?)});
+                }
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::io::Write;
+    use std::process;
+    use super::super::action::Action;
+    use super::super::random::{Secret, SECRET_LEN};
+    use super::super::tcg::TCG;
+
+    fn temp_path(name : &str) -> PathBuf {
+        env::temp_dir().join(format!("open_tcg_profile_{}_{}.xml", name, process::id()))
+    }
+
+    fn test_log(started : u64, winner : Option<Option<PlayerId>>) -> GameLog {
+        let tcg = TCG::new();
+        let deck = tcg.new_deck();
+        let mut log = GameLog::new(&tcg, started, [("Me", &deck), ("Them", &deck)],
+            [Secret([1; SECRET_LEN]), Secret([2; SECRET_LEN])]);
+        if let Some(winner) = winner {
+            log.actions.push(Action::EndGame{winner : winner});
+        }
+        log
+    }
+
+    #[test]
+    fn format_times() {
+        assert_eq!(format_time(0), "1970-01-01 00:00");
+        assert_eq!(format_time(951782400), "2000-02-29 00:00");
+        assert_eq!(format_time(1480000000), "2016-11-24 15:06");
+        assert_eq!(format_time(4102444799), "2099-12-31 23:59");
+    }
+
+    #[test]
+    fn reads_documented_format() {
+        let path = temp_path("documented");
+        File::create(&path).unwrap().write_all(br#"<?xml version="1.0"?>
+<Profile>
+    <Name>Johan</Name>
+    <Avatar>/home/johan/avatar.png</Avatar>
+    <Decks>
+        <Deck>
+            <Path>/home/johan/decks/goblins.tcgdeck</Path>
+            <TCG>Example TCG</TCG>
+            <LastEdited>1480000000</LastEdited>
+        </Deck>
+    </Decks>
+    <Matches>
+        <Match>
+            <TCG>Example TCG</TCG>
+            <Started>1480000000</Started>
+            <Opponent>Player 2</Opponent>
+            <Deck>goblins</Deck>
+            <Result>Win</Result>
+            <Log>/home/johan/decks/game-1480000000.tcglog</Log>
+        </Match>
+    </Matches>
+</Profile>"#).unwrap();
+        let profile = Profile::read_from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(profile.name, "Johan");
+        assert_eq!(profile.avatar, Some(PathBuf::from("/home/johan/avatar.png")));
+        assert_eq!(profile.decks, vec![DeckRecord{path : PathBuf::from("/home/johan/decks/goblins.tcgdeck"),
+            tcg_name : "Example TCG".to_string(), last_edited : 1480000000}]);
+        assert_eq!(profile.decks[0].name(), "goblins");
+        assert_eq!(profile.matches, vec![MatchRecord{tcg_name : "Example TCG".to_string(), started : 1480000000,
+            opponent : "Player 2".to_string(), deck_name : "goblins".to_string(), result : MatchResult::Win,
+            log_path : PathBuf::from("/home/johan/decks/game-1480000000.tcglog")}]);
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut profile = Profile::new();
+        profile.name = "Johan".to_string();
+        profile.touch_deck(&PathBuf::from("goblins.tcgdeck"), "Example TCG");
+        profile.touch_deck(&PathBuf::from("knights.tcgdeck"), "Example TCG");
+        profile.record_match(&test_log(10, Some(Some(0))), &PathBuf::from("game-10.tcglog"), 0);
+        profile.record_match(&test_log(20, None), &PathBuf::from("game-20.tcglog"), 0);
+
+        let path = temp_path("round_trip");
+        profile.write_to_file(&path).unwrap();
+        let read = Profile::read_from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(read, profile);
+
+        // without an avatar, and with the most recently touched deck first
+        assert_eq!(read.avatar, None);
+        assert_eq!(read.decks[0].name(), "knights");
+    }
+
+    #[test]
+    fn record_match_replaces_its_log() {
+        let mut profile = Profile::new();
+        let path = PathBuf::from("game-10.tcglog");
+        profile.record_match(&test_log(10, None), &path, 0);
+        assert_eq!(profile.matches[0].result, MatchResult::Unfinished);
+        assert_eq!(profile.matches[0].opponent, "Them");
+
+        profile.record_match(&test_log(10, Some(Some(1))), &path, 0);
+        assert_eq!(profile.matches.len(), 1);
+        assert_eq!(profile.matches[0].result, MatchResult::Loss);
+
+        profile.record_match(&test_log(30, Some(None)), &PathBuf::from("game-30.tcglog"), 0);
+        assert_eq!(profile.matches.len(), 2);
+        assert_eq!(profile.matches[1].result, MatchResult::Draw);
+    }
+
+    #[test]
+    fn statistics() {
+        let mut profile = Profile::new();
+        for (i, &winner) in [Some(Some(0)), Some(Some(0)), Some(Some(1)), Some(None), None].iter().enumerate() {
+            profile.record_match(&test_log(i as u64, winner), &PathBuf::from(format!("game-{}.tcglog", i)), 0);
+        }
+        let stats = profile.statistics();
+        let stats = &stats[""];
+        assert_eq!(*stats, TcgStatistics{games : 5, wins : 2, losses : 1, draws : 1});
+        // the unfinished game doesn't count towards the win rate
+        assert_eq!(stats.win_rate(), Some(0.5));
+        assert_eq!(TcgStatistics{games : 1, ..TcgStatistics::default()}.win_rate(), None);
+    }
+}
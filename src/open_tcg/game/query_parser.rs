@@ -0,0 +1,391 @@
+// MIT License
+
+// Copyright (c) 2016 Johan Burke
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Parser for the text query language of the card search box.
+//!
+//! A query is a list of terms which must all match, for example
+//! `type:creature cost<=3 text:"draw a card" -set:BASE`.
+//!
+//! * A bare word or quoted phrase searches card names.
+//! * `name:`, `text:`, `type:`, `set:` (set code prefix) and `setname:`
+//!   filter on the corresponding card field. Using `=` instead of `:` with `name`
+//!   or `text` matches whole words only.
+//! * Any other key names a card parameter and is compared numerically with
+//!   `:`/`=`, `!=`, `<`, `<=`, `>` or `>=`.
+//! * A leading `-` negates a term, `OR` combines terms, and parentheses group them.
+
+use std::fmt;
+
+use super::search::{Comparison, Query, TextMatch};
+use super::tcg::TCG;
+
+/// An error in a query, along with the (0-based) character position at which it was found.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryParseError {
+    pub position : usize,
+    pub message : String
+}
+
+impl fmt::Display for QueryParseError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "column {}: {}", self.position + 1, self.message)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LeftParen,
+    RightParen,
+    Or,
+    Not,
+    Term{key : Option<String>, op : String, value : String}
+}
+
+/// Parses a query, checking parameter names against the card types of the given TCG.
+pub fn parse(input : &str, tcg : &TCG) -> Result<Query, QueryParseError> {
+    let tokens = tokenize(input)?;
+    let mut parser = Parser{tokens : tokens, index : 0, tcg : tcg, end : input.chars().count()};
+
+    if parser.tokens.is_empty() {
+        return Ok(Query::All);
+    }
+
+    let query = parser.parse_or()?;
+    if let Some(&(ref token, position)) = parser.peek() {
+        let message = match *token {
+            Token::RightParen => "unmatched ')'".to_string(),
+            _ => "unexpected input".to_string()
+        };
+        return Err(QueryParseError{position : position, message : message});
+    }
+    Ok(query)
+}
+
+fn tokenize(input : &str) -> Result<Vec<(Token, usize)>, QueryParseError> {
+    let chars : Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '(' {
+            tokens.push((Token::LeftParen, i));
+            i += 1;
+        } else if c == ')' {
+            tokens.push((Token::RightParen, i));
+            i += 1;
+        } else if c == '-' {
+            tokens.push((Token::Not, i));
+            i += 1;
+        } else if is_operator_char(c) {
+            return Err(unexpected_char(c, i));
+        } else {
+            let start = i;
+            let (first, quoted) = read_value(&chars, &mut i)?;
+
+            if !quoted && first == "OR" {
+                tokens.push((Token::Or, start));
+                continue;
+            }
+
+            let op = if quoted { String::new() } else { read_operator(&chars, &mut i) };
+            if op.is_empty() {
+                tokens.push((Token::Term{key : None, op : op, value : first}, start));
+            } else {
+                if i >= chars.len() || chars[i].is_whitespace() || chars[i] == ')' {
+                    return Err(QueryParseError{position : i,
+                        message : format!("expected a value after \"{}{}\"", first, op)});
+                }
+                if is_operator_char(chars[i]) {
+                    return Err(unexpected_char(chars[i], i));
+                }
+                let (value, _) = read_value(&chars, &mut i)?;
+                tokens.push((Token::Term{key : Some(first.to_lowercase()), op : op, value : value}, start));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn is_operator_char(c : char) -> bool {
+    c == ':' || c == '=' || c == '<' || c == '>' || c == '!'
+}
+
+fn unexpected_char(c : char, position : usize) -> QueryParseError {
+    QueryParseError{position : position, message : format!("unexpected '{}'", c)}
+}
+
+/// Reads a bare word or a quoted phrase starting at `chars[*i]`.
+fn read_value(chars : &Vec<char>, i : &mut usize) -> Result<(String, bool), QueryParseError> {
+    let mut value = String::new();
+    if chars[*i] == '"' {
+        let start = *i;
+        *i += 1;
+        while *i < chars.len() && chars[*i] != '"' {
+            value.push(chars[*i]);
+            *i += 1;
+        }
+        if *i >= chars.len() {
+            return Err(QueryParseError{position : start, message : "unterminated quote".to_string()});
+        }
+        *i += 1;
+        Ok((value, true))
+    } else {
+        while *i < chars.len() && !chars[*i].is_whitespace() && chars[*i] != '(' && chars[*i] != ')'
+            && !is_operator_char(chars[*i]) && chars[*i] != '"' {
+            value.push(chars[*i]);
+            *i += 1;
+        }
+        Ok((value, false))
+    }
+}
+
+fn read_operator(chars : &Vec<char>, i : &mut usize) -> String {
+    let mut op = String::new();
+    while *i < chars.len() && is_operator_char(chars[*i]) && op.len() < 2 {
+        op.push(chars[*i]);
+        *i += 1;
+    }
+    op
+}
+
+struct Parser<'a> {
+    tokens : Vec<(Token, usize)>,
+    index : usize,
+    tcg : &'a TCG,
+    end : usize
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&(Token, usize)> {
+        self.tokens.get(self.index)
+    }
+
+    fn next(&mut self) -> Option<(Token, usize)> {
+        let token = self.tokens.get(self.index).cloned();
+        if token.is_some() {
+            self.index += 1;
+        }
+        token
+    }
+
+    fn parse_or(&mut self) -> Result<Query, QueryParseError> {
+        let mut queries = vec![self.parse_and()?];
+        while let Some(&(Token::Or, _)) = self.peek() {
+            self.next();
+            queries.push(self.parse_and()?);
+        }
+        if queries.len() == 1 {
+            Ok(queries.remove(0))
+        } else {
+            Ok(Query::Or(queries))
+        }
+    }
+
+    fn parse_and(&mut self) -> Result<Query, QueryParseError> {
+        let mut queries = vec![self.parse_unary()?];
+        loop {
+            match self.peek() {
+                None | Some(&(Token::Or, _)) | Some(&(Token::RightParen, _)) => break,
+                _ => queries.push(self.parse_unary()?)
+            }
+        }
+        if queries.len() == 1 {
+            Ok(queries.remove(0))
+        } else {
+            Ok(Query::And(queries))
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Query, QueryParseError> {
+        match self.next() {
+            Some((Token::Not, _)) => Ok(Query::Not(Box::new(self.parse_unary()?))),
+            Some((Token::LeftParen, position)) => {
+                let query = self.parse_or()?;
+                match self.next() {
+                    Some((Token::RightParen, _)) => Ok(query),
+                    _ => Err(QueryParseError{position : position, message : "unmatched '('".to_string()})
+                }
+            },
+            Some((Token::Term{key, op, value}, position)) => self.make_term(key, op, value, position),
+            Some((Token::Or, position)) => Err(QueryParseError{position : position,
+                message : "expected a search term before OR".to_string()}),
+            Some((Token::RightParen, position)) => Err(QueryParseError{position : position,
+                message : "unmatched ')'".to_string()}),
+            None => Err(QueryParseError{position : self.end, message : "expected a search term".to_string()})
+        }
+    }
+
+    fn make_term(&self, key : Option<String>, op : String, value : String, position : usize) -> Result<Query, QueryParseError> {
+        let key = match key {
+            Some(key) => key,
+            None => return Ok(Query::Name(value, TextMatch::Contains))
+        };
+
+        let text_mode = match op.as_str() {
+            ":" => Some(TextMatch::Contains),
+            "=" => Some(TextMatch::WholeWord),
+            _ => None
+        };
+        let text_op_error = || QueryParseError{position : position,
+            message : format!("\"{}\" only supports ':' and '='", key)};
+
+        match key.as_str() {
+            "name" => text_mode.map(|mode| Query::Name(value, mode)).ok_or_else(text_op_error),
+            "text" => text_mode.map(|mode| Query::Text(value, mode)).ok_or_else(text_op_error),
+            "type" => {
+                text_mode.ok_or_else(&text_op_error)?;
+                match self.find_type(&value) {
+                    Some(name) => Ok(Query::Type(name)),
+                    None => Err(QueryParseError{position : position,
+                        message : format!("unknown card type \"{}\"", value)})
+                }
+            },
+            "set" => text_mode.map(|_| Query::SetCode(value)).ok_or_else(text_op_error),
+            "setname" => text_mode.map(|_| Query::SetName(value)).ok_or_else(text_op_error),
+            _ => {
+                let param = match self.find_param(&key) {
+                    Some(param) => param,
+                    None => return Err(QueryParseError{position : position,
+                        message : format!("unknown search key or parameter \"{}\"", key)})
+                };
+                let comparison = match op.as_str() {
+                    ":" | "=" => Comparison::Equal,
+                    "!=" => Comparison::NotEqual,
+                    "<" => Comparison::Less,
+                    "<=" => Comparison::LessEqual,
+                    ">" => Comparison::Greater,
+                    ">=" => Comparison::GreaterEqual,
+                    _ => return Err(QueryParseError{position : position,
+                        message : format!("unknown operator \"{}\"", op)})
+                };
+                match value.parse() {
+                    Ok(number) => Ok(Query::Param(param, comparison, number)),
+                    Err(_) => Err(QueryParseError{position : position,
+                        message : format!("\"{}\" must be compared with a number, found \"{}\"", param, value)})
+                }
+            }
+        }
+    }
+
+    /// Finds the proper name of the card type with the given name or alias, ignoring case.
+    fn find_type(&self, name : &str) -> Option<String> {
+        let name = name.to_lowercase();
+        self.tcg.card_types.values().find(|t| {
+            t.name.to_lowercase() == name || t.aliases.iter().any(|a| a.to_lowercase() == name)
+        }).map(|t| t.name.clone())
+    }
+
+    /// Finds the properly cased name of a parameter declared by any card type.
+    fn find_param(&self, name : &str) -> Option<String> {
+        for card_type in self.tcg.card_types.values() {
+            for param in card_type.param_names.iter() {
+                if param.to_lowercase() == name {
+                    return Some(param.clone());
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::card::CardType;
+
+    fn test_tcg() -> TCG {
+        let mut tcg = TCG::new();
+        let mut creature = CardType::new();
+        creature.name = "Creature".to_string();
+        creature.aliases.push("Monster".to_string());
+        creature.param_names.push("Cost".to_string());
+        tcg.card_types.insert(creature.name.clone(), creature);
+        tcg
+    }
+
+    fn name(text : &str) -> Query {
+        Query::Name(text.to_string(), TextMatch::Contains)
+    }
+
+    fn error_at(input : &str) -> (usize, String) {
+        let error = parse(input, &test_tcg()).unwrap_err();
+        (error.position, error.message)
+    }
+
+    #[test]
+    fn example_query() {
+        let query = parse("type:creature cost<=3 text:\"draw a card\" -set:BASE", &test_tcg()).unwrap();
+        assert_eq!(query, Query::And(vec![
+            Query::Type("Creature".to_string()),
+            Query::Param("Cost".to_string(), Comparison::LessEqual, 3),
+            Query::Text("draw a card".to_string(), TextMatch::Contains),
+            Query::Not(Box::new(Query::SetCode("BASE".to_string())))]));
+    }
+
+    #[test]
+    fn terms() {
+        let tcg = test_tcg();
+        assert_eq!(parse("  ", &tcg).unwrap(), Query::All);
+        assert_eq!(parse("\"Stone Giant\"", &tcg).unwrap(), name("Stone Giant"));
+        assert_eq!(parse("TEXT=draw", &tcg).unwrap(), Query::Text("draw".to_string(), TextMatch::WholeWord));
+        assert_eq!(parse("type:monster", &tcg).unwrap(), Query::Type("Creature".to_string()));
+        assert_eq!(parse("setname:\"Base Set\"", &tcg).unwrap(), Query::SetName("Base Set".to_string()));
+        assert_eq!(parse("cost!=2", &tcg).unwrap(), Query::Param("Cost".to_string(), Comparison::NotEqual, 2));
+        assert_eq!(parse("cost:-1", &tcg).unwrap(), Query::Param("Cost".to_string(), Comparison::Equal, -1));
+    }
+
+    #[test]
+    fn precedence() {
+        let tcg = test_tcg();
+        // AND binds tighter than OR, and - applies to a single term
+        assert_eq!(parse("a b OR -c d", &tcg).unwrap(), Query::Or(vec![
+            Query::And(vec![name("a"), name("b")]),
+            Query::And(vec![Query::Not(Box::new(name("c"))), name("d")])]));
+        assert_eq!(parse("a (b OR c)", &tcg).unwrap(), Query::And(vec![
+            name("a"), Query::Or(vec![name("b"), name("c")])]));
+        assert_eq!(parse("-(a OR b) OR c", &tcg).unwrap(), Query::Or(vec![
+            Query::Not(Box::new(Query::Or(vec![name("a"), name("b")]))), name("c")]));
+        // only an upper case, unquoted OR is an operator
+        assert_eq!(parse("a or \"OR\"", &tcg).unwrap(), Query::And(vec![name("a"), name("or"), name("OR")]));
+    }
+
+    #[test]
+    fn error_positions() {
+        assert_eq!(error_at("text:foo:bar"), (8, "unexpected ':'".to_string()));
+        assert_eq!(error_at(":foo"), (0, "unexpected ':'".to_string()));
+        assert_eq!(error_at("cost<=<3"), (6, "unexpected '<'".to_string()));
+        assert_eq!(error_at("a \"draw"), (2, "unterminated quote".to_string()));
+        assert_eq!(error_at("a (b OR c"), (2, "unmatched '('".to_string()));
+        assert_eq!(error_at("a b)"), (3, "unmatched ')'".to_string()));
+        assert_eq!(error_at("OR a"), (0, "expected a search term before OR".to_string()));
+        assert_eq!(error_at("a OR"), (4, "expected a search term".to_string()));
+        assert_eq!(error_at("name: a"), (5, "expected a value after \"name:\"".to_string()));
+        assert_eq!(error_at("a power>3"), (2, "unknown search key or parameter \"power\"".to_string()));
+        assert_eq!(error_at("type:spell"), (0, "unknown card type \"spell\"".to_string()));
+        assert_eq!(error_at("name<3"), (0, "\"name\" only supports ':' and '='".to_string()));
+        assert_eq!(error_at("cost>x"), (0, "\"Cost\" must be compared with a number, found \"x\"".to_string()));
+    }
+}
@@ -0,0 +1,445 @@
+// MIT License
+
+// Copyright (c) 2016 Johan Burke
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+extern crate getrandom;
+extern crate sha2;
+
+use std::fmt;
+
+use self::sha2::{Digest, Sha256};
+
+use open_tcg::util::hex;
+
+/// Number of bytes in a secret or commitment.
+pub const SECRET_LEN : usize = 32;
+
+/// A small deterministic random number generator (SplitMix64).
+///
+/// The same seed always produces the same sequence, on every platform and in
+/// every version of OpenTCG, so that logged games replay exactly. It is not
+/// suitable for anything the other player must not be able to predict; use
+/// `Secret` and `Commitment` to agree on seeds with another player.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rng {
+    state : u64
+}
+
+impl Rng {
+    pub fn new(seed : u64) -> Rng {
+        Rng{state : seed}
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number in `0..bound` without modulo bias. `bound` must not be 0.
+    pub fn below(&mut self, bound : u64) -> u64 {
+        let zone = u64::max_value() - u64::max_value() % bound;
+        loop {
+            let value = self.next_u64();
+            if value < zone {
+                return value % bound;
+            }
+        }
+    }
+
+    /// Shuffles the items in place (Fisher-Yates).
+    pub fn shuffle<T>(&mut self, items : &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = self.below(i as u64 + 1) as usize;
+            items.swap(i, j);
+        }
+    }
+
+    /// Returns a random order of `0..len`.
+    pub fn permutation(&mut self, len : usize) -> Vec<usize> {
+        let mut result : Vec<usize> = (0..len).collect();
+        self.shuffle(&mut result);
+        result
+    }
+
+    pub fn flip_coin(&mut self) -> bool {
+        self.below(2) == 1
+    }
+
+    /// Rolls a die with the given number of sides, returning a number in `1..sides + 1`.
+    pub fn roll_die(&mut self, sides : u32) -> u32 {
+        self.below(sides as u64) as u32 + 1
+    }
+}
+
+/// Fills `bytes` from the operating system's source of randomness.
+fn fill_from_os(bytes : &mut [u8]) -> Result<(), RandomError> {
+    getrandom::getrandom(bytes).map_err(RandomError::Unavailable)
+}
+
+/// Reads a seed from the first 8 bytes, most significant first.
+fn seed_from_bytes(bytes : &[u8]) -> u64 {
+    bytes[..8].iter().fold(0, |seed, &b| (seed << 8) | b as u64)
+}
+
+/// A seed that can't be predicted, such as the id of a network session.
+pub fn random_seed() -> Result<u64, RandomError> {
+    let mut bytes = [0; 8];
+    fill_from_os(&mut bytes)?;
+    Ok(seed_from_bytes(&bytes))
+}
+
+fn from_hex(text : &str) -> Option<[u8; SECRET_LEN]> {
+    match hex::from_hex(text) {
+        Some(ref bytes) if bytes.len() == SECRET_LEN => {
+            let mut result = [0; SECRET_LEN];
+            result.copy_from_slice(bytes);
+            Some(result)
+        },
+        _ => None
+    }
+}
+
+fn sha256(parts : &[&[u8]]) -> [u8; SECRET_LEN] {
+    let mut hasher = Sha256::default();
+    for part in parts {
+        hasher.input(part);
+    }
+    let mut result = [0; SECRET_LEN];
+    result.copy_from_slice(hasher.result().as_slice());
+    result
+}
+
+/// A player's private contribution to a random value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Secret(pub [u8; SECRET_LEN]);
+
+impl Secret {
+    /// Creates a new secret from the operating system's source of randomness.
+    pub fn generate() -> Result<Secret, RandomError> {
+        let mut bytes = [0; SECRET_LEN];
+        fill_from_os(&mut bytes)?;
+        Ok(Secret(bytes))
+    }
+
+    pub fn commitment(&self) -> Commitment {
+        Commitment(sha256(&[b"commit", &self.0]))
+    }
+
+    /// Derives a seed for the given purpose, which can't be predicted without
+    /// the secret and tells nothing about it.
+    pub fn derive(&self, purpose : &str) -> u64 {
+        seed_from_bytes(&sha256(&[b"derive", purpose.as_bytes(), &self.0]))
+    }
+
+    pub fn to_hex(&self) -> String {
+        hex::to_hex(&self.0)
+    }
+
+    pub fn from_hex(text : &str) -> Option<Secret> {
+        from_hex(text).map(Secret)
+    }
+}
+
+/// A hash binding a player to a secret without revealing it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Commitment(pub [u8; SECRET_LEN]);
+
+impl Commitment {
+    /// Whether the given secret is the one this commitment was made to.
+    pub fn verify(&self, secret : &Secret) -> bool {
+        secret.commitment() == *self
+    }
+
+    pub fn to_hex(&self) -> String {
+        hex::to_hex(&self.0)
+    }
+
+    pub fn from_hex(text : &str) -> Option<Commitment> {
+        from_hex(text).map(Commitment)
+    }
+}
+
+/// Derives a seed from the secrets of two players. The order of the secrets matters,
+/// so both players must pass them in the same order, e.g. by player id.
+pub fn combine_secrets(first : &Secret, second : &Secret) -> u64 {
+    seed_from_bytes(&sha256(&[b"seed", &first.0, &second.0]))
+}
+
+/// Reasons a random value agreed on with another player can't be trusted.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RandomError {
+    /// The operating system's source of randomness can't be read
+    Unavailable(getrandom::Error),
+
+    /// The secret revealed doesn't match the commitment made earlier
+    CommitmentMismatch,
+
+    /// A step of the protocol was taken out of order
+    OutOfOrder,
+
+    /// The cards aren't in the order the agreed seed puts them in
+    OrderMismatch
+}
+
+impl fmt::Display for RandomError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RandomError::Unavailable(ref error) => write!(f, "no random numbers are available: {}", error),
+            RandomError::CommitmentMismatch => write!(f, "the revealed secret doesn't match its commitment"),
+            RandomError::OutOfOrder => write!(f, "the random value was agreed on out of order"),
+            RandomError::OrderMismatch => write!(f, "the cards weren't shuffled with the agreed seed")
+        }
+    }
+}
+
+/// One player's side of agreeing on a random value that both players see,
+/// such as a coin flip or die roll.
+///
+/// 1. Both players send the commitment of their secret.
+/// 2. Once a player has the other's commitment, they send their secret.
+/// 3. Each player checks the other's secret against its commitment and
+///    derives the seed with `combine_secrets`.
+///
+/// Neither player can pick their secret after seeing the other's, so neither
+/// can influence the result.
+pub struct JointRandom {
+    secret : Secret,
+    other_commitment : Option<Commitment>
+}
+
+impl JointRandom {
+    pub fn new() -> Result<JointRandom, RandomError> {
+        Secret::generate().map(JointRandom::new_with)
+    }
+
+    pub fn new_with(secret : Secret) -> JointRandom {
+        JointRandom{secret : secret, other_commitment : None}
+    }
+
+    pub fn commitment(&self) -> Commitment {
+        self.secret.commitment()
+    }
+
+    /// Records the other player's commitment and returns the secret to send them.
+    pub fn receive_commitment(&mut self, commitment : Commitment) -> Secret {
+        self.other_commitment = Some(commitment);
+        self.secret
+    }
+
+    /// Checks the other player's secret and returns the agreed seed.
+    /// `first` tells whether this player's secret comes first in the seed.
+    pub fn receive_secret(&self, secret : &Secret, first : bool) -> Result<u64, RandomError> {
+        match self.other_commitment {
+            Some(ref commitment) if commitment.verify(secret) => {
+                Ok(if first { combine_secrets(&self.secret, secret) } else { combine_secrets(secret, &self.secret) })
+            },
+            Some(_) => Err(RandomError::CommitmentMismatch),
+            None => Err(RandomError::OutOfOrder)
+        }
+    }
+}
+
+/// Everything needed to check a shuffle of one player's hidden zone, such as their deck.
+///
+/// The owner of the zone commits to a secret and the other player answers with
+/// a secret of their own in the open. The owner shuffles with the seed of both
+/// secrets but keeps theirs hidden until the game is over, so the other player
+/// can't know the order of the zone. The owner can't choose the order, since
+/// they committed before seeing the other player's secret. After the game,
+/// the owner reveals their secret and the other player checks the order the
+/// cards were in against the seed with `verify`.
+///
+/// This doesn't hide the order from the owner: knowing both secrets, they can
+/// work out the order of their zone after every shuffle, and so what they will
+/// draw. Keeping the order from both players would take a mental poker scheme,
+/// which OpenTCG doesn't have.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShuffleRecord {
+    /// Commitment to the secret of the zone's owner
+    pub commitment : Commitment,
+
+    /// Secret of the other player
+    pub contribution : Secret,
+
+    /// Secret of the zone's owner, once revealed
+    pub revealed : Option<Secret>
+}
+
+impl ShuffleRecord {
+    /// Started by the player that doesn't own the zone on receiving the owner's commitment.
+    /// Returns the record and the secret to send to the owner.
+    pub fn new_contributor(commitment : Commitment) -> Result<(ShuffleRecord, Secret), RandomError> {
+        let contribution = Secret::generate()?;
+        Ok((ShuffleRecord{commitment : commitment, contribution : contribution, revealed : None}, contribution))
+    }
+
+    /// Started by the owner of the zone once they have the other player's secret.
+    /// Returns the record and the seed to shuffle with.
+    pub fn new_owner(secret : &Secret, contribution : Secret) -> (ShuffleRecord, u64) {
+        let record = ShuffleRecord{commitment : secret.commitment(), contribution : contribution, revealed : None};
+        (record, combine_secrets(secret, &contribution))
+    }
+
+    /// Records the owner's secret, revealed after the game.
+    pub fn reveal(&mut self, secret : Secret) -> Result<(), RandomError> {
+        if !self.commitment.verify(&secret) {
+            return Err(RandomError::CommitmentMismatch);
+        }
+        self.revealed = Some(secret);
+        Ok(())
+    }
+
+    /// The seed of the shuffle, once the owner's secret has been revealed.
+    pub fn seed(&self) -> Option<u64> {
+        self.revealed.as_ref().map(|secret| combine_secrets(secret, &self.contribution))
+    }
+
+    /// Checks that shuffling the cards in the order `before` with the agreed seed,
+    /// as `Rng::shuffle` does, puts them in the order `after`, which is the order
+    /// the owner's cards were logged in after the shuffle.
+    pub fn verify<T : PartialEq>(&self, before : &[T], after : &[T]) -> Result<(), RandomError> {
+        let seed = self.seed().ok_or(RandomError::OutOfOrder)?;
+        let permutation = Rng::new(seed).permutation(before.len());
+        if after.len() == before.len() && permutation.iter().zip(after.iter()).all(|(&i, card)| before[i] == *card) {
+            Ok(())
+        } else {
+            Err(RandomError::OrderMismatch)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secret(byte : u8) -> Secret {
+        Secret([byte; SECRET_LEN])
+    }
+
+    #[test]
+    fn rng_is_deterministic() {
+        let (mut first, mut second) = (Rng::new(42), Rng::new(42));
+        for _ in 0..16 {
+            assert_eq!(first.next_u64(), second.next_u64());
+        }
+        assert_eq!(Rng::new(42).permutation(10), Rng::new(42).permutation(10));
+        assert!(Rng::new(1).permutation(10) != Rng::new(2).permutation(10));
+
+        let mut rng = Rng::new(7);
+        for _ in 0..100 {
+            let value = rng.roll_die(6);
+            assert!(value >= 1 && value <= 6);
+        }
+    }
+
+    #[test]
+    fn secrets_from_os_differ() {
+        assert!(Secret::generate().unwrap() != Secret::generate().unwrap());
+        assert!(random_seed().unwrap() != random_seed().unwrap());
+    }
+
+    #[test]
+    fn joint_random_agrees() {
+        let (mut host, mut guest) = (JointRandom::new().unwrap(), JointRandom::new().unwrap());
+        let (host_commitment, guest_commitment) = (host.commitment(), guest.commitment());
+        let host_secret = host.receive_commitment(guest_commitment);
+        let guest_secret = guest.receive_commitment(host_commitment);
+
+        let seed = host.receive_secret(&guest_secret, true).unwrap();
+        assert_eq!(guest.receive_secret(&host_secret, false), Ok(seed));
+    }
+
+    #[test]
+    fn joint_random_catches_cheating() {
+        let (mut host, guest) = (JointRandom::new_with(secret(1)), JointRandom::new_with(secret(2)));
+        assert_eq!(host.receive_secret(&secret(2), true), Err(RandomError::OutOfOrder));
+
+        host.receive_commitment(guest.commitment());
+        // the guest changes their mind after seeing the host's secret
+        assert_eq!(host.receive_secret(&secret(3), true), Err(RandomError::CommitmentMismatch));
+    }
+
+    /// Both players' sides of a shuffle of the owner's deck, as it would go over the network.
+    fn shuffle_deck(owner_secret : &Secret, deck : &[u32]) -> (ShuffleRecord, Vec<u32>) {
+        let (contributor_record, contribution) = ShuffleRecord::new_contributor(owner_secret.commitment()).unwrap();
+        let (owner_record, seed) = ShuffleRecord::new_owner(owner_secret, contribution);
+        assert_eq!(owner_record, contributor_record);
+
+        let mut shuffled = deck.to_vec();
+        Rng::new(seed).shuffle(&mut shuffled);
+        (contributor_record, shuffled)
+    }
+
+    #[test]
+    fn shuffle_verifies() {
+        let deck : Vec<u32> = (0..40).collect();
+        let (mut record, shuffled) = shuffle_deck(&secret(1), &deck);
+        assert!(shuffled != deck);
+        assert_eq!(record.verify(&deck, &shuffled), Err(RandomError::OutOfOrder));
+
+        record.reveal(secret(1)).unwrap();
+        assert_eq!(record.verify(&deck, &shuffled), Ok(()));
+    }
+
+    #[test]
+    fn owner_cant_choose_the_order() {
+        let deck : Vec<u32> = (0..40).collect();
+        let owner_secret = secret(1);
+
+        // whatever the owner committed to, the order depends on the other player's contribution
+        let orders : Vec<Vec<u32>> = (2..5).map(|byte| {
+            let (_, seed) = ShuffleRecord::new_owner(&owner_secret, secret(byte));
+            let mut shuffled = deck.clone();
+            Rng::new(seed).shuffle(&mut shuffled);
+            shuffled
+        }).collect();
+        assert!(orders[0] != orders[1] && orders[1] != orders[2] && orders[0] != orders[2]);
+
+        // but once it has the contribution, the owner knows the order, as documented
+        let (record, shuffled) = shuffle_deck(&owner_secret, &deck);
+        let mut worked_out = deck.clone();
+        Rng::new(combine_secrets(&owner_secret, &record.contribution)).shuffle(&mut worked_out);
+        assert_eq!(worked_out, shuffled);
+    }
+
+    #[test]
+    fn shuffle_catches_cheating() {
+        let deck : Vec<u32> = (0..40).collect();
+        let (mut record, _) = shuffle_deck(&secret(1), &deck);
+
+        // the owner reveals a secret they didn't commit to
+        assert_eq!(record.reveal(secret(2)), Err(RandomError::CommitmentMismatch));
+        assert_eq!(record.revealed, None);
+
+        // the owner orders their deck with a seed of their own choosing
+        record.reveal(secret(1)).unwrap();
+        let mut stacked = deck.clone();
+        Rng::new(combine_secrets(&secret(1), &secret(9))).shuffle(&mut stacked);
+        assert_eq!(record.verify(&deck, &stacked), Err(RandomError::OrderMismatch));
+
+        // or leaves a card out
+        let mut shuffled = deck.clone();
+        Rng::new(record.seed().unwrap()).shuffle(&mut shuffled);
+        assert_eq!(record.verify(&deck, &shuffled[1..]), Err(RandomError::OrderMismatch));
+    }
+}
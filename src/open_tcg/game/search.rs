@@ -0,0 +1,418 @@
+// MIT License
+
+// Copyright (c) 2016 Johan Burke
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::cmp::Ordering;
+
+use super::card::CardInfo;
+use super::tcg::TCG;
+
+/// How text given in a query is matched against card text.
+/// Matching is always case-insensitive.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextMatch {
+    /// The text may appear anywhere, including inside a word.
+    Contains,
+
+    /// The text must appear as whole words.
+    WholeWord
+}
+
+/// Comparison operators for numeric parameter queries.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Less,
+    LessEqual,
+    Equal,
+    NotEqual,
+    GreaterEqual,
+    Greater
+}
+
+impl Comparison {
+    pub fn compare(&self, left : i32, right : i32) -> bool {
+        let ordering = left.cmp(&right);
+        match *self {
+            Comparison::Less => ordering == Ordering::Less,
+            Comparison::LessEqual => ordering != Ordering::Greater,
+            Comparison::Equal => ordering == Ordering::Equal,
+            Comparison::NotEqual => ordering != Ordering::Equal,
+            Comparison::GreaterEqual => ordering != Ordering::Less,
+            Comparison::Greater => ordering == Ordering::Greater
+        }
+    }
+}
+
+/// A query that selects cards from the card pool of a TCG.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    /// Matches every card.
+    All,
+
+    /// Matches cards whose name contains the given text.
+    Name(String, TextMatch),
+
+    /// Matches cards whose rules text contains the given text.
+    Text(String, TextMatch),
+
+    /// Matches cards of the given type (by name or alias) or any of its subtypes.
+    Type(String),
+
+    /// Matches cards whose set name equals the given name, ignoring case.
+    SetName(String),
+
+    /// Matches cards whose set code starts with the given code, ignoring case.
+    /// Since set codes usually carry a per-card suffix (e.g. BASE-001), this
+    /// allows a whole set to be selected by its prefix.
+    SetCode(String),
+
+    /// Matches cards that have the given parameter and whose value satisfies the comparison.
+    Param(String, Comparison, i32),
+
+    /// Matches cards that match all of the given queries.
+    And(Vec<Query>),
+
+    /// Matches cards that match any of the given queries.
+    Or(Vec<Query>),
+
+    /// Matches cards that don't match the given query.
+    Not(Box<Query>)
+}
+
+impl Query {
+    /// Returns true if the card satisfies this query.
+    ///
+    /// The TCG is needed to resolve card types and their subtypes.
+    pub fn matches(&self, card : &CardInfo, tcg : &TCG) -> bool {
+        match *self {
+            Query::All => true,
+            Query::Name(ref text, mode) => text_matches(&card.name, text, mode),
+            Query::Text(ref text, mode) => text_matches(&card.text, text, mode),
+            Query::Type(ref name) => card.card_type.is_subtype_of(name, &tcg.card_types),
+            Query::SetName(ref name) => card.set_name.to_lowercase() == name.to_lowercase(),
+            Query::SetCode(ref code) => card.set_code.to_lowercase().starts_with(&code.to_lowercase()),
+            Query::Param(ref name, comparison, value) => {
+                match card.param_values.get(name) {
+                    Some(&card_value) => comparison.compare(card_value, value),
+                    None => false
+                }
+            },
+            Query::And(ref queries) => queries.iter().all(|q| q.matches(card, tcg)),
+            Query::Or(ref queries) => queries.iter().any(|q| q.matches(card, tcg)),
+            Query::Not(ref query) => !query.matches(card, tcg)
+        }
+    }
+
+    /// Returns every card of the TCG that satisfies this query.
+    pub fn search<'a>(&self, tcg : &'a TCG) -> Vec<&'a CardInfo> {
+        tcg.cards.values().filter(|c| self.matches(c, tcg)).collect()
+    }
+}
+
+/// Combines the fields of the card search form into one query: a parsed text query,
+/// text the card's name and rules text must contain, and the card type picked from
+/// the type list. Empty fields and a missing type don't restrict the search.
+pub fn form_query(query : Query, name : &str, text : &str, card_type : Option<&str>) -> Query {
+    let mut queries = vec![query];
+    if !name.is_empty() {
+        queries.push(Query::Name(name.to_string(), TextMatch::Contains));
+    }
+    if !text.is_empty() {
+        queries.push(Query::Text(text.to_string(), TextMatch::Contains));
+    }
+    if let Some(card_type) = card_type {
+        queries.push(Query::Type(card_type.to_string()));
+    }
+    Query::And(queries)
+}
+
+/// A property of a card that search results can be sorted by.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SortField {
+    Name,
+    CardType,
+    SetCode,
+
+    /// Sorts by the value of the given parameter.
+    /// Cards that don't have the parameter always come last.
+    Param(String)
+}
+
+impl SortField {
+    pub fn label(&self) -> String {
+        match *self {
+            SortField::Name => "Name".to_string(),
+            SortField::CardType => "Type".to_string(),
+            SortField::SetCode => "Set".to_string(),
+            SortField::Param(ref name) => name.clone()
+        }
+    }
+
+    fn compare(&self, a : &CardInfo, b : &CardInfo, descending : bool) -> Ordering {
+        let ordering = match *self {
+            SortField::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+            SortField::CardType => a.card_type.name.cmp(&b.card_type.name),
+            SortField::SetCode => a.set_code.cmp(&b.set_code),
+            SortField::Param(ref name) => {
+                match (a.param_values.get(name), b.param_values.get(name)) {
+                    (Some(x), Some(y)) => x.cmp(y),
+                    // missing values go last regardless of direction
+                    (Some(_), None) => return Ordering::Less,
+                    (None, Some(_)) => return Ordering::Greater,
+                    (None, None) => Ordering::Equal
+                }
+            }
+        };
+        if descending { ordering.reverse() } else { ordering }
+    }
+}
+
+/// One level of ordering applied to search results.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortKey {
+    pub field : SortField,
+    pub descending : bool
+}
+
+impl SortKey {
+    pub fn new(field : SortField, descending : bool) -> SortKey {
+        SortKey{field : field, descending : descending}
+    }
+}
+
+/// Sorts cards by the given keys, where later keys break ties in earlier ones.
+///
+/// Cards that compare equal on every key are ordered by name, so the order
+/// is the same no matter what order the cards were given in.
+pub fn sort_cards(cards : &mut Vec<&CardInfo>, keys : &[SortKey]) {
+    cards.sort_by(|a, b| {
+        for key in keys.iter() {
+            let ordering = key.field.compare(a, b, key.descending);
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+        a.name.cmp(&b.name).then_with(|| a.set_code.cmp(&b.set_code))
+    });
+}
+
+/// Returns every parameter name declared by the card types of the TCG, sorted alphabetically.
+pub fn param_names(tcg : &TCG) -> Vec<String> {
+    let mut names : Vec<String> = Vec::new();
+    for card_type in tcg.card_types.values() {
+        for name in card_type.param_names.iter() {
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+    }
+    names.sort();
+    names
+}
+
+/// Returns true if `text` appears in `haystack`, ignoring case.
+pub fn text_matches(haystack : &str, text : &str, mode : TextMatch) -> bool {
+    let haystack = haystack.to_lowercase();
+    let text = text.to_lowercase();
+    match mode {
+        TextMatch::Contains => haystack.contains(&text),
+        TextMatch::WholeWord => contains_whole_word(&haystack, &text)
+    }
+}
+
+fn contains_whole_word(haystack : &str, word : &str) -> bool {
+    if word.is_empty() {
+        return true;
+    }
+
+    let mut start = 0;
+    while let Some(pos) = haystack[start..].find(word) {
+        let begin = start + pos;
+        let end = begin + word.len();
+        let before = haystack[..begin].chars().next_back();
+        let after = haystack[end..].chars().next();
+        let is_boundary = |c : Option<char>| match c {
+            Some(c) => !c.is_alphanumeric(),
+            None => true
+        };
+        if is_boundary(before) && is_boundary(after) {
+            return true;
+        }
+        start = begin + haystack[begin..].chars().next().map_or(1, |c| c.len_utf8());
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::card::CardType;
+
+    fn card_type(name : &str, parent : Option<&str>, aliases : &[&str]) -> CardType {
+        let mut result = CardType::new();
+        result.name = name.to_string();
+        result.parent = parent.map(|p| p.to_string());
+        result.aliases = aliases.iter().map(|a| a.to_string()).collect();
+        result
+    }
+
+    fn card(tcg : &mut TCG, name : &str, type_name : &str, set_code : &str, text : &str, params : &[(&str, i32)]) {
+        let mut result = CardInfo::new();
+        result.name = name.to_string();
+        result.card_type = tcg.card_types[type_name].clone();
+        result.set_name = "Base Set".to_string();
+        result.set_code = set_code.to_string();
+        result.text = text.to_string();
+        for &(param, value) in params.iter() {
+            result.param_values.insert(param.to_string(), value);
+        }
+        tcg.cards.insert(result.name.clone(), result);
+    }
+
+    fn test_tcg() -> TCG {
+        let mut tcg = TCG::new();
+        for card_type in vec![card_type("Creature", None, &["Monster"]),
+                              card_type("Goblin", Some("Creature"), &[]),
+                              card_type("Spell", None, &[])] {
+            tcg.card_types.insert(card_type.name.clone(), card_type);
+        }
+        card(&mut tcg, "Goblin Raider", "Goblin", "BASE-001", "When this enters play, draw a card.", &[("Cost", 2)]);
+        card(&mut tcg, "Stone Giant", "Creature", "BASE-002", "Cannot attack.", &[("Cost", 5)]);
+        card(&mut tcg, "Withdraw", "Spell", "EXP-001", "Return a creature to its owner's hand.", &[]);
+        tcg
+    }
+
+    fn names(query : &Query, tcg : &TCG) -> Vec<String> {
+        let mut result : Vec<String> = query.search(tcg).iter().map(|c| c.name.clone()).collect();
+        result.sort();
+        result
+    }
+
+    #[test]
+    fn matches() {
+        let tcg = test_tcg();
+        let goblin = &tcg.cards["Goblin Raider"];
+        assert!(Query::All.matches(goblin, &tcg));
+        assert!(Query::Name("raider".to_string(), TextMatch::Contains).matches(goblin, &tcg));
+        assert!(Query::Text("DRAW A CARD".to_string(), TextMatch::WholeWord).matches(goblin, &tcg));
+        assert!(Query::SetName("base set".to_string()).matches(goblin, &tcg));
+        assert!(Query::SetCode("base".to_string()).matches(goblin, &tcg));
+        assert!(!Query::SetCode("001".to_string()).matches(goblin, &tcg));
+        assert!(Query::Not(Box::new(Query::Type("Spell".to_string()))).matches(goblin, &tcg));
+
+        let either = Query::Or(vec![Query::Name("Giant".to_string(), TextMatch::Contains),
+                                    Query::SetCode("EXP".to_string())]);
+        assert_eq!(names(&either, &tcg), vec!["Stone Giant", "Withdraw"]);
+        let both = Query::And(vec![either, Query::Type("Creature".to_string())]);
+        assert_eq!(names(&both, &tcg), vec!["Stone Giant"]);
+    }
+
+    #[test]
+    fn subtypes() {
+        let tcg = test_tcg();
+        assert_eq!(names(&Query::Type("Creature".to_string()), &tcg), vec!["Goblin Raider", "Stone Giant"]);
+        assert_eq!(names(&Query::Type("Monster".to_string()), &tcg), vec!["Goblin Raider", "Stone Giant"]);
+        assert_eq!(names(&Query::Type("Goblin".to_string()), &tcg), vec!["Goblin Raider"]);
+        assert!(names(&Query::Type("Artifact".to_string()), &tcg).is_empty());
+    }
+
+    #[test]
+    fn whole_words() {
+        assert!(contains_whole_word("draw a card.", "card"));
+        assert!(contains_whole_word("draw a card.", "draw"));
+        assert!(contains_whole_word("(draw), then discard", "draw"));
+        assert!(contains_whole_word("owner's hand", "owner"));
+        assert!(contains_whole_word("cardboard card", "card"));
+        assert!(!contains_whole_word("withdraw", "draw"));
+        assert!(!contains_whole_word("drawn", "draw"));
+        assert!(!contains_whole_word("cards", "card"));
+        assert!(contains_whole_word("anything", ""));
+        assert!(!contains_whole_word("", "draw"));
+        assert!(!text_matches("Withdraw", "draw", TextMatch::WholeWord));
+        assert!(text_matches("Withdraw", "DRAW", TextMatch::Contains));
+    }
+
+    #[test]
+    fn params() {
+        let tcg = test_tcg();
+        let cost = |comparison, value| Query::Param("Cost".to_string(), comparison, value);
+        assert_eq!(names(&cost(Comparison::Less, 5), &tcg), vec!["Goblin Raider"]);
+        assert_eq!(names(&cost(Comparison::LessEqual, 5), &tcg), vec!["Goblin Raider", "Stone Giant"]);
+        assert_eq!(names(&cost(Comparison::Equal, 2), &tcg), vec!["Goblin Raider"]);
+        assert_eq!(names(&cost(Comparison::NotEqual, 2), &tcg), vec!["Stone Giant"]);
+        assert_eq!(names(&cost(Comparison::GreaterEqual, 2), &tcg), vec!["Goblin Raider", "Stone Giant"]);
+        assert_eq!(names(&cost(Comparison::Greater, 5), &tcg), Vec::<String>::new());
+    }
+
+    #[test]
+    fn form() {
+        let tcg = test_tcg();
+        let all = form_query(Query::All, "", "", None);
+        assert_eq!(names(&all, &tcg).len(), 3);
+
+        let creatures = form_query(Query::All, "", "", Some("Creature"));
+        assert_eq!(names(&creatures, &tcg), vec!["Goblin Raider", "Stone Giant"]);
+
+        let query = form_query(Query::SetCode("BASE".to_string()), "", "draw", Some("Creature"));
+        assert_eq!(names(&query, &tcg), vec!["Goblin Raider"]);
+
+        let query = form_query(Query::All, "giant", "", Some("Goblin"));
+        assert!(names(&query, &tcg).is_empty());
+    }
+
+    fn sorted(tcg : &TCG, keys : &[SortKey]) -> Vec<String> {
+        let mut cards : Vec<&CardInfo> = tcg.cards.values().collect();
+        sort_cards(&mut cards, keys);
+        cards.iter().map(|c| c.name.clone()).collect()
+    }
+
+    #[test]
+    fn sorting() {
+        let mut tcg = test_tcg();
+        card(&mut tcg, "Goblin Chief", "Goblin", "EXP-002", "", &[("Cost", 2)]);
+
+        assert_eq!(sorted(&tcg, &[]), vec!["Goblin Chief", "Goblin Raider", "Stone Giant", "Withdraw"]);
+        assert_eq!(sorted(&tcg, &[SortKey::new(SortField::Name, true)]),
+            vec!["Withdraw", "Stone Giant", "Goblin Raider", "Goblin Chief"]);
+        assert_eq!(sorted(&tcg, &[SortKey::new(SortField::SetCode, false)]),
+            vec!["Goblin Raider", "Stone Giant", "Withdraw", "Goblin Chief"]);
+
+        // later keys break ties in earlier ones
+        assert_eq!(sorted(&tcg, &[SortKey::new(SortField::CardType, false), SortKey::new(SortField::SetCode, true)]),
+            vec!["Stone Giant", "Goblin Chief", "Goblin Raider", "Withdraw"]);
+        assert_eq!(sorted(&tcg, &[SortKey::new(SortField::Param("Cost".to_string()), true),
+                                  SortKey::new(SortField::SetCode, true)]),
+            vec!["Stone Giant", "Goblin Chief", "Goblin Raider", "Withdraw"]);
+    }
+
+    #[test]
+    fn missing_params_sort_last() {
+        let tcg = test_tcg();
+        let cost = |descending| sorted(&tcg, &[SortKey::new(SortField::Param("Cost".to_string()), descending)]);
+        assert_eq!(cost(false), vec!["Goblin Raider", "Stone Giant", "Withdraw"]);
+        assert_eq!(cost(true), vec!["Stone Giant", "Goblin Raider", "Withdraw"]);
+
+        // cards without the parameter are still sorted among themselves
+        let power = sorted(&tcg, &[SortKey::new(SortField::Param("Power".to_string()), false),
+                                   SortKey::new(SortField::Name, true)]);
+        assert_eq!(power, vec!["Withdraw", "Stone Giant", "Goblin Raider"]);
+    }
+}
@@ -0,0 +1,241 @@
+// MIT License
+
+// Copyright (c) 2016 Johan Burke
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+extern crate sxd_document;
+
+use std::fs::{self, File};
+use std::path::PathBuf;
+
+use self::sxd_document::Package;
+use self::sxd_document::writer::format_document;
+
+use super::tcg::TCG;
+use open_tcg::util::{files, xml};
+use open_tcg::util::error::{OpenTcgError, OpenTcgResult};
+
+/// Name of the settings file within the config directory.
+pub const SETTINGS_FILE : &'static str = "settings.xml";
+
+pub const DEFAULT_SMALL_SCALE : i32 = 100;
+pub const DEFAULT_LARGE_SCALE : i32 = 250;
+
+/// Application settings, stored in the config directory as
+///
+/// ```xml
+/// <Settings>
+///   <TCGDirectories><Directory>...</Directory>...</TCGDirectories>
+///   <ImageDirectory>...</ImageDirectory>
+///   <DeckDirectory>...</DeckDirectory>
+///   <DefaultTCG>...</DefaultTCG>
+///   <ImageScales><Small>100</Small><Large>250</Large></ImageScales>
+/// </Settings>
+/// ```
+///
+/// Every element is optional; missing ones keep their default value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    /// Directories searched for TCG definitions
+    pub tcg_directories : Vec<PathBuf>,
+
+    /// Directory card images are loaded from
+    pub image_directory : PathBuf,
+
+    /// Directory decks and game logs are saved to by default
+    pub deck_directory : PathBuf,
+
+    /// Name of the TCG to load on start, or the first one found if None
+    pub default_tcg : Option<String>,
+
+    /// Size in pixels of card images in lists and of the enlarged card image
+    pub small_image_scale : i32,
+    pub large_image_scale : i32
+}
+
+impl Settings {
+    /// The default settings, which keep TCGs, images and decks in the data
+    /// directory (see `files::data_directory`), wherever OpenTCG is started from.
+    pub fn new() -> Settings {
+        let data_directory = files::data_directory();
+        Settings{tcg_directories : vec![data_directory.join("tcgs")],
+            image_directory : data_directory.join("images"),
+            deck_directory : data_directory.join("decks"),
+            default_tcg : None,
+            small_image_scale : DEFAULT_SMALL_SCALE,
+            large_image_scale : DEFAULT_LARGE_SCALE}
+    }
+
+    pub fn default_path() -> PathBuf {
+        files::config_directory().join(SETTINGS_FILE)
+    }
+
+    /// Reads the settings file, or writes one with the default settings if there is none yet.
+    pub fn read_or_new() -> OpenTcgResult<Settings> {
+        let path = Settings::default_path();
+        if path.exists() {
+            Settings::read_from_file(&path)
+        } else {
+            let result = Settings::new();
+            result.write()?;
+            Ok(result)
+        }
+    }
+
+    pub fn write(&self) -> OpenTcgResult<()> {
+        let path = Settings::default_path();
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory).map_err(|e| OpenTcgError::Io{path : directory.to_path_buf(), error : e})?;
+        }
+        self.write_to_file(&path)
+    }
+
+    /// Finds the definitions of every TCG in the search directories.
+    pub fn discover_tcgs(&self) -> Vec<PathBuf> {
+        TCG::discover(&self.tcg_directories)
+    }
+
+    /// Finds the definition of the default TCG, falling back to the first one found
+    /// if there is no default or it isn't installed.
+    pub fn find_default_tcg(&self) -> Option<PathBuf> {
+        let found = self.discover_tcgs();
+        if let Some(ref name) = self.default_tcg {
+            for path in found.iter() {
+                match TCG::read_name(path) {
+                    Ok(ref tcg_name) if tcg_name == name => return Some(path.clone()),
+                    _ => ()
+                }
+            }
+        }
+        found.into_iter().next()
+    }
+
+    pub fn write_to_file(&self, filename : &PathBuf) -> OpenTcgResult<()> {
+        let package = Package::new();
+        let doc = package.as_document();
+
+        let root = doc.create_element("Settings");
+        let directories = doc.create_element("TCGDirectories");
+        for directory in self.tcg_directories.iter() {
+            xml::append_text_element(&doc, &directories, "Directory", &directory.to_string_lossy());
+        }
+        root.append_child(directories);
+        xml::append_text_element(&doc, &root, "ImageDirectory", &self.image_directory.to_string_lossy());
+        xml::append_text_element(&doc, &root, "DeckDirectory", &self.deck_directory.to_string_lossy());
+        if let Some(ref name) = self.default_tcg {
+            xml::append_text_element(&doc, &root, "DefaultTCG", name);
+        }
+
+        let scales = doc.create_element("ImageScales");
+        xml::append_text_element(&doc, &scales, "Small", &self.small_image_scale.to_string());
+        xml::append_text_element(&doc, &scales, "Large", &self.large_image_scale.to_string());
+        root.append_child(scales);
+        doc.root().append_child(root);
+
+        let mut file = File::create(filename).map_err(|e| OpenTcgError::Io{path : filename.clone(), error : e})?;
+        format_document(&doc, &mut file).map_err(|e| OpenTcgError::Io{path : filename.clone(), error : e})
+    }
+
+    pub fn read_from_file(filename : &PathBuf) -> OpenTcgResult<Settings> {
+        let file = files::document_from_file(filename)?;
+        let root = file.root_element("Settings")?;
+        let path = |name : &str| xml::find_child(&root, name).map(|e| PathBuf::from(xml::read_text_from_element(&e)));
+
+        let mut result = Settings::new();
+        if let Some(directories) = xml::find_child(&root, "TCGDirectories") {
+            result.tcg_directories = directories.children().iter()
+                .filter_map(|c| c.element())
+                .map(|e| PathBuf::from(xml::read_text_from_element(&e)))
+                .collect();
+        }
+        if let Some(directory) = path("ImageDirectory") {
+            result.image_directory = directory;
+        }
+        if let Some(directory) = path("DeckDirectory") {
+            result.deck_directory = directory;
+        }
+        result.default_tcg = xml::find_child(&root, "DefaultTCG").map(|e| xml::read_text_from_element(&e));
+
+        if let Some(scales) = xml::find_child(&root, "ImageScales") {
+            if let Some(small) = xml::find_child(&scales, "Small") {
+                result.small_image_scale = xml::read_int_from_element(&small, &file)?;
+            }
+            if let Some(large) = xml::find_child(&scales, "Large") {
+                result.large_image_scale = xml::read_int_from_element(&large, &file)?;
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::io::Write;
+    use std::process;
+
+    fn temp_path(name : &str) -> PathBuf {
+        env::temp_dir().join(format!("open_tcg_settings_{}_{}.xml", name, process::id()))
+    }
+
+    #[test]
+    fn defaults_are_in_the_data_directory() {
+        let settings = Settings::new();
+        let data_directory = files::data_directory();
+        assert!(settings.tcg_directories.iter().all(|d| d.starts_with(&data_directory)));
+        assert!(settings.image_directory.starts_with(&data_directory));
+        assert!(settings.deck_directory.starts_with(&data_directory));
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut settings = Settings::new();
+        settings.tcg_directories = vec![PathBuf::from("/opt/tcgs"), PathBuf::from("/home/johan/tcgs")];
+        settings.image_directory = PathBuf::from("/home/johan/images");
+        settings.deck_directory = PathBuf::from("/home/johan/decks");
+        settings.default_tcg = Some("Example TCG".to_string());
+        settings.small_image_scale = 80;
+        settings.large_image_scale = 300;
+
+        let path = temp_path("round_trip");
+        settings.write_to_file(&path).unwrap();
+        let read = Settings::read_from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(read, settings);
+    }
+
+    #[test]
+    fn missing_elements_keep_defaults() {
+        let path = temp_path("partial");
+        File::create(&path).unwrap().write_all(br#"<?xml version="1.0"?>
+<Settings>
+  <DeckDirectory>/home/johan/decks</DeckDirectory>
+  <ImageScales><Large>300</Large></ImageScales>
+</Settings>"#).unwrap();
+        let read = Settings::read_from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let mut expected = Settings::new();
+        expected.deck_directory = PathBuf::from("/home/johan/decks");
+        expected.large_image_scale = 300;
+        assert_eq!(read, expected);
+    }
+}
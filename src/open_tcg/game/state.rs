@@ -0,0 +1,625 @@
+// MIT License
+
+// Copyright (c) 2016 Johan Burke
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use super::card::CardInfo;
+use super::deck::Deck;
+use super::random::{self, Rng, Secret};
+use super::tcg::TCG;
+
+/// Identifies a single card instance for the duration of a game.
+///
+/// Ids are opaque: they're drawn at random from the owner's secret, so an id
+/// says nothing about which card it is or where it started out.
+pub type CardId = u32;
+
+/// Index of a zone within a `GameState`.
+pub type ZoneId = usize;
+
+/// Index of a player within a `GameState`.
+pub type PlayerId = usize;
+
+/// Names of the zones created by `ZoneInfo::default_zones`.
+pub const DECK_ZONE : &'static str = "Deck";
+pub const HAND_ZONE : &'static str = "Hand";
+pub const FIELD_ZONE : &'static str = "Field";
+pub const DISCARD_ZONE : &'static str = "Discard";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Orientation {
+    Upright,
+
+    /// Turned sideways, e.g. tapped or in defense position
+    Sideways
+}
+
+/// A card in play. Unlike a `CardInfo`, which is shared by every copy of a card,
+/// this holds the attributes of one particular copy during a game.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CardInstance {
+    pub id : CardId,
+
+    /// Name of the `CardInfo` this is an instance of
+    pub card_name : String,
+
+    /// The player whose deck the card came from
+    pub owner : PlayerId,
+
+    pub orientation : Orientation,
+    pub face_up : bool,
+
+    /// Named counters placed on the card
+    pub counters : HashMap<String, i32>
+}
+
+impl CardInstance {
+    pub fn new(id : CardId, card_name : &String, owner : PlayerId) -> CardInstance {
+        CardInstance{id : id, card_name : card_name.clone(), owner : owner,
+            orientation : Orientation::Upright, face_up : false, counters : HashMap::new()}
+    }
+
+    /// Looks up the info of this card in the given TCG.
+    pub fn info<'a>(&self, tcg : &'a TCG) -> Option<&'a CardInfo> {
+        tcg.cards.get(&self.card_name)
+    }
+}
+
+/// Whether each player gets their own copy of a zone or all players share one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ZoneOwner {
+    Player,
+    Shared
+}
+
+/// Who may see the faces of the cards in a zone.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ZoneVisibility {
+    /// Every player, e.g. the field or a discard pile
+    Public,
+
+    /// No player, e.g. a deck
+    Private,
+
+    /// Only the player owning the zone, e.g. a hand
+    OwnerOnly
+}
+
+/// The part a zone plays in actions that need to know which zone to use,
+/// such as drawing a card.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ZoneRole {
+    /// Cards are drawn from the top of this zone
+    DrawPile,
+
+    /// Drawn cards are put into this zone
+    Hand
+}
+
+impl fmt::Display for ZoneRole {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ZoneRole::DrawPile => write!(f, "draw pile"),
+            ZoneRole::Hand => write!(f, "hand")
+        }
+    }
+}
+
+/// This structure defines the play zones a TCG's table is made up of.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZoneInfo {
+    pub name : String,
+    pub owner : ZoneOwner,
+    pub visibility : ZoneVisibility,
+
+    /// Whether the order of the cards in the zone matters
+    pub ordered : bool,
+
+    /// Maximum number of cards the zone can hold, or 0 for no limit
+    pub capacity : u32,
+
+    /// Group of the deck sections whose cards start the game in this zone, if any
+    pub starting_group : Option<u32>,
+
+    /// What the zone is used for, if it has a role in any action
+    pub role : Option<ZoneRole>
+}
+
+impl ZoneInfo {
+    pub fn new() -> ZoneInfo {
+        ZoneInfo{name : String::new(), owner : ZoneOwner::Player, visibility : ZoneVisibility::Public,
+            ordered : false, capacity : 0, starting_group : None, role : None}
+    }
+
+    fn new_with(name : &str, visibility : ZoneVisibility, ordered : bool, starting_group : Option<u32>) -> ZoneInfo {
+        ZoneInfo{name : name.to_string(), owner : ZoneOwner::Player, visibility : visibility,
+            ordered : ordered, capacity : 0, starting_group : starting_group, role : None}
+    }
+
+    /// The zones used when a TCG doesn't declare any: a deck, hand, field and
+    /// discard pile for each player. The deck starts with the cards of the
+    /// group of the TCG's first deck section, and cards are drawn from it into the hand.
+    pub fn default_zones(tcg : &TCG) -> Vec<ZoneInfo> {
+        let deck_group = tcg.sections.first().map(|s| s.group);
+        let mut deck = ZoneInfo::new_with(DECK_ZONE, ZoneVisibility::Private, true, deck_group);
+        deck.role = Some(ZoneRole::DrawPile);
+        let mut hand = ZoneInfo::new_with(HAND_ZONE, ZoneVisibility::OwnerOnly, false, None);
+        hand.role = Some(ZoneRole::Hand);
+        vec![deck, hand,
+            ZoneInfo::new_with(FIELD_ZONE, ZoneVisibility::Public, false, None),
+            ZoneInfo::new_with(DISCARD_ZONE, ZoneVisibility::Public, true, None)]
+    }
+}
+
+/// An area of the table that holds cards, such as a deck, hand or discard pile.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Zone {
+    /// Descriptor of the zone as declared by the TCG.
+    pub info : ZoneInfo,
+
+    /// The player the zone belongs to, or None if it is shared by all players
+    pub owner : Option<PlayerId>,
+
+    /// Cards in the zone, where the last card is the top of the zone
+    pub cards : Vec<CardId>
+}
+
+impl Zone {
+    pub fn new(info : &ZoneInfo, owner : Option<PlayerId>) -> Zone {
+        Zone{info : info.clone(), owner : owner, cards : Vec::new()}
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.info.capacity > 0 && self.cards.len() >= self.info.capacity as usize
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerState {
+    pub name : String,
+
+    /// Named counters belonging to the player, such as life points
+    pub counters : HashMap<String, i32>
+}
+
+impl PlayerState {
+    pub fn new(name : &str) -> PlayerState {
+        PlayerState{name : name.to_string(), counters : HashMap::new()}
+    }
+}
+
+/// The result of a die roll or coin flip, which is a roll of a two-sided die.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DieRoll {
+    pub player : PlayerId,
+    pub sides : u32,
+    pub value : u32
+}
+
+/// Errors resulting from operations that are impossible in the current game state.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GameError {
+    UnknownCard(CardId),
+    UnknownZone(ZoneId),
+    UnknownPlayer(PlayerId),
+    EmptyZone(ZoneId),
+    ZoneFull(ZoneId),
+    MissingZone(ZoneRole),
+    InvalidDie(u32)
+}
+
+impl fmt::Display for GameError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            GameError::UnknownCard(id) => write!(f, "no card with id {}", id),
+            GameError::UnknownZone(id) => write!(f, "no zone with id {}", id),
+            GameError::UnknownPlayer(id) => write!(f, "no player with id {}", id),
+            GameError::EmptyZone(id) => write!(f, "zone {} is empty", id),
+            GameError::ZoneFull(id) => write!(f, "zone {} is full", id),
+            GameError::MissingZone(role) => write!(f, "no zone is used as the {}", role),
+            GameError::InvalidDie(sides) => write!(f, "can't roll a die with {} sides", sides)
+        }
+    }
+}
+
+/// The complete state of a game in progress.
+///
+/// The state holds both players' secrets and the order of every zone, so it
+/// is only meant for hot-seat play, where both players share one table. A
+/// player who has the state can work out everything about the other player's
+/// cards; `GameView` only hides them on screen.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameState {
+    pub players : Vec<PlayerState>,
+    pub zones : Vec<Zone>,
+    cards : HashMap<CardId, CardInstance>,
+
+    /// Generators of the ids of each player's cards, seeded by their secret
+    card_ids : Vec<Rng>,
+
+    /// Set once the game has ended
+    pub finished : bool,
+
+    /// The player who won, or None if the game was a draw or hasn't ended
+    pub winner : Option<PlayerId>,
+
+    /// The most recent die roll or coin flip
+    pub last_roll : Option<DieRoll>,
+
+    /// The secret of each player, which seeds the shuffles of their zones
+    secrets : Vec<Secret>,
+
+    /// Players that were shown a card they otherwise couldn't see, until the card changes zone
+    disclosed : HashMap<CardId, Vec<PlayerId>>
+}
+
+impl GameState {
+    pub fn new() -> GameState {
+        GameState{players : Vec::new(), zones : Vec::new(), cards : HashMap::new(), card_ids : Vec::new(),
+            finished : false, winner : None, last_roll : None, secrets : Vec::new(),
+            disclosed : HashMap::new()}
+    }
+
+    /// Sets up a two player game from the players' decks.
+    ///
+    /// Zones are created from the zones declared by the TCG, or from
+    /// `ZoneInfo::default_zones` if it declares none. The cards of each deck section
+    /// start face down in the zone whose starting group matches the section's group,
+    /// in order of name. Sections no zone starts from get a zone of their own,
+    /// named after the section and visible only to its owner.
+    ///
+    /// The cards aren't shuffled yet; shuffling the zones they start in is up to
+    /// the players, each seeding the shuffles of their zones with their secret.
+    pub fn new_from_decks(tcg : &TCG, first : &Deck, second : &Deck, secrets : [Secret; 2]) -> GameState {
+        let mut result = GameState::new();
+        result.secrets = secrets.to_vec();
+        result.card_ids = secrets.iter().map(|s| Rng::new(s.derive("card ids"))).collect();
+
+        let zone_infos = if tcg.zones.is_empty() { ZoneInfo::default_zones(tcg) } else { tcg.zones.clone() };
+        for info in zone_infos.iter().filter(|z| z.owner == ZoneOwner::Shared) {
+            result.zones.push(Zone::new(info, None));
+        }
+
+        for (player, deck) in [first, second].iter().enumerate() {
+            result.players.push(PlayerState::new(&deck.name));
+            for info in zone_infos.iter().filter(|z| z.owner == ZoneOwner::Player) {
+                result.zones.push(Zone::new(info, Some(player)));
+            }
+
+            for section in deck.sections.iter() {
+                let group = Some(section.info.group);
+                let zone = result.zones.iter().position(|z| z.info.starting_group == group && z.owner == Some(player))
+                    .or_else(|| result.zones.iter().position(|z| z.info.starting_group == group && z.owner == None));
+                let zone = match zone {
+                    Some(zone) => zone,
+                    None => {
+                        let info = ZoneInfo::new_with(&section.info.name, ZoneVisibility::OwnerOnly, false, None);
+                        result.zones.push(Zone::new(&info, Some(player)));
+                        result.zones.len() - 1
+                    }
+                };
+                result.add_section_cards(tcg, section.cards.borrow().iter(), player, zone);
+            }
+        }
+
+        result
+    }
+
+    /// The zones shuffled when the game starts, which are the ordered zones
+    /// that cards start in, such as the decks.
+    pub fn starting_shuffles(&self) -> Vec<ZoneId> {
+        let starts_shuffled = |zone : &Zone| zone.info.ordered && zone.info.starting_group.is_some();
+        (0..self.zones.len()).filter(|&z| starts_shuffled(&self.zones[z])).collect()
+    }
+
+    /// Creates instances for the cards of a deck section and puts them in the given zone.
+    fn add_section_cards<'a, I>(&mut self, tcg : &TCG, cards : I, owner : PlayerId, zone : ZoneId)
+        where I : Iterator<Item = (&'a String, &'a u32)> {
+        let mut names : Vec<(&String, &u32)> = cards.filter(|&(name, _)| tcg.cards.contains_key(name)).collect();
+        names.sort();
+        for (name, &copies) in names {
+            for _ in 0..copies {
+                let id = self.create_card(name, owner);
+                self.zones[zone].cards.push(id);
+            }
+        }
+    }
+
+    /// Creates a new, face down card instance that isn't in any zone yet.
+    /// Its id is the next unused one from the owner's generator of ids, so
+    /// the other player can't tell one of the owner's cards from another by id
+    /// until the owner's secret is revealed.
+    pub fn create_card(&mut self, card_name : &String, owner : PlayerId) -> CardId {
+        let mut id = self.card_ids[owner].next_u64() as CardId;
+        while self.cards.contains_key(&id) {
+            id = self.card_ids[owner].next_u64() as CardId;
+        }
+        self.cards.insert(id, CardInstance::new(id, card_name, owner));
+        id
+    }
+
+    pub fn card(&self, id : CardId) -> Option<&CardInstance> {
+        self.cards.get(&id)
+    }
+
+    pub fn card_mut(&mut self, id : CardId) -> Option<&mut CardInstance> {
+        self.cards.get_mut(&id)
+    }
+
+    pub fn zone(&self, id : ZoneId) -> Option<&Zone> {
+        self.zones.get(id)
+    }
+
+    /// Finds the zone with the given name belonging to the given player,
+    /// or the shared zone with that name if `owner` is None.
+    pub fn find_zone(&self, owner : Option<PlayerId>, name : &str) -> Option<ZoneId> {
+        self.zones.iter().position(|z| z.owner == owner && z.info.name == name)
+    }
+
+    /// Finds the zone with the given role belonging to the given player,
+    /// or the shared zone with that role if the player has none.
+    pub fn find_zone_with_role(&self, player : PlayerId, role : ZoneRole) -> Option<ZoneId> {
+        let has_role = |z : &Zone| z.info.role == Some(role);
+        self.zones.iter().position(|z| has_role(z) && z.owner == Some(player))
+            .or_else(|| self.zones.iter().position(|z| has_role(z) && z.owner == None))
+    }
+
+    /// Returns the zone holding the given card and the card's position within it.
+    pub fn locate(&self, card : CardId) -> Option<(ZoneId, usize)> {
+        for (zone_id, zone) in self.zones.iter().enumerate() {
+            if let Some(position) = zone.cards.iter().position(|&c| c == card) {
+                return Some((zone_id, position));
+            }
+        }
+        None
+    }
+
+    /// Moves a card to another zone. The card is put on top of the zone
+    /// unless a position is given, which is clamped to the size of the zone.
+    /// Moving a card within the zone it is already in only changes its position.
+    pub fn move_card(&mut self, card : CardId, to : ZoneId, position : Option<usize>) -> Result<(), GameError> {
+        if !self.cards.contains_key(&card) {
+            return Err(GameError::UnknownCard(card));
+        }
+        if to >= self.zones.len() {
+            return Err(GameError::UnknownZone(to));
+        }
+
+        let from = self.locate(card);
+        let within_zone = match from {
+            Some((zone, _)) => zone == to,
+            None => false
+        };
+        if !within_zone && self.zones[to].is_full() {
+            return Err(GameError::ZoneFull(to));
+        }
+
+        if let Some((from, index)) = from {
+            self.zones[from].cards.remove(index);
+        }
+        if !within_zone {
+            self.disclosed.remove(&card);
+        }
+        let cards = &mut self.zones[to].cards;
+        let position = match position {
+            Some(p) if p < cards.len() => p,
+            _ => cards.len()
+        };
+        cards.insert(position, card);
+        Ok(())
+    }
+
+    /// Moves the top card of a player's draw pile to their hand and returns it.
+    /// The zones used are the ones with the `DrawPile` and `Hand` roles.
+    pub fn draw(&mut self, player : PlayerId) -> Result<CardId, GameError> {
+        if player >= self.players.len() {
+            return Err(GameError::UnknownPlayer(player));
+        }
+        let deck = self.find_zone_with_role(player, ZoneRole::DrawPile).ok_or(GameError::MissingZone(ZoneRole::DrawPile))?;
+        let hand = self.find_zone_with_role(player, ZoneRole::Hand).ok_or(GameError::MissingZone(ZoneRole::Hand))?;
+
+        let card = match self.zones[deck].cards.last() {
+            Some(&card) => card,
+            None => return Err(GameError::EmptyZone(deck))
+        };
+        self.move_card(card, hand, None)?;
+        Ok(card)
+    }
+
+    /// Shuffles a zone with the seed made from the secret of the given player and
+    /// the other player's contribution, as agreed through a `ShuffleRecord`.
+    /// That player can work out the new order of the zone; see `ShuffleRecord`.
+    /// Players lose track of the cards they were shown in the zone.
+    pub fn shuffle_zone(&mut self, zone : ZoneId, player : PlayerId, contribution : &Secret) -> Result<(), GameError> {
+        let seed = random::combine_secrets(self.secrets.get(player).ok_or(GameError::UnknownPlayer(player))?, contribution);
+        let cards = &mut self.zones.get_mut(zone).ok_or(GameError::UnknownZone(zone))?.cards;
+        Rng::new(seed).shuffle(cards);
+        for card in cards.iter() {
+            self.disclosed.remove(card);
+        }
+        Ok(())
+    }
+
+    /// Whether the given player can see the face of a card.
+    ///
+    /// Face up cards in public zones can be seen by everyone, and face down
+    /// ones by their owner. Cards in zones visible only to their owner can be
+    /// seen by the owner of the zone, or by the owner of the card if the zone
+    /// is shared. Cards in private zones can't be seen. Any card can also be
+    /// seen by the players it was disclosed to.
+    pub fn is_visible_to(&self, card : CardId, viewer : PlayerId) -> bool {
+        let instance = match self.cards.get(&card) {
+            Some(instance) => instance,
+            None => return false
+        };
+        if self.disclosed.get(&card).map_or(false, |players| players.contains(&viewer)) {
+            return true;
+        }
+        let zone = match self.locate(card) {
+            Some((zone, _)) => &self.zones[zone],
+            None => return instance.owner == viewer
+        };
+        match zone.info.visibility {
+            ZoneVisibility::Public => instance.face_up || instance.owner == viewer,
+            ZoneVisibility::OwnerOnly => zone.owner.unwrap_or(instance.owner) == viewer,
+            ZoneVisibility::Private => false
+        }
+    }
+
+    /// Lets the given players see a card until it changes zone.
+    pub fn disclose(&mut self, card : CardId, players : &[PlayerId]) -> Result<(), GameError> {
+        if !self.cards.contains_key(&card) {
+            return Err(GameError::UnknownCard(card));
+        }
+        if let Some(&player) = players.iter().find(|&&p| p >= self.players.len()) {
+            return Err(GameError::UnknownPlayer(player));
+        }
+        let disclosed = self.disclosed.entry(card).or_insert_with(Vec::new);
+        for &player in players {
+            if !disclosed.contains(&player) {
+                disclosed.push(player);
+            }
+        }
+        disclosed.sort();
+        Ok(())
+    }
+
+    /// Shows a card to every player.
+    pub fn reveal(&mut self, card : CardId) -> Result<(), GameError> {
+        let players : Vec<PlayerId> = (0..self.players.len()).collect();
+        self.disclose(card, &players)
+    }
+
+    /// Shows the top `count` cards of a zone to a player, or all its cards if there are fewer.
+    pub fn look_at_top(&mut self, player : PlayerId, zone : ZoneId, count : usize) -> Result<(), GameError> {
+        let cards = self.zones.get(zone).ok_or(GameError::UnknownZone(zone))?.cards.clone();
+        for &card in cards.iter().rev().take(count) {
+            self.disclose(card, &[player])?;
+        }
+        Ok(())
+    }
+
+    /// Rolls a die for a player with the given seed, which the players agreed on
+    /// through a `JointRandom`, and returns the result.
+    pub fn roll_die(&mut self, player : PlayerId, sides : u32, seed : u64) -> Result<u32, GameError> {
+        if player >= self.players.len() {
+            return Err(GameError::UnknownPlayer(player));
+        }
+        if sides == 0 {
+            return Err(GameError::InvalidDie(sides));
+        }
+        let value = Rng::new(seed).roll_die(sides);
+        self.last_roll = Some(DieRoll{player : player, sides : sides, value : value});
+        Ok(value)
+    }
+
+    pub fn set_orientation(&mut self, card : CardId, orientation : Orientation) -> Result<(), GameError> {
+        let instance = self.cards.get_mut(&card).ok_or(GameError::UnknownCard(card))?;
+        instance.orientation = orientation;
+        Ok(())
+    }
+
+    pub fn set_face_up(&mut self, card : CardId, face_up : bool) -> Result<(), GameError> {
+        let instance = self.cards.get_mut(&card).ok_or(GameError::UnknownCard(card))?;
+        instance.face_up = face_up;
+        Ok(())
+    }
+
+    /// Adds `delta` to the named counter on a card, creating the counter if needed.
+    pub fn change_card_counter(&mut self, card : CardId, name : &str, delta : i32) -> Result<(), GameError> {
+        let instance = self.cards.get_mut(&card).ok_or(GameError::UnknownCard(card))?;
+        *instance.counters.entry(name.to_string()).or_insert(0) += delta;
+        Ok(())
+    }
+
+    /// Adds `delta` to the named counter of a player, creating the counter if needed.
+    pub fn change_player_counter(&mut self, player : PlayerId, name : &str, delta : i32) -> Result<(), GameError> {
+        let state = self.players.get_mut(player).ok_or(GameError::UnknownPlayer(player))?;
+        *state.counters.entry(name.to_string()).or_insert(0) += delta;
+        Ok(())
+    }
+
+    pub fn end(&mut self, winner : Option<PlayerId>) {
+        self.finished = true;
+        self.winner = winner;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::deck::DeckSectionInfo;
+
+    fn test_tcg(zones : Vec<ZoneInfo>) -> TCG {
+        let mut tcg = TCG::new();
+        let mut section = DeckSectionInfo::new();
+        section.name = "Main".to_string();
+        tcg.sections.push(section);
+        let mut card = CardInfo::new();
+        card.name = "Goblin".to_string();
+        tcg.cards.insert(card.name.clone(), card);
+        tcg.zones = zones;
+        tcg
+    }
+
+    fn secrets() -> [Secret; 2] {
+        [Secret([1; random::SECRET_LEN]), Secret([2; random::SECRET_LEN])]
+    }
+
+    fn test_deck(tcg : &TCG, copies : u32) -> Deck {
+        let deck = tcg.new_deck();
+        deck.sections[0].cards.borrow_mut().insert("Goblin".to_string(), copies);
+        deck
+    }
+
+    #[test]
+    fn draw_uses_zone_roles() {
+        let mut library = ZoneInfo::new_with("Library", ZoneVisibility::Private, true, Some(0));
+        library.role = Some(ZoneRole::DrawPile);
+        let mut hand = ZoneInfo::new_with("Hand", ZoneVisibility::OwnerOnly, false, None);
+        hand.role = Some(ZoneRole::Hand);
+        let tcg = test_tcg(vec![library, hand]);
+        let deck = test_deck(&tcg, 2);
+        let mut state = GameState::new_from_decks(&tcg, &deck, &deck, secrets());
+
+        let card = state.draw(1).unwrap();
+        let hand = state.find_zone(Some(1), "Hand").unwrap();
+        assert_eq!(state.locate(card), Some((hand, 0)));
+        state.draw(1).unwrap();
+        let library = state.find_zone(Some(1), "Library").unwrap();
+        assert_eq!(state.draw(1), Err(GameError::EmptyZone(library)));
+    }
+
+    #[test]
+    fn draw_needs_zone_roles() {
+        let tcg = test_tcg(vec![ZoneInfo::new_with(DECK_ZONE, ZoneVisibility::Private, true, Some(0)),
+                                ZoneInfo::new_with(HAND_ZONE, ZoneVisibility::OwnerOnly, false, None)]);
+        let deck = test_deck(&tcg, 2);
+        let mut state = GameState::new_from_decks(&tcg, &deck, &deck, secrets());
+        assert_eq!(state.draw(0), Err(GameError::MissingZone(ZoneRole::DrawPile)));
+
+        let tcg = test_tcg(Vec::new());
+        let mut state = GameState::new_from_decks(&tcg, &deck, &deck, secrets());
+        assert!(state.draw(0).is_ok());
+    }
+}
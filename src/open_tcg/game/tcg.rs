@@ -22,13 +22,17 @@
 
 extern crate sxd_document;
 
-use std::collections::HashMap;
-use std::fs;
+use std::collections::{BTreeSet, HashMap};
+use std::fs::{self, File};
+use std::io::prelude::*;
 use std::path::{Path, PathBuf};
 
 use open_tcg::game::deck::*;
-use open_tcg::game::card::{CardInfo, CardType};
+use open_tcg::game::card::{self, CardInfo, CardType};
+use open_tcg::game::state::{ZoneInfo, ZoneOwner, ZoneRole, ZoneVisibility};
 use open_tcg::util::{files, xml};
+use open_tcg::util::error::{OpenTcgError, OpenTcgResult};
+use open_tcg::util::files::XmlFile;
 
 use self::sxd_document::QName;
 use self::sxd_document::dom::Element;
@@ -52,32 +56,76 @@ pub struct TCG {
 
     pub cards : CardMap,
 
-    pub card_types : CardTypes
+    pub card_types : CardTypes,
+
+    /// Play zones declared by the TCG. If empty, `ZoneInfo::default_zones` is used.
+    pub zones : Vec<ZoneInfo>,
+
+    /// Card and type files that could not be loaded.
+    /// These are skipped so that the rest of the TCG remains usable.
+    pub load_errors : Vec<OpenTcgError>
 }
 
 impl TCG {
     pub fn new() -> TCG {
         TCG{cards : HashMap::new(), name : String::new(),
             card_limit : 0, set_file : String::new(),
-            sections : Vec::new(), card_types : HashMap::new()}
+            sections : Vec::new(), card_types : HashMap::new(), zones : Vec::new(),
+            load_errors : Vec::new()}
+    }
+
+    /// Returns the paths of the files in the given directory, recording any failure in `errors`.
+    fn read_dir_paths(directory : &PathBuf, errors : &mut Vec<OpenTcgError>) -> Vec<PathBuf> {
+        let mut result = Vec::new();
+
+        match fs::read_dir(directory) {
+            Ok(entries) => {
+                for entry in entries {
+                    match entry {
+                        Ok(entry) => result.push(entry.path()),
+                        Err(e) => errors.push(OpenTcgError::Io{path : directory.clone(), error : e})
+                    }
+                }
+            },
+            Err(e) => errors.push(OpenTcgError::Io{path : directory.clone(), error : e})
+        }
+
+        result
     }
 
-    fn read_card_types(directory : &PathBuf) -> CardTypes {
+    fn read_card_types(directory : &PathBuf, errors : &mut Vec<OpenTcgError>) -> CardTypes {
         let mut result = HashMap::new();
+        let mut paths = HashMap::new();
 
         let path = Path::new(directory);
         if path.exists() {
-            for entry in fs::read_dir(path).expect("Error reading directory") {
-                let entry = entry.expect("Error reading directory entry");
-                let card_type = CardType::new_from_file(&entry.path());
-                let name = card_type.name.clone();
-                result.insert(name, card_type);
+            for entry in TCG::read_dir_paths(directory, errors) {
+                match CardType::new_from_file(&entry) {
+                    Ok(card_type) => {
+                        let name = card_type.name.clone();
+                        paths.insert(name.clone(), entry.clone());
+                        result.insert(name, card_type);
+                    },
+                    Err(e) => errors.push(e)
+                }
             }
         }
 
+        card::resolve_card_types(&mut result, &paths, errors);
+
         result
     }
 
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    /// Names of the sets the TCG's cards belong to, in alphabetical order.
+    pub fn set_names(&self) -> Vec<String> {
+        let names : BTreeSet<&String> = self.cards.values().map(|c| &c.set_name).collect();
+        names.into_iter().cloned().collect()
+    }
+
     pub fn new_deck(&self) -> Deck {
         let mut result = Deck{ sections : Vec::new(), name : String::new() };
 
@@ -90,32 +138,45 @@ impl TCG {
         result
     }
 
-    fn read_set(set_name : &String, cards : &mut CardMap) {
-        let path_buf = PathBuf::from(set_name);
+    fn read_set(set_name : &String, base : &Path, card_types : &CardTypes, cards : &mut CardMap, errors : &mut Vec<OpenTcgError>) {
+        let path_buf = base.join(set_name);
         let path = Path::new(&path_buf);
 
         if path.exists() {
-            for entry in fs::read_dir(path).expect("Error reading directory") {
-                let entry = entry.expect("Error reading directory entry");
-                let card = CardInfo::new_from_file(&entry.path());
-                let name = card.name.clone();
-                cards.insert(name, card);
+            for entry in TCG::read_dir_paths(&path_buf, errors) {
+                match CardInfo::new_from_file(&entry, card_types, errors) {
+                    Ok(card) => {
+                        let name = card.name.clone();
+                        cards.insert(name, card);
+                    },
+                    Err(e) => errors.push(e)
+                }
             }
         }
     }
 
-    fn read_cards(set_file : &PathBuf) -> CardMap {
+    fn read_cards(set_file : &PathBuf, base : &Path, card_types : &CardTypes, errors : &mut Vec<OpenTcgError>) -> CardMap {
         let mut result = HashMap::new();
 
-        for line in files::lines_from_file(set_file) {
-            let line = line.expect("Error reading line from file");
-            TCG::read_set(&line.trim().to_string(), &mut result);
+        match files::lines_from_file(set_file) {
+            Ok(lines) => {
+                for line in lines {
+                    match line {
+                        Ok(line) => TCG::read_set(&line.trim().to_string(), base, card_types, &mut result, errors),
+                        Err(e) => {
+                            errors.push(OpenTcgError::Io{path : set_file.clone(), error : e});
+                            break;
+                        }
+                    }
+                }
+            },
+            Err(e) => errors.push(e)
         }
 
         result
     }
 
-    fn read_deck(deck_element : &Element) -> DeckSections {
+    fn read_deck(deck_element : &Element, file : &XmlFile) -> OpenTcgResult<DeckSections> {
         let mut sections = Vec::new();
 
         let section_name = QName::new("Subsection");
@@ -136,15 +197,15 @@ impl TCG {
                             if element_name == name_name {
                                 section.name = xml::read_text_from_element(&section_element);
                             } else if element_name == group_name {
-                                section.group = xml::read_num_from_element(&section_element);
+                                section.group = xml::read_num_from_element(&section_element, file)?;
                             } else if element_name == min_name {
-                                section.min_size = xml::read_num_from_element(&section_element);
+                                section.min_size = xml::read_num_from_element(&section_element, file)?;
                             } else if element_name == max_name {
-                                section.max_size = xml::read_num_from_element(&section_element);
+                                section.max_size = xml::read_num_from_element(&section_element, file)?;
                             } else if element_name == row_name {
-                                section.rows = xml::read_num_from_element(&section_element);
+                                section.rows = xml::read_num_from_element(&section_element, file)?;
                             } else if element_name == columns_name {
-                                section.columns = xml::read_num_from_element(&section_element);
+                                section.columns = xml::read_num_from_element(&section_element, file)?;
                             }
                         }
                     }
@@ -153,49 +214,245 @@ impl TCG {
             }
         }
 
-        sections
+        Ok(sections)
     }
 
-    pub fn new_from_file(filename : &PathBuf) -> TCG {
+    /// Reads the `<Zone>` children of a `<Zones>` element. Elements missing from a
+    /// zone keep the defaults of `ZoneInfo::new`, and an unknown `<Owner>`,
+    /// `<Visibility>` or `<Role>` is an error.
+    fn read_zones(zones_element : &Element, file : &XmlFile) -> OpenTcgResult<Vec<ZoneInfo>> {
+        let mut zones = Vec::new();
+
+        let zone_name = QName::new("Zone");
+        let name_name = QName::new("Name");
+        let owner_name = QName::new("Owner");
+        let visibility_name = QName::new("Visibility");
+        let ordered_name = QName::new("Ordered");
+        let capacity_name = QName::new("Capacity");
+        let group_name = QName::new("StartingGroup");
+        let role_name = QName::new("Role");
+
+        for e in zones_element.children() {
+            if let Some(element) = e.element() {
+                if element.name() == zone_name {
+                    let mut zone = ZoneInfo::new();
+                    for zone_info in element.children() {
+                        if let Some(zone_element) = zone_info.element() {
+                            let element_name = zone_element.name();
+                            if element_name == name_name {
+                                zone.name = xml::read_text_from_element(&zone_element);
+                            } else if element_name == owner_name {
+                                zone.owner = match xml::read_text_from_element(&zone_element).to_lowercase().as_str() {
+                                    "player" => ZoneOwner::Player,
+                                    "shared" => ZoneOwner::Shared,
+                                    _ => return Err(xml::invalid_value(&zone_element, file))
+                                };
+                            } else if element_name == visibility_name {
+                                zone.visibility = match xml::read_text_from_element(&zone_element).to_lowercase().as_str() {
+                                    "public" => ZoneVisibility::Public,
+                                    "private" => ZoneVisibility::Private,
+                                    "owner" => ZoneVisibility::OwnerOnly,
+                                    _ => return Err(xml::invalid_value(&zone_element, file))
+                                };
+                            } else if element_name == ordered_name {
+                                zone.ordered = xml::read_bool_from_element(&zone_element, file)?;
+                            } else if element_name == capacity_name {
+                                zone.capacity = xml::read_num_from_element(&zone_element, file)?;
+                            } else if element_name == group_name {
+                                zone.starting_group = Some(xml::read_num_from_element(&zone_element, file)?);
+                            } else if element_name == role_name {
+                                zone.role = match xml::read_text_from_element(&zone_element).to_lowercase().as_str() {
+                                    "draw" => Some(ZoneRole::DrawPile),
+                                    "hand" => Some(ZoneRole::Hand),
+                                    _ => return Err(xml::invalid_value(&zone_element, file))
+                                };
+                            }
+                        }
+                    }
+                    zones.push(zone);
+                }
+            }
+        }
+
+        Ok(zones)
+    }
+
+    /// Loads a TCG definition along with its card types and card pool.
+    ///
+    /// An error is returned only if the definition file itself can't be loaded.
+    /// Individual card and type files that fail to load are collected
+    /// in `load_errors` instead.
+    ///
+    /// Relative paths in the definition and in its set file are taken
+    /// relative to the directory of the definition.
+    pub fn new_from_file(filename : &PathBuf) -> OpenTcgResult<TCG> {
         let mut instance = TCG::new();
+        let base = filename.parent().unwrap_or(Path::new("")).to_path_buf();
 
-        let pkg = files::document_from_file(filename);
-        let doc = pkg.as_document();
-        let root = doc.root();
-        let children = root.children();
+        let file = files::document_from_file(filename)?;
+        let tcg_root = file.root_element("TCG")?;
 
         let name_name = QName::new("Name");
         let card_limit_name = QName::new("CardLimit");
         let sets_name = QName::new("SetFile");
         let deck_name = QName::new("Deck");
         let types_name = QName::new("TypeDirectory");
+        let zones_name = QName::new("Zones");
 
-        if let Some(tcg_root) = children[0].element() {
-            if tcg_root.name() == QName::new("TCG") {
-                for e in tcg_root.children() {
-                    if let Some(element) = e.element() {
-                        let element_name = element.name();
-                        if element_name == name_name {
-                            instance.name = xml::read_text_from_element(&element);
-                        } else if element_name == card_limit_name {
-                            instance.card_limit = xml::read_num_from_element(&element);
-                        } else if element_name == sets_name {
-                            instance.set_file = xml::read_text_from_element(&element);
-                            instance.cards = TCG::read_cards(&PathBuf::from(&instance.set_file));
-                        } else if element_name == types_name {
-                            let type_dir = xml::read_text_from_element(&element);
-                            instance.card_types = TCG::read_card_types(&PathBuf::from(&type_dir));
-                        } else if element_name == deck_name {
-                            instance.sections = TCG::read_deck(&element);
-                        }
-                    }
+        for e in tcg_root.children() {
+            if let Some(element) = e.element() {
+                let element_name = element.name();
+                if element_name == name_name {
+                    instance.name = xml::read_text_from_element(&element);
+                } else if element_name == card_limit_name {
+                    instance.card_limit = xml::read_num_from_element(&element, &file)?;
+                } else if element_name == sets_name {
+                    instance.set_file = xml::read_text_from_element(&element);
+                } else if element_name == types_name {
+                    let type_dir = xml::read_text_from_element(&element);
+                    instance.card_types = TCG::read_card_types(&base.join(&type_dir),
+                        &mut instance.load_errors);
+                } else if element_name == deck_name {
+                    instance.sections = TCG::read_deck(&element, &file)?;
+                } else if element_name == zones_name {
+                    instance.zones = TCG::read_zones(&element, &file)?;
                 }
             }
         }
+        // cards are read last since their types must be known
+        if !instance.set_file.is_empty() {
+            instance.cards = TCG::read_cards(&base.join(&instance.set_file), &base, &instance.card_types,
+                &mut instance.load_errors);
+        }
         // TODO: more stuff here...
 
-        instance
+        Ok(instance)
+    }
+
+    /// Reads only the name of the TCG defined in the given file.
+    pub fn read_name(filename : &PathBuf) -> OpenTcgResult<String> {
+        let file = files::document_from_file(filename)?;
+        let tcg_root = file.root_element("TCG")?;
+        Ok(xml::find_child(&tcg_root, "Name").map(|e| xml::read_text_from_element(&e)).unwrap_or(String::new()))
+    }
+
+    /// Finds the TCG definitions in the given directories and their immediate subdirectories,
+    /// so that each TCG can be installed in a directory of its own.
+    /// A definition is any `.xml` file whose root element is `<TCG>`.
+    pub fn discover(directories : &[PathBuf]) -> Vec<PathBuf> {
+        let mut result = Vec::new();
+        // errors only mean a directory can't be searched, which isn't worth reporting
+        let mut errors = Vec::new();
+
+        for directory in directories.iter().filter(|d| d.is_dir()) {
+            let mut candidates = Vec::new();
+            for entry in TCG::read_dir_paths(directory, &mut errors) {
+                if entry.is_dir() {
+                    candidates.append(&mut TCG::read_dir_paths(&entry, &mut errors));
+                } else {
+                    candidates.push(entry);
+                }
+            }
+            candidates.sort();
+
+            for candidate in candidates {
+                if TCG::is_definition(&candidate) && !result.contains(&candidate) {
+                    result.push(candidate);
+                }
+            }
+        }
+
+        result
+    }
+
+    fn is_definition(path : &PathBuf) -> bool {
+        if path.extension().map(|e| e != "xml").unwrap_or(true) {
+            return false;
+        }
+        let mut source = String::new();
+        match File::open(path).and_then(|mut f| f.read_to_string(&mut source)) {
+            // card and type files are skipped without being parsed
+            Ok(_) if source.contains("<TCG") => (),
+            _ => return false
+        }
+        match XmlFile::new_from_string(path, source) {
+            Ok(file) => file.root_element("TCG").is_ok(),
+            Err(_) => false
+        }
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    /// Loads a TCG whose definition holds the given `<Zones>` element.
+    fn read_zones(name : &str, zones : &str) -> OpenTcgResult<Vec<ZoneInfo>> {
+        let path = env::temp_dir().join(format!("open_tcg_zones_{}_{}.xml", name, process::id()));
+        let definition = format!("<?xml version=\"1.0\"?>\n<TCG>\n<Name>Test</Name>\n{}\n</TCG>", zones);
+        File::create(&path).unwrap().write_all(definition.as_bytes()).unwrap();
+        let result = TCG::new_from_file(&path).map(|tcg| tcg.zones);
+        fs::remove_file(&path).unwrap();
+        result
+    }
+
+    #[test]
+    fn custom_zones() {
+        let zones = read_zones("custom", r#"<Zones>
+  <Zone>
+    <Name>Library</Name>
+    <Owner>Player</Owner>
+    <Visibility>Private</Visibility>
+    <Ordered>true</Ordered>
+    <StartingGroup>0</StartingGroup>
+    <Role>Draw</Role>
+  </Zone>
+  <Zone>
+    <Name>Hand</Name>
+    <Visibility>owner</Visibility>
+    <Capacity>7</Capacity>
+    <Role>hand</Role>
+  </Zone>
+  <Zone>
+    <Name>Stack</Name>
+    <Owner>Shared</Owner>
+  </Zone>
+</Zones>"#).unwrap();
+
+        let mut library = ZoneInfo::new();
+        library.name = "Library".to_string();
+        library.visibility = ZoneVisibility::Private;
+        library.ordered = true;
+        library.starting_group = Some(0);
+        library.role = Some(ZoneRole::DrawPile);
+
+        let mut hand = ZoneInfo::new();
+        hand.name = "Hand".to_string();
+        hand.visibility = ZoneVisibility::OwnerOnly;
+        hand.capacity = 7;
+        hand.role = Some(ZoneRole::Hand);
+
+        // elements that are left out keep their defaults
+        let mut stack = ZoneInfo::new();
+        stack.name = "Stack".to_string();
+        stack.owner = ZoneOwner::Shared;
+
+        assert_eq!(zones, vec![library, hand, stack]);
+    }
 
+    #[test]
+    fn invalid_zones() {
+        for &(name, element) in [("owner", "<Owner>Nobody</Owner>"), ("visibility", "<Visibility>Hidden</Visibility>"),
+                                 ("ordered", "<Ordered>yes</Ordered>"), ("capacity", "<Capacity>-1</Capacity>"),
+                                 ("group", "<StartingGroup>main</StartingGroup>"), ("role", "<Role>Graveyard</Role>")].iter() {
+            let zones = format!("<Zones>\n<Zone><Name>Zone</Name>\n{}\n</Zone>\n</Zones>", element);
+            match read_zones(name, &zones) {
+                Err(OpenTcgError::InvalidValue{line, ..}) | Err(OpenTcgError::InvalidNumber{line, ..}) =>
+                    assert_eq!(line, Some(6), "{}", element),
+                other => panic!("{} gave {:?}", element, other)
+            }
+        }
+    }
+}
@@ -0,0 +1,241 @@
+// MIT License
+
+// Copyright (c) 2016 Johan Burke
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use super::deck::Deck;
+use super::tcg::TCG;
+
+/// A single rule of the TCG that a deck fails to satisfy.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Violation {
+    /// The section holds fewer cards than its `min_size`.
+    SectionTooSmall{section : String, size : u32, min_size : u32},
+
+    /// The section holds more cards than its `max_size`.
+    SectionTooLarge{section : String, size : u32, max_size : u32},
+
+    /// The sections of a group together hold more copies of a card than the TCG's `card_limit`.
+    TooManyCopies{group : u32, card : String, copies : u32, limit : u32},
+
+    /// The section holds a card that isn't part of the TCG's card pool.
+    UnknownCard{section : String, card : String}
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Violation::SectionTooSmall{ref section, size, min_size} =>
+                write!(f, "{} has {} cards but needs at least {}", section, size, min_size),
+            Violation::SectionTooLarge{ref section, size, max_size} =>
+                write!(f, "{} has {} cards but may have at most {}", section, size, max_size),
+            Violation::TooManyCopies{ref card, copies, limit, ..} =>
+                write!(f, "{} copies of {} exceed the limit of {}", copies, card, limit),
+            Violation::UnknownCard{ref section, ref card} =>
+                write!(f, "{} contains unknown card {}", section, card)
+        }
+    }
+}
+
+/// Checks whether one copy of a card can be moved between two sections of a deck.
+///
+/// Returns the rule the move would break: the target section growing past its
+/// `max_size`, or the target's group holding more copies of the card than the
+/// TCG's `card_limit`. The violation describes the deck as it would be after the move.
+pub fn check_move(deck : &Deck, tcg : &TCG, from : usize, to : usize, card : &str) -> Option<Violation> {
+    let source = &deck.sections[from];
+    let target = &deck.sections[to];
+
+    let size = target.size() + 1;
+    if target.info.max_size > 0 && size > target.info.max_size {
+        return Some(Violation::SectionTooLarge{section : target.info.name.clone(),
+            size : size, max_size : target.info.max_size});
+    }
+
+    // copies only change group when the sections are in different groups
+    if source.info.group != target.info.group {
+        let copies = deck.copies_in_group(target.info.group, card) + 1;
+        if copies > tcg.card_limit {
+            return Some(Violation::TooManyCopies{group : target.info.group, card : card.to_string(),
+                copies : copies, limit : tcg.card_limit});
+        }
+    }
+
+    None
+}
+
+/// Checks a whole deck against the rules of the given TCG.
+///
+/// A `max_size` of 0 means the section has no upper bound. Copies of a card
+/// are counted across all sections sharing the same group.
+/// Returns an empty list if the deck is legal.
+pub fn validate_deck(deck : &Deck, tcg : &TCG) -> Vec<Violation> {
+    let mut violations = Vec::new();
+
+    // group -> card name -> copies
+    let mut group_copies : HashMap<u32, HashMap<String, u32>> = HashMap::new();
+
+    for section in deck.sections.iter() {
+        let cards = section.cards.borrow();
+        let mut size = 0;
+
+        let mut names : Vec<&String> = cards.keys().collect();
+        names.sort();
+        for name in names {
+            let copies = cards[name];
+            size += copies;
+
+            if !tcg.cards.contains_key(name) {
+                violations.push(Violation::UnknownCard{section : section.info.name.clone(),
+                    card : name.clone()});
+            }
+
+            *group_copies.entry(section.info.group).or_insert(HashMap::new())
+                .entry(name.clone()).or_insert(0) += copies;
+        }
+
+        if size < section.info.min_size {
+            violations.push(Violation::SectionTooSmall{section : section.info.name.clone(),
+                size : size, min_size : section.info.min_size});
+        }
+        if section.info.max_size > 0 && size > section.info.max_size {
+            violations.push(Violation::SectionTooLarge{section : section.info.name.clone(),
+                size : size, max_size : section.info.max_size});
+        }
+    }
+
+    let mut groups : Vec<&u32> = group_copies.keys().collect();
+    groups.sort();
+    for group in groups {
+        let mut counts : Vec<(&String, &u32)> = group_copies[group].iter().collect();
+        counts.sort();
+        for (name, &copies) in counts {
+            if copies > tcg.card_limit {
+                violations.push(Violation::TooManyCopies{group : *group, card : name.clone(),
+                    copies : copies, limit : tcg.card_limit});
+            }
+        }
+    }
+
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::card::CardInfo;
+    use super::super::deck::DeckSectionInfo;
+
+    /// A TCG allowing 2 copies of a card, with a main deck of 3 to 5 cards and
+    /// an unbounded side deck sharing its group, and an extra deck of its own.
+    fn test_tcg() -> TCG {
+        let mut tcg = TCG::new();
+        tcg.card_limit = 2;
+        for &(name, group, min_size, max_size) in [("Main", 0, 3, 5), ("Side", 0, 0, 0), ("Extra", 1, 0, 2)].iter() {
+            let mut info = DeckSectionInfo::new();
+            info.name = name.to_string();
+            info.group = group;
+            info.min_size = min_size;
+            info.max_size = max_size;
+            tcg.sections.push(info);
+        }
+        for name in ["Goblin", "Knight", "Wizard"].iter() {
+            let mut card = CardInfo::new();
+            card.name = name.to_string();
+            tcg.cards.insert(card.name.clone(), card);
+        }
+        tcg
+    }
+
+    fn test_deck(tcg : &TCG, sections : &[&[(&str, u32)]]) -> Deck {
+        let deck = tcg.new_deck();
+        for (section, cards) in deck.sections.iter().zip(sections.iter()) {
+            for &(name, copies) in cards.iter() {
+                section.cards.borrow_mut().insert(name.to_string(), copies);
+            }
+        }
+        deck
+    }
+
+    #[test]
+    fn legal_deck() {
+        let tcg = test_tcg();
+        let deck = test_deck(&tcg, &[&[("Goblin", 2), ("Knight", 1)], &[("Wizard", 2)], &[("Goblin", 2)]]);
+        assert_eq!(validate_deck(&deck, &tcg), Vec::new());
+    }
+
+    #[test]
+    fn section_sizes() {
+        let tcg = test_tcg();
+        let deck = test_deck(&tcg, &[&[("Goblin", 2)], &[], &[("Goblin", 1), ("Knight", 1), ("Wizard", 1)]]);
+        assert_eq!(validate_deck(&deck, &tcg), vec![
+            Violation::SectionTooSmall{section : "Main".to_string(), size : 2, min_size : 3},
+            Violation::SectionTooLarge{section : "Extra".to_string(), size : 3, max_size : 2}]);
+
+        let deck = test_deck(&tcg, &[&[("Goblin", 2), ("Knight", 2), ("Wizard", 2)], &[], &[]]);
+        assert_eq!(validate_deck(&deck, &tcg), vec![
+            Violation::SectionTooLarge{section : "Main".to_string(), size : 6, max_size : 5}]);
+    }
+
+    #[test]
+    fn unbounded_section() {
+        let mut tcg = test_tcg();
+        tcg.card_limit = 100;
+        let deck = test_deck(&tcg, &[&[("Goblin", 3)], &[("Knight", 60)], &[]]);
+        assert_eq!(validate_deck(&deck, &tcg), Vec::new());
+    }
+
+    #[test]
+    fn copies_are_counted_by_group() {
+        let tcg = test_tcg();
+        // 3 Goblins across Main and Side, but the 2 in Extra are another group
+        let deck = test_deck(&tcg, &[&[("Goblin", 2), ("Knight", 1)], &[("Goblin", 1)], &[("Goblin", 2)]]);
+        assert_eq!(validate_deck(&deck, &tcg), vec![
+            Violation::TooManyCopies{group : 0, card : "Goblin".to_string(), copies : 3, limit : 2}]);
+    }
+
+    #[test]
+    fn unknown_cards() {
+        let tcg = test_tcg();
+        let deck = test_deck(&tcg, &[&[("Goblin", 2), ("Dragon", 1)], &[], &[]]);
+        assert_eq!(validate_deck(&deck, &tcg), vec![
+            Violation::UnknownCard{section : "Main".to_string(), card : "Dragon".to_string()}]);
+    }
+
+    #[test]
+    fn moves() {
+        let tcg = test_tcg();
+        let deck = test_deck(&tcg, &[&[("Goblin", 2), ("Knight", 2), ("Wizard", 1)], &[("Wizard", 1)], &[("Goblin", 2)]]);
+
+        // Main is full
+        assert_eq!(check_move(&deck, &tcg, 1, 0, "Wizard"),
+            Some(Violation::SectionTooLarge{section : "Main".to_string(), size : 6, max_size : 5}));
+        // moving within a group doesn't change the number of copies in it
+        assert_eq!(check_move(&deck, &tcg, 0, 1, "Goblin"), None);
+        // but moving into another group does
+        assert_eq!(check_move(&deck, &tcg, 2, 1, "Goblin"),
+            Some(Violation::TooManyCopies{group : 0, card : "Goblin".to_string(), copies : 3, limit : 2}));
+        assert_eq!(check_move(&deck, &tcg, 0, 2, "Knight"), Some(Violation::SectionTooLarge{
+            section : "Extra".to_string(), size : 3, max_size : 2}));
+    }
+}
@@ -0,0 +1,264 @@
+// MIT License
+
+// Copyright (c) 2016 Johan Burke
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+use std::collections::HashMap;
+
+use super::state::{CardId, CardInstance, DieRoll, GameState, Orientation, PlayerId, PlayerState, ZoneInfo};
+
+/// A card as one player sees it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CardView {
+    /// The player can see the face of the card.
+    Visible(CardInstance),
+
+    /// The player only sees the back of the card. Hidden cards are numbered in
+    /// the order they appear in the view, so `handle` says nothing about which
+    /// card it is or where it was before. What is done to the card, such as
+    /// turning it sideways or putting counters on it, remains visible.
+    Hidden{handle : u32, owner : PlayerId, orientation : Orientation, counters : HashMap<String, i32>}
+}
+
+impl CardView {
+    /// The id of the card, if it is visible.
+    pub fn id(&self) -> Option<CardId> {
+        match *self {
+            CardView::Visible(ref instance) => Some(instance.id),
+            CardView::Hidden{..} => None
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZoneView {
+    pub info : ZoneInfo,
+    pub owner : Option<PlayerId>,
+
+    /// Cards in the zone, where the last card is the top of the zone
+    pub cards : Vec<CardView>
+}
+
+/// The state of a game as one player sees it, with the cards they can't see
+/// replaced by hidden cards. This is what may be shown to that player when
+/// both players share a table. The view is made from the whole `GameState`,
+/// so it doesn't keep cards secret from a player who has the state.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameView {
+    pub viewer : PlayerId,
+    pub players : Vec<PlayerState>,
+
+    /// Zones in the same order as in the game state, so zone ids stay valid
+    pub zones : Vec<ZoneView>,
+
+    pub finished : bool,
+    pub winner : Option<PlayerId>,
+    pub last_roll : Option<DieRoll>
+}
+
+impl GameView {
+    /// Creates the view of the given player.
+    pub fn new(state : &GameState, viewer : PlayerId) -> GameView {
+        let mut next_handle = 0;
+        let mut zones = Vec::new();
+        for zone in state.zones.iter() {
+            let mut cards = Vec::new();
+            for &id in zone.cards.iter() {
+                let instance = match state.card(id) {
+                    Some(instance) => instance,
+                    None => continue
+                };
+                if state.is_visible_to(id, viewer) {
+                    cards.push(CardView::Visible(instance.clone()));
+                } else {
+                    cards.push(CardView::Hidden{handle : next_handle, owner : instance.owner,
+                        orientation : instance.orientation, counters : instance.counters.clone()});
+                    next_handle += 1;
+                }
+            }
+            zones.push(ZoneView{info : zone.info.clone(), owner : zone.owner, cards : cards});
+        }
+
+        GameView{viewer : viewer, players : state.players.clone(), zones : zones,
+            finished : state.finished, winner : state.winner, last_roll : state.last_roll.clone()}
+    }
+
+    /// Finds a visible card.
+    pub fn card(&self, id : CardId) -> Option<&CardInstance> {
+        for zone in self.zones.iter() {
+            for card in zone.cards.iter() {
+                if let CardView::Visible(ref instance) = *card {
+                    if instance.id == id {
+                        return Some(instance);
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+impl GameState {
+    /// The state of the game as the given player sees it.
+    pub fn view(&self, viewer : PlayerId) -> GameView {
+        GameView::new(self, viewer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::action::Action;
+    use super::super::card::CardInfo;
+    use super::super::deck::DeckSectionInfo;
+    use super::super::random::{Secret, SECRET_LEN};
+    use super::super::state::{DECK_ZONE, HAND_ZONE};
+    use super::super::tcg::TCG;
+
+    const CARD_NAMES : [&'static str; 3] = ["Goblin", "Knight", "Wizard"];
+
+    fn test_tcg() -> TCG {
+        let mut tcg = TCG::new();
+        let mut section = DeckSectionInfo::new();
+        section.name = "Main".to_string();
+        tcg.sections.push(section);
+        for name in CARD_NAMES.iter() {
+            let mut card = CardInfo::new();
+            card.name = name.to_string();
+            tcg.cards.insert(card.name.clone(), card);
+        }
+        tcg
+    }
+
+    /// A game with the default zones, where both players' decks hold 4 copies of each card.
+    fn test_state(tcg : &TCG, secrets : [Secret; 2]) -> GameState {
+        let deck = tcg.new_deck();
+        for name in CARD_NAMES.iter() {
+            deck.sections[0].cards.borrow_mut().insert(name.to_string(), 4);
+        }
+        GameState::new_from_decks(tcg, &deck, &deck, secrets)
+    }
+
+    fn secrets() -> [Secret; 2] {
+        [Secret([1; SECRET_LEN]), Secret([2; SECRET_LEN])]
+    }
+
+    fn is_visible(view : &GameView, card : CardId) -> bool {
+        view.card(card).is_some()
+    }
+
+    fn apply(state : &mut GameState, action : Action) {
+        action.apply(state).unwrap();
+    }
+
+    #[test]
+    fn hides_private_cards() {
+        let tcg = test_tcg();
+        let mut state = test_state(&tcg, secrets());
+        apply(&mut state, Action::Draw{player : 0});
+        let hand = state.find_zone(Some(0), HAND_ZONE).unwrap();
+        let deck = state.find_zone(Some(0), DECK_ZONE).unwrap();
+        let card = state.zones[hand].cards[0];
+
+        let own = state.view(0);
+        assert!(is_visible(&own, card));
+        assert!(own.zones[deck].cards.iter().all(|c| c.id().is_none()));
+
+        let other = state.view(1);
+        assert!(!is_visible(&other, card));
+        assert!(other.zones[hand].cards.iter().all(|c| c.id().is_none()));
+
+        // hidden cards are numbered in the order they're shown, whatever their ids
+        let handles : Vec<u32> = other.zones.iter().flat_map(|z| z.cards.iter()).filter_map(|c| match *c {
+            CardView::Hidden{handle, ..} => Some(handle),
+            CardView::Visible(_) => None
+        }).collect();
+        assert_eq!(handles, (0..handles.len() as u32).collect::<Vec<u32>>());
+    }
+
+    #[test]
+    fn ids_are_opaque() {
+        let tcg = test_tcg();
+        let state = test_state(&tcg, secrets());
+        let deck = state.find_zone(Some(0), DECK_ZONE).unwrap();
+        let ids = state.zones[deck].cards.clone();
+
+        // the cards start in order of name, but their ids aren't in any order
+        let mut sorted = ids.clone();
+        sorted.sort();
+        assert!(ids != sorted);
+        assert!(ids != (0..ids.len() as CardId).collect::<Vec<CardId>>());
+
+        // ids depend on the owner's secret, and replaying with the same secrets gives the same ids
+        assert_eq!(test_state(&tcg, secrets()).zones[deck].cards, ids);
+        let other_secrets = [Secret([3; SECRET_LEN]), secrets()[1]];
+        assert!(test_state(&tcg, other_secrets).zones[deck].cards != ids);
+        let other_deck = state.find_zone(Some(1), DECK_ZONE).unwrap();
+        assert!(state.zones[other_deck].cards != ids);
+    }
+
+    #[test]
+    fn reveal_lasts_until_the_card_moves() {
+        let tcg = test_tcg();
+        let mut state = test_state(&tcg, secrets());
+        apply(&mut state, Action::Draw{player : 0});
+        let hand = state.find_zone(Some(0), HAND_ZONE).unwrap();
+        let deck = state.find_zone(Some(0), DECK_ZONE).unwrap();
+        let card = state.zones[hand].cards[0];
+
+        apply(&mut state, Action::Reveal{card : card});
+        assert!(is_visible(&state.view(0), card));
+        assert!(is_visible(&state.view(1), card));
+
+        apply(&mut state, Action::Move{card : card, to : deck, position : None});
+        apply(&mut state, Action::Move{card : card, to : hand, position : None});
+        assert!(is_visible(&state.view(0), card));
+        assert!(!is_visible(&state.view(1), card));
+    }
+
+    #[test]
+    fn peek_shows_one_player() {
+        let tcg = test_tcg();
+        let mut state = test_state(&tcg, secrets());
+        let deck = state.find_zone(Some(0), DECK_ZONE).unwrap();
+        let card = state.zones[deck].cards[0];
+
+        apply(&mut state, Action::Peek{player : 1, card : card});
+        assert!(!is_visible(&state.view(0), card));
+        assert!(is_visible(&state.view(1), card));
+    }
+
+    #[test]
+    fn look_at_top_until_shuffled() {
+        let tcg = test_tcg();
+        let mut state = test_state(&tcg, secrets());
+        let deck = state.find_zone(Some(0), DECK_ZONE).unwrap();
+        let cards = state.zones[deck].cards.clone();
+        let (below, top) = cards.split_at(cards.len() - 3);
+
+        apply(&mut state, Action::LookAtTop{player : 0, zone : deck, count : 3});
+        let own = state.view(0);
+        assert!(top.iter().all(|&card| is_visible(&own, card)));
+        assert!(below.iter().all(|&card| !is_visible(&own, card)));
+        assert!(cards.iter().all(|&card| !is_visible(&state.view(1), card)));
+
+        apply(&mut state, Action::Shuffle{zone : deck, player : 0, contribution : Secret([4; SECRET_LEN])});
+        assert!(cards.iter().all(|&card| !is_visible(&state.view(0), card)));
+    }
+}
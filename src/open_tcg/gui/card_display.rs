@@ -41,7 +41,6 @@ pub struct CardDisplay {
 
 impl CardDisplay {
     // TODO: determine if this needs to be an Rc
-    // TODO: add card image, description, and parameters
     pub fn new(tcg : Rc<TCG>, img_manager : Rc<ImageManager>) -> CardDisplay {
         let glade_src = include_str!("card_display.glade");
         let builder = Builder::new_from_string(glade_src);
@@ -62,7 +61,18 @@ impl CardDisplay {
        if let Some(card) = self.current_tcg.cards.get(name) {
            self.card_name_label.set_text(&card.name);
            if let Some(buffer) = self.card_text_view.get_buffer() {
-               buffer.set_text(&card.text);
+               let mut text = String::new();
+               if !card.card_type.name.is_empty() {
+                   text.push_str(&card.card_type.name);
+                   text.push('\n');
+               }
+               for param in card.card_type.param_names.iter() {
+                   if let Some(value) = card.param_values.get(param) {
+                       text.push_str(&format!("{}: {}\n", param, value));
+                   }
+               }
+               text.push_str(&card.text);
+               buffer.set_text(&text);
            }
            if let Some(img) = self.img_manager.get_large_image(&card.set_code) {
                self.card_image.set_from_pixbuf(Some(&img));
@@ -28,19 +28,30 @@ use std::cell::RefCell;
 
 use gtk::prelude::*;
 use gtk::{Builder, Button, Frame, 
-    SearchEntry, ComboBoxText, SelectionData};
+    SearchEntry, ComboBoxText, SelectionData, Label, CheckButton, Orientation};
 use self::gdk::{EventButton, EventMotion, DragContext};
 use gtk::Box as GtkBox;
 use open_tcg::game::tcg::TCG;
 use open_tcg::game::card::CardInfo;
+use open_tcg::game::search::{self, Query, SortField, SortKey};
+use open_tcg::game::query_parser;
 use super::card_view::{CardView, CardViewType};
 use super::image_manager::ImageManager;
 
 pub struct CardSearch {
     pub frame : Frame,
+    query_search : SearchEntry,
+    query_error_label : Label,
     card_name_search : SearchEntry,
     card_text_search : SearchEntry,
     type_choice : ComboBoxText,
+    sort_box : GtkBox,
+    sort_choice : ComboBoxText,
+    sort_descending : CheckButton,
+    then_sort_choice : ComboBoxText,
+    then_sort_descending : CheckButton,
+    /// Fields offered by the sort choices, in the order they appear
+    sort_fields : Vec<SortField>,
     search_items_box : GtkBox,
     card_view : Rc<CardView>, 
     update_button : Button,
@@ -68,10 +79,18 @@ impl CardSearch {
 
         let tcg_clone = tcg.clone();
         let instance = CardSearch{frame : builder.get_object("card_search").unwrap(),
+            query_search : SearchEntry::new(),
+            query_error_label : Label::new(None),
             current_tcg : tcg,
             card_name_search : builder.get_object("card_name_search").unwrap(),
             card_text_search : builder.get_object("card_text_search").unwrap(),
             type_choice : builder.get_object("type_choice").unwrap(),
+            sort_box : GtkBox::new(Orientation::Horizontal, 5),
+            sort_choice : ComboBoxText::new(),
+            sort_descending : CheckButton::new_with_label("Descending"),
+            then_sort_choice : ComboBoxText::new(),
+            then_sort_descending : CheckButton::new_with_label("Descending"),
+            sort_fields : CardSearch::available_sort_fields(&tcg_clone),
             search_items_box : builder.get_object("search_items_box").unwrap(),
             update_button : builder.get_object("update_button").unwrap(),
             clear_button : builder.get_object("clear_button").unwrap(),
@@ -87,13 +106,73 @@ impl CardSearch {
         }
         instance.type_choice.set_active(0);
 
+        instance.query_search.set_placeholder_text("e.g. type:creature cost<=3 text:\"draw a card\" -set:BASE");
+        instance.search_items_box.pack_start(&instance.query_search, false, false, 0);
+        instance.search_items_box.reorder_child(&instance.query_search, 0);
+        instance.search_items_box.pack_start(&instance.query_error_label, false, false, 0);
+        instance.search_items_box.reorder_child(&instance.query_error_label, 1);
+
+        instance.then_sort_choice.append(None, "(none)");
+        for field in instance.sort_fields.iter() {
+            instance.sort_choice.append(None, &field.label());
+            instance.then_sort_choice.append(None, &field.label());
+        }
+        instance.sort_choice.set_active(0);
+        instance.then_sort_choice.set_active(0);
+        instance.sort_box.pack_start(&Label::new(Some("Sort by:")), false, false, 0);
+        instance.sort_box.pack_start(&instance.sort_choice, false, false, 0);
+        instance.sort_box.pack_start(&instance.sort_descending, false, false, 0);
+        instance.sort_box.pack_start(&Label::new(Some("then by:")), false, false, 0);
+        instance.sort_box.pack_start(&instance.then_sort_choice, false, false, 0);
+        instance.sort_box.pack_start(&instance.then_sort_descending, false, false, 0);
+        instance.search_items_box.pack_start(&instance.sort_box, false, false, 0);
+
         // TODO: add spacing
-        instance.search_items_box.pack_start(&instance.card_view.grid, false, false, 0);
+        instance.search_items_box.pack_start(&instance.card_view.container, false, false, 0);
 
         instance
     }
 
+    fn available_sort_fields(tcg : &TCG) -> Vec<SortField> {
+        let mut fields = vec![SortField::Name, SortField::CardType, SortField::SetCode];
+        for name in search::param_names(tcg) {
+            fields.push(SortField::Param(name));
+        }
+        fields
+    }
+
     fn connect_events(instance : Rc<CardSearch>) {
+        // changing the sort order re-sorts the current results
+        {
+            let instance_copy = instance.clone();
+            instance.sort_choice.connect_changed(move |_| {
+                instance_copy.on_update_clicked();
+            });
+        }
+        {
+            let instance_copy = instance.clone();
+            instance.sort_descending.connect_toggled(move |_| {
+                instance_copy.on_update_clicked();
+            });
+        }
+        {
+            let instance_copy = instance.clone();
+            instance.then_sort_choice.connect_changed(move |_| {
+                instance_copy.on_update_clicked();
+            });
+        }
+        {
+            let instance_copy = instance.clone();
+            instance.then_sort_descending.connect_toggled(move |_| {
+                instance_copy.on_update_clicked();
+            });
+        }
+        {
+            let instance_copy = instance.clone();
+            instance.query_search.connect_activate(move |_| {
+                instance_copy.on_update_clicked();
+            });
+        }
         {
             let instance_copy = instance.clone();
             instance.update_button.connect_clicked(move |_| {
@@ -175,22 +254,54 @@ impl CardSearch {
     }
 
 
-    fn on_update_clicked(&self) {
-        // TODO: update grid of card_view with cards
-        // meeting the current search criteria
-        let mut cards : Vec<CardInfo> = self.current_tcg.cards.values().map(|c| c.clone()).collect();
-        if let Some(text) = self.card_name_search.get_text() {
-            cards = cards.iter().filter(|&c| c.name.contains(&text)).map(|c| c.clone()).collect();
+    /// Builds a query from the current contents of the search fields.
+    /// Returns an error message if the text query can't be parsed.
+    fn build_query(&self) -> Result<Query, String> {
+        let query = match self.query_search.get_text() {
+            Some(text) => query_parser::parse(&text, &self.current_tcg).map_err(|e| format!("{}", e))?,
+            None => Query::All
+        };
+        let name = self.card_name_search.get_text().unwrap_or_default();
+        let text = self.card_text_search.get_text().unwrap_or_default();
+        // the first entry is "All Types"
+        let card_type = if self.type_choice.get_active() > 0 { self.type_choice.get_active_text() } else { None };
+        Ok(search::form_query(query, &name, &text, card_type.as_ref().map(|t| t.as_str())))
+    }
+
+    /// Returns the sort keys currently selected by the user.
+    /// These are left untouched by clearing the search, so they apply to every search.
+    fn sort_keys(&self) -> Vec<SortKey> {
+        let mut keys = Vec::new();
+        let active = self.sort_choice.get_active();
+        if active >= 0 && (active as usize) < self.sort_fields.len() {
+            keys.push(SortKey::new(self.sort_fields[active as usize].clone(), self.sort_descending.get_active()));
         }
-        if let Some(text) = self.card_text_search.get_text() {
-            cards = cards.iter().filter(|&c| c.text.contains(&text)).map(|c| c.clone()).collect();
+        // the first entry of the secondary choice is "(none)"
+        let then_active = self.then_sort_choice.get_active();
+        if then_active > 0 && ((then_active - 1) as usize) < self.sort_fields.len() {
+            keys.push(SortKey::new(self.sort_fields[(then_active - 1) as usize].clone(),
+                self.then_sort_descending.get_active()));
+        }
+        keys
+    }
+
+    fn on_update_clicked(&self) {
+        match self.build_query() {
+            Ok(query) => {
+                self.query_error_label.set_text("");
+                let mut results = query.search(&self.current_tcg);
+                search::sort_cards(&mut results, &self.sort_keys());
+                let cards : Vec<CardInfo> = results.into_iter().map(|c| c.clone()).collect();
+                self.card_view.set_cards(&cards);
+            },
+            Err(message) => self.query_error_label.set_text(&message)
         }
-        self.card_view.set_cards(&cards);
-        // TODO: pass cards to cardview
     }
 
     fn on_clear_clicked(&self) {
         // TODO: not sure if the previous search results should be cleared as well
+        self.query_search.set_text("");
+        self.query_error_label.set_text("");
         self.card_name_search.set_text("");
         self.card_text_search.set_text("");
         self.type_choice.set_active(0);
@@ -28,11 +28,13 @@ extern crate gtk_sys as gtk_ffi;
 extern crate glib;
 
 use std::rc::Rc;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::ptr;
+use std::mem;
 
 use gtk::prelude::*;
-use gtk::{Grid, Image, EventBox, SelectionData, Menu, MenuItem};
+use gtk::{Grid, Image, EventBox, SelectionData, Menu, MenuItem, Button, Label, Orientation};
+use gtk::Box as GtkBox;
 use self::gdk::{EventButton, EventMotion, DragContext};
 
 use open_tcg::game::card::CardInfo;
@@ -52,10 +54,20 @@ pub enum CardViewType {
 }
 
 pub struct CardView {
+    /// Holds the grid of cards along with the controls for switching pages
+    pub container : GtkBox,
     pub grid : Grid,
+    page_box : GtkBox,
+    prev_button : Button,
+    next_button : Button,
+    page_label : Label,
+    /// Index of the page of cards currently shown in the grid
+    page : Cell<usize>,
     images : Vec<Image>,
     boxes : Vec<EventBox>,
     cards : RefCell<Vec<CardInfo>>,
+    /// Set codes of the images loaded for the current page, unloaded when the page changes
+    loaded : RefCell<Vec<String>>,
     card_clicked_events : RefCell<Vec<Box<Fn(&CardView, &String, &EventButton)>>>,
     card_hover_events : RefCell<Vec<Box<Fn(&CardView, &String, &EventMotion)>>>,
     card_drag_data_get_events : RefCell<Vec<Box<Fn(&CardView, &DragContext, &SelectionData, u32, u32)>>>,
@@ -89,6 +101,18 @@ impl CardView {
     }
 
     fn connect_events(instance : Rc<CardView>) {
+        {
+            let instance_copy = instance.clone();
+            instance.prev_button.connect_clicked(move |_| {
+                instance_copy.on_prev_clicked();
+            });
+        }
+        {
+            let instance_copy = instance.clone();
+            instance.next_button.connect_clicked(move |_| {
+                instance_copy.on_next_clicked();
+            });
+        }
         for i in 0..instance.row_count {
             for j in 0..instance.col_count {
                 let index = i * instance.col_count + j;
@@ -290,10 +314,17 @@ impl CardView {
         // then we can simply set those lying past a certain index
         // to not be visible
         let count = row_count * col_count;
-        let mut result = CardView{grid : Grid::new(),
+        let mut result = CardView{container : GtkBox::new(Orientation::Vertical, 5),
+            grid : Grid::new(),
+            page_box : GtkBox::new(Orientation::Horizontal, 5),
+            prev_button : Button::new_with_label("<"),
+            next_button : Button::new_with_label(">"),
+            page_label : Label::new(None),
+            page : Cell::new(0),
             images : Vec::with_capacity(count),
             boxes : Vec::with_capacity(count),
             cards : RefCell::new(Vec::new()),
+            loaded : RefCell::new(Vec::new()),
             img_manager : img_manager,
             current_tcg : tcg,
             card_clicked_events : RefCell::new(Vec::new()),
@@ -331,6 +362,12 @@ impl CardView {
             }
         }
 
+        result.page_box.pack_start(&result.prev_button, false, false, 0);
+        result.page_box.pack_start(&result.page_label, true, true, 0);
+        result.page_box.pack_start(&result.next_button, false, false, 0);
+        result.container.pack_start(&result.grid, false, false, 0);
+        result.container.pack_start(&result.page_box, false, false, 0);
+
         result.img_manager.load_image(&"proxy".to_string());
         let empty_vec : Vec<CardInfo> = Vec::new();
         result.set_cards(&empty_vec);
@@ -344,43 +381,105 @@ impl CardView {
         self.update_cards();
     }
 
+    /// Number of cards that fit on a single page of the grid.
+    fn page_size(&self) -> usize {
+        self.row_count * self.col_count
+    }
+
+    /// Number of pages needed to show every card, which is at least 1.
+    pub fn page_count(&self) -> usize {
+        let count = self.cards.borrow().len();
+        let page_size = self.page_size();
+        if count == 0 || page_size == 0 {
+            1
+        } else {
+            (count + page_size - 1) / page_size
+        }
+    }
+
+    pub fn get_page(&self) -> usize {
+        self.page.get()
+    }
+
+    /// Shows the given page of cards, clamped to the last page.
+    pub fn set_page(&self, page : usize) {
+        let last = self.page_count() - 1;
+        self.page.set(if page > last { last } else { page });
+        self.update_cards();
+    }
+
+    fn on_prev_clicked(&self) {
+        let page = self.page.get();
+        if page > 0 {
+            self.set_page(page - 1);
+        }
+    }
+
+    fn on_next_clicked(&self) {
+        self.set_page(self.page.get() + 1);
+    }
+
+    fn update_page_controls(&self) {
+        let page = self.page.get();
+        let page_count = self.page_count();
+        self.page_label.set_text(&format!("Page {} of {}", page + 1, page_count));
+        self.prev_button.set_sensitive(page > 0);
+        self.next_button.set_sensitive(page + 1 < page_count);
+    }
+
     fn update_cards(&self) {
+        // keep the current page valid if cards were removed
+        let last = self.page_count() - 1;
+        if self.page.get() > last {
+            self.page.set(last);
+        }
+        self.update_page_controls();
+
         // using CardInfos directly removes the need to keep an Rc to the current TCG
         let cards = self.cards.borrow();
         let cutoff = cards.len();
+        let offset = self.page.get() * self.page_size();
 
-        let mut targets : Vec<gtk_ffi::GtkTargetEntry> = Vec::new();
-        
-
+        // only the images of the cards on the current page are loaded
+        let mut loaded = Vec::new();
         for i in 0..self.row_count {
             for j in 0..self.col_count {
                 let index = i * self.col_count + j;
-                if index < cutoff {
-                    // TODO: unload previously loaded images
-                    self.img_manager.load_image(&cards[index].set_code);
-                    if let Some(img) = self.img_manager.get_small_image(&cards[index].set_code) {
+                let card_index = offset + index;
+                if card_index < cutoff {
+                    self.img_manager.load_image(&cards[card_index].set_code);
+                    loaded.push(cards[card_index].set_code.clone());
+                    if let Some(img) = self.img_manager.get_small_image(&cards[card_index].set_code) {
                         self.images[index].set_from_pixbuf(Some(&img));
-                        self.boxes[index].set_tooltip_text(Some(&cards[index].name));
+                        self.boxes[index].set_tooltip_text(Some(&cards[card_index].name));
                         
                         self.boxes[index].drag_source_set_icon_pixbuf(&img);
+                        continue;
                     }
-                } else {
-                    if let Some(img) = self.img_manager.get_small_image(&"proxy".to_string()) {
-                        self.images[index].set_from_pixbuf(Some(&img));
-                        self.boxes[index].set_tooltip_text(None);
-                    }
                 }
+                // empty spaces and cards without an image show the proxy
+                if let Some(img) = self.img_manager.get_small_image(&"proxy".to_string()) {
+                    self.images[index].set_from_pixbuf(Some(&img));
+                }
+                self.boxes[index].set_tooltip_text(None);
             }
         }
 
+        // the previous page is unloaded after the new one is loaded, so cards on both keep
+        // their image
+        let previous = mem::replace(&mut *self.loaded.borrow_mut(), loaded);
+        for set_code in previous {
+            self.img_manager.unload_image(&set_code);
+        }
     }
 
     /// Set the cards displayed by this CardView
     ///
-    /// Updates the grid to the images corresponding to the given cards, and
+    /// Updates the grid to the images corresponding to the first page of the given cards, and
     /// sets the remaining spaces to display a blank image.
     pub fn set_cards(&self, cards : &Vec<CardInfo>) {
         *self.cards.borrow_mut() = cards.clone();
+        self.page.set(0);
         self.update_cards();
     }
 
@@ -398,6 +497,5 @@ impl CardView {
     // TODO: connect mouse events
     // TODO: update images based upon collection of card names
     // TODO: figure out the best way to have the minimal amount of images loaded at once
-    // TODO: create way to handle having more than 20 results
 }
 
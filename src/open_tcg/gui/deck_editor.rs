@@ -23,28 +23,39 @@
 extern crate gtk;
 extern crate gdk;
 
+use std::fs;
 use std::path::PathBuf;
 use std::rc::Rc;
 use std::cell::RefCell;
 
 use gtk::prelude::*;
 use gtk::{Window, WindowPosition, FileChooserDialog, FileChooserAction,
-    Builder, Orientation, Frame, FlowBox, Button, SelectionData, ResponseType};
+    Builder, Orientation, Frame, FlowBox, Button, SelectionData, ResponseType, Label,
+    MessageDialog, DialogFlags, MessageType, ButtonsType, Menu, MenuItem, Entry, ComboBoxText, FileFilter};
 use gtk::Box as GtkBox;
 
-use self::gdk::{Screen, EventButton, DragContext};
+use self::gdk::{Screen, EventButton, EventKey, DragContext};
+use self::gdk::enums::key;
 
 use open_tcg::game::tcg::TCG;
-use open_tcg::game::deck::Deck;
-use open_tcg::game::card::CardInfo;
+use open_tcg::game::deck::{Deck, DECK_EXTENSION};
+use open_tcg::game::history::{DeckEdit, DeckHistory};
+use open_tcg::game::profile::Profile;
+use open_tcg::game::settings::Settings;
+use open_tcg::game::validation::{self, Violation};
 use super::card_display::CardDisplay;
 use super::card_search::CardSearch;
 use super::card_view::{CardView, CardViewType};
 use super::image_manager::ImageManager;
 
+const RIGHT_MOUSE_BUTTON : u32 = 3;
+
 struct DragInfo {
     source_type : CardViewType,
-    source_data : String
+    source_data : String,
+
+    /// Index of the deck section the card is dragged from, if any
+    source_section : Option<usize>
 }
 
 pub struct DeckEditor {
@@ -53,6 +64,11 @@ pub struct DeckEditor {
     editor_box : GtkBox,
     display_box : GtkBox,
     deck_view : Frame,
+    validation_label : Label,
+    name_entry : Entry,
+
+    /// Explains why the last change to the deck was refused
+    status_label : Label,
 
     // custom subcontrols
     card_display : CardDisplay,
@@ -62,10 +78,18 @@ pub struct DeckEditor {
     // fields related to data storage
     current_tcg : Rc<TCG>,
     img_manager : Rc<ImageManager>,
-    current_deck : Deck,
-    deck_filename : String,
+    current_deck : RefCell<Deck>,
+    history : RefCell<DeckHistory>,
+
+    /// File the current deck was opened from or last saved to
+    deck_path : RefCell<Option<PathBuf>>,
+
+    /// Directory the open and save dialogs start in, whose decks are listed in `decks_list`
+    deck_directory : PathBuf,
 
     // controls at the bottom left used for navigation and open/save
+    decks_list : ComboBoxText,
+    open_button : Button,
     save_button : Button,
     save_as_button : Button,
 
@@ -74,25 +98,42 @@ pub struct DeckEditor {
 }
 
 impl DeckEditor {
-    pub fn new(tcg : Rc<TCG>) -> Rc<DeckEditor> {
-        let instance = Rc::new(DeckEditor::init_controls(tcg));
+    pub fn new(tcg : Rc<TCG>, settings : &Settings) -> Rc<DeckEditor> {
+        let instance = Rc::new(DeckEditor::init_controls(tcg, settings));
 
-        // TODO: load deck that was last being edited
         DeckEditor::connect_events(instance.clone());
 
         instance.determine_size();
 
-        // TODO: set title based on current deck
-        instance.window.set_title("DeckEdit");
+        instance.update_title();
+        instance.update_decks_list();
         instance.window.show_all();
+
+        if let Some(path) = DeckEditor::last_edited_deck(instance.current_tcg.get_name()) {
+            instance.open_deck(&path);
+        }
         instance
     }
 
-    fn init_controls(tcg : Rc<TCG>) -> DeckEditor {
+    /// Whether the editor's window is still open.
+    pub fn is_open(&self) -> bool {
+        self.window.get_visible()
+    }
+
+    /// Brings the editor's window to the front.
+    pub fn present(&self) {
+        self.window.present();
+    }
+
+    pub fn close(&self) {
+        self.window.destroy();
+    }
+
+    fn init_controls(tcg : Rc<TCG>, settings : &Settings) -> DeckEditor {
         let glade_src = include_str!("deck_editor.glade");
         let builder = Builder::new_from_string(glade_src);
 
-        let img_manager = Rc::new(ImageManager::new());
+        let img_manager = Rc::new(ImageManager::new(settings));
         let tcg_clone = tcg.clone();
         let mut instance = DeckEditor{
             // layout stuff
@@ -100,6 +141,9 @@ impl DeckEditor {
             editor_box : builder.get_object("editor_box").unwrap(),
             display_box : builder.get_object("display_box").unwrap(),
             deck_view : Frame::new(Some("Deck")),
+            validation_label : Label::new(None),
+            name_entry : Entry::new(),
+            status_label : Label::new(None),
 
             // custom subcontrols
             card_display : CardDisplay::new(tcg.clone(), img_manager.clone()), 
@@ -109,10 +153,14 @@ impl DeckEditor {
             // fields related to data storage
             current_tcg : tcg,
             img_manager : img_manager,
-            current_deck : tcg_clone.new_deck(),
-            deck_filename : String::new(),
+            current_deck : RefCell::new(tcg_clone.new_deck()),
+            history : RefCell::new(DeckHistory::new()),
+            deck_path : RefCell::new(None),
+            deck_directory : settings.deck_directory.clone(),
 
             // controls at the bottom left used for navigation and open/save
+            decks_list : builder.get_object("decks_list").unwrap(),
+            open_button : builder.get_object("open_button").unwrap(),
             save_button : builder.get_object("save_button").unwrap(),
             save_as_button : builder.get_object("save_as_button").unwrap(),
 
@@ -121,7 +169,14 @@ impl DeckEditor {
         
 
         instance.init_deck_views();
+        instance.name_entry.set_placeholder_text("Deck Name");
+        instance.display_box.pack_start(&instance.name_entry, false, false, 0);
         instance.display_box.pack_start(&instance.card_display.frame, true, true, 0);
+        instance.display_box.pack_start(&instance.validation_label, false, false, 0);
+        instance.validation_label.set_line_wrap(true);
+        instance.display_box.pack_start(&instance.status_label, false, false, 0);
+        instance.status_label.set_line_wrap(true);
+        instance.update_validation();
         instance.editor_box.pack_start(&instance.deck_view, true, true, 0);
         instance.editor_box.pack_end(&instance.card_search.frame, false, false, 0);
 
@@ -139,7 +194,7 @@ impl DeckEditor {
             let section_view = CardView::new_with_size(CardViewType::EditorView, self.current_tcg.clone(), self.img_manager.clone(),
                 section.rows as usize, section.columns as usize);
 
-            section_frame.add(&section_view.grid);
+            section_frame.add(&section_view.container);
 
             views_box.pack_start(&section_frame, false, false, 0);
             self.section_views.push(section_view);
@@ -166,90 +221,488 @@ impl DeckEditor {
         }
     }
 
-    fn on_card_view_drag_data_get(&self, view : &CardView, context : &DragContext, data : &SelectionData, info : u32, time : u32) {
+    fn on_card_view_drag_data_get(&self, section : Option<usize>, view : &CardView, context : &DragContext, data : &SelectionData, info : u32, time : u32) {
         if let Some(text) = view.get_dragged_text() {
             *self.drag_info.borrow_mut() = Some(DragInfo{source_type : view.get_view_type(),
-                source_data : text.clone()});
+                source_data : text.clone(), source_section : section});
         }
     }
 
     fn on_deck_view_drag_drop(&self, index : usize, view : &CardView, context : &DragContext, x : i32, y : i32, time : u32) {
         if let Some(ref info) = *self.drag_info.borrow() {
-            if let CardViewType::SearchView = info.source_type {
-                if let Some(card_info) = self.current_tcg.cards.get(&info.source_data) {
-                    self.add_card_to_section(index, view, card_info);
+            match info.source_type {
+                CardViewType::SearchView => self.add_card_to_section(index, &info.source_data),
+                CardViewType::EditorView => {
+                    if let Some(from) = info.source_section {
+                        if from != index {
+                            self.move_card_between_sections(from, index, &info.source_data);
+                        }
+                    }
                 }
             }
         }
     }
 
-    fn add_card_to_section(&self, index : usize, view : &CardView, info : &CardInfo) {
-        let mut section_cards = self.current_deck.sections[index].cards.borrow_mut();
-        if section_cards.contains_key(&info.name) {
-            let entry = section_cards.get_mut(&info.name);
-            if let Some(copies) = entry {
-                if *copies < self.current_tcg.card_limit {
-                    *copies += 1;
-                    view.add_card(&info);
+    /// Moves one copy of a card to another section, unless the target section is
+    /// full or its group already holds as many copies as the TCG allows.
+    fn move_card_between_sections(&self, from : usize, to : usize, name : &String) {
+        let target_name = self.current_deck.borrow().sections[to].info.name.clone();
+
+        let violation = validation::check_move(&self.current_deck.borrow(), &self.current_tcg, from, to, name);
+        if let Some(violation) = violation {
+            let reason = match violation {
+                Violation::SectionTooLarge{max_size, ..} => format!("{} can hold at most {} cards.", target_name, max_size),
+                Violation::TooManyCopies{limit, ..} => format!("{} already has the maximum of {} copies.", target_name, limit),
+                other => format!("{}.", other)
+            };
+            self.status_label.set_text(&format!("Could not move {} to {}: {}", name, target_name, reason));
+            return;
+        }
+
+        let edit = DeckEdit::Move{from : from, to : to, card : name.clone()};
+        if self.perform(edit) {
+            self.status_label.set_text(&format!("Moved {} to {}.", name, target_name));
+        }
+    }
+
+    fn add_card_to_section(&self, index : usize, name : &String) {
+        if !self.current_tcg.cards.contains_key(name) {
+            return;
+        }
+        let copies = {
+            let deck = self.current_deck.borrow();
+            deck.copies_in_group(deck.sections[index].info.group, name)
+        };
+        if copies < self.current_tcg.card_limit {
+            self.perform(DeckEdit::Add{section : index, card : name.clone()});
+        }
+    }
+
+    /// Removes one copy of a card from a section of the deck.
+    fn remove_card_from_section(&self, index : usize, name : &String) {
+        self.perform(DeckEdit::Remove{section : index, card : name.clone()});
+    }
+
+    fn remove_all_copies_from_section(&self, index : usize, name : &String) {
+        let copies = self.current_deck.borrow().sections[index].copies_of(name);
+        let edits = (0..copies).map(|_| DeckEdit::Remove{section : index, card : name.clone()}).collect();
+        self.perform(DeckEdit::Group(edits));
+    }
+
+    /// Removes every card from a section of the deck, as a single edit.
+    fn clear_section(&self, index : usize) {
+        let edits = {
+            let deck = self.current_deck.borrow();
+            let cards = deck.sections[index].cards.borrow();
+            let mut edits = Vec::new();
+            for (name, &copies) in cards.iter() {
+                for _ in 0..copies {
+                    edits.push(DeckEdit::Remove{section : index, card : name.clone()});
+                }
+            }
+            edits
+        };
+        if !edits.is_empty() {
+            self.perform(DeckEdit::Group(edits));
+        }
+    }
+
+    /// Renames the deck to the name entered, if it was changed.
+    fn on_name_entry_changed(&self) {
+        let name = self.name_entry.get_text().unwrap_or(String::new());
+        let old_name = self.current_deck.borrow().name.clone();
+        if name != old_name {
+            self.perform(DeckEdit::Rename{from : old_name, to : name});
+        }
+    }
+
+    /// Applies an edit to the current deck and records it so that it can be undone.
+    fn perform(&self, edit : DeckEdit) -> bool {
+        let applied = self.history.borrow_mut().perform(edit, &mut self.current_deck.borrow_mut());
+        if applied {
+            self.update_deck_views();
+        }
+        applied
+    }
+
+    fn undo(&self) {
+        let undone = self.history.borrow_mut().undo(&mut self.current_deck.borrow_mut());
+        if undone.is_some() {
+            self.status_label.set_text("");
+            self.update_deck_views();
+        }
+    }
+
+    fn redo(&self) {
+        let redone = self.history.borrow_mut().redo(&mut self.current_deck.borrow_mut());
+        if redone.is_some() {
+            self.status_label.set_text("");
+            self.update_deck_views();
+        }
+    }
+
+    /// Shows the cards of the current deck in the section views, sorted by name,
+    /// keeping each view on the page it was showing.
+    fn update_deck_views(&self) {
+        {
+            let deck = self.current_deck.borrow();
+            for (section, view) in deck.sections.iter().zip(self.section_views.iter()) {
+                let cards = section.cards.borrow();
+                let mut names : Vec<&String> = cards.keys().collect();
+                names.sort();
+
+                let mut infos = Vec::new();
+                for name in names {
+                    if let Some(info) = self.current_tcg.cards.get(name) {
+                        for _ in 0..cards[name] {
+                            infos.push(info.clone());
+                        }
+                    }
                 }
+
+                let page = view.get_page();
+                view.set_cards(&infos);
+                view.set_page(page);
+            }
+
+            if self.name_entry.get_text().unwrap_or(String::new()) != deck.name {
+                self.name_entry.set_text(&deck.name);
             }
+        }
+        self.update_validation();
+        self.update_title();
+    }
+
+    /// Ctrl+Z undoes the last edit and Ctrl+Shift+Z redoes it.
+    fn on_key_press(&self, evt : &EventKey) -> Inhibit {
+        let state = evt.get_state();
+        if !state.contains(gdk::CONTROL_MASK) {
+            return Inhibit(false);
+        }
+        let keyval = evt.get_keyval();
+        // with Caps Lock on, Z comes without Shift, so only the Shift modifier picks redo
+        if keyval != key::z && keyval != key::Z {
+            return Inhibit(false);
+        }
+        if state.contains(gdk::SHIFT_MASK) {
+            self.redo();
+        } else {
+            self.undo();
+        }
+        Inhibit(true)
+    }
+
+    /// Displays the rules of the TCG that the current deck breaks, if any.
+    fn update_validation(&self) {
+        let violations = validation::validate_deck(&self.current_deck.borrow(), &self.current_tcg);
+        if violations.is_empty() {
+            self.validation_label.set_text("Deck is legal.");
         } else {
-            section_cards.insert(info.name.clone(), 1);
-            view.add_card(&info);
+            let lines : Vec<String> = violations.iter().map(|v| format!("{}", v)).collect();
+            self.validation_label.set_text(&lines.join("\n"));
+        }
+    }
+
+    /// Asks the user whether to continue if the current deck is illegal.
+    /// Returns true if the deck should be saved.
+    fn confirm_save(&self) -> bool {
+        if validation::validate_deck(&self.current_deck.borrow(), &self.current_tcg).is_empty() {
+            return true;
         }
+
+        let dialog = MessageDialog::new(Some(&self.window), DialogFlags::empty(), MessageType::Warning,
+            ButtonsType::YesNo, "This deck is not legal. Save it anyway?");
+        let response = dialog.run();
+        dialog.destroy();
+        let yes : i32 = ResponseType::Yes.into();
+        response == yes
     }
 
     fn on_card_search_drag_drop(&self, view : &CardView, context : &DragContext, x : i32, y : i32, time : u32) {
         if let Some(ref info) = *self.drag_info.borrow() {
             if let CardViewType::EditorView = info.source_type {
-                if let Some(card_info) = self.current_tcg.cards.get(&info.source_data) {
-                    // TODO: remove card 
-                    println!("Card will be removed");
+                if let Some(index) = info.source_section {
+                    self.remove_card_from_section(index, &info.source_data);
                 }
             }
         }
     }
 
-    fn save_as(&self) {
+    /// The deck of the given TCG that the player edited last, if its file still exists.
+    fn last_edited_deck(tcg_name : &str) -> Option<PathBuf> {
+        let profile = match Profile::read_or_new() {
+            Ok(profile) => profile,
+            Err(_) => return None
+        };
+        profile.decks.into_iter()
+            .find(|d| d.tcg_name == tcg_name && d.path.is_file())
+            .map(|d| d.path)
+    }
+
+    /// Replaces the current deck with the one in the given file,
+    /// telling the user about any problems with it.
+    fn open_deck(&self, path : &PathBuf) -> bool {
+        match Deck::read_from_file(path, &self.current_tcg) {
+            Ok((deck, problems)) => {
+                *self.current_deck.borrow_mut() = deck;
+                self.history.borrow_mut().clear();
+                self.set_deck_path(path);
+                self.status_label.set_text("");
+                for view in self.section_views.iter() {
+                    view.set_page(0);
+                }
+                self.update_deck_views();
+
+                if !problems.is_empty() {
+                    let dialog = MessageDialog::new(Some(&self.window), DialogFlags::empty(), MessageType::Warning,
+                        ButtonsType::Ok, "Some cards of the deck could not be loaded.");
+                    let details : Vec<String> = problems.iter().map(|p| format!("{}", p)).collect();
+                    dialog.set_secondary_text(Some(&details.join("\n")));
+                    dialog.run();
+                    dialog.destroy();
+                }
+                true
+            },
+            Err(e) => {
+                let dialog = MessageDialog::new(Some(&self.window), DialogFlags::empty(), MessageType::Error,
+                    ButtonsType::Ok, "The deck could not be opened.");
+                dialog.set_secondary_text(Some(&format!("{}", e)));
+                dialog.run();
+                dialog.destroy();
+                false
+            }
+        }
+    }
+
+    /// Shows "deck name — TCG name" in the title, with a trailing * if the deck has unsaved changes.
+    fn update_title(&self) {
+        let deck_name = self.current_deck.borrow().name.clone();
+        let deck_name = if deck_name.is_empty() { "Untitled".to_string() } else { deck_name };
+        let modified = if self.history.borrow().is_modified() { "*" } else { "" };
+        self.window.set_title(&format!("{} — {}{}", deck_name, self.current_tcg.get_name(), modified));
+    }
+
+    /// Whether the current deck has changes that haven't been saved.
+    pub fn is_modified(&self) -> bool {
+        self.history.borrow().is_modified()
+    }
+
+    /// Asks the user what to do with unsaved changes before the editor is closed.
+    /// Returns true if the editor may close.
+    pub fn confirm_close(&self) -> bool {
+        self.confirm_discard_changes("Save changes to the deck before closing?")
+    }
+
+    /// Asks the user whether to save unsaved changes before they would be lost.
+    /// Returns true if the current deck may be replaced.
+    fn confirm_discard_changes(&self, question : &str) -> bool {
+        if !self.is_modified() {
+            return true;
+        }
+
+        let dialog = MessageDialog::new(Some(&self.window), DialogFlags::empty(), MessageType::Question,
+            ButtonsType::None, question);
+        dialog.add_buttons(&[
+                           ("Don't Save", ResponseType::No.into()),
+                           ("Cancel", ResponseType::Cancel.into()),
+                           ("Save", ResponseType::Yes.into())
+        ]);
+        let response = dialog.run();
+        dialog.destroy();
+
+        let yes : i32 = ResponseType::Yes.into();
+        let no : i32 = ResponseType::No.into();
+        if response == yes {
+            self.on_save_button_clicked();
+            !self.is_modified()
+        } else {
+            response == no
+        }
+    }
+
+    /// Asks for a file to save the deck to. Returns true if the deck was saved.
+    fn save_as(&self) -> bool {
+        if !self.confirm_save() {
+            return false;
+        }
+
         let file_dialog = FileChooserDialog::new(Some("Choose a File"), Some(&self.window), FileChooserAction::Save);
 
         file_dialog.add_buttons(&[
                                 ("Save", ResponseType::Ok.into()),
                                 ("Cancel", ResponseType::Cancel.into())
         ]);
-
-        file_dialog.run();
-        if let Some(file) = file_dialog.get_filename() {
-            self.set_deck_path(&file);
-            self.save(&file);
+        if fs::create_dir_all(&self.deck_directory).is_ok() {
+            file_dialog.set_current_folder(&self.deck_directory);
         }
+        let name = self.current_deck.borrow().name.clone();
+        if !name.is_empty() {
+            file_dialog.set_current_name(format!("{}.{}", name, DECK_EXTENSION));
+        }
+
+        let response = file_dialog.run();
+        let ok : i32 = ResponseType::Ok.into();
+        let file = if response == ok { file_dialog.get_filename() } else { None };
         file_dialog.destroy();
+
+        match file {
+            Some(file) => {
+                // a deck without a name takes the name of its file, as when it is opened
+                if name.is_empty() {
+                    if let Some(stem) = file.file_stem() {
+                        self.perform(DeckEdit::Rename{from : name, to : stem.to_string_lossy().into_owned()});
+                    }
+                }
+                self.save(&file)
+            },
+            None => false
+        }
     }
 
     fn set_deck_path(&self, path : &PathBuf) {
-        // TODO: set window title and entry in deck drop down
+        *self.deck_path.borrow_mut() = Some(path.clone());
+        self.update_decks_list();
     }
 
-    /// Perform file write operation with the current deck.
-    fn save(&self, path : &PathBuf) {
-        self.current_deck.write_to_file(path);
+    /// Lists the decks in the deck directory, and the current deck if it is elsewhere,
+    /// selecting the current deck.
+    fn update_decks_list(&self) {
+        let current = self.deck_path.borrow().clone();
+        let mut paths = Deck::find_in_directory(&self.deck_directory);
+        if let Some(ref current) = current {
+            if !paths.contains(current) {
+                paths.insert(0, current.clone());
+            }
+        }
+
+        // changing the list fires its changed event, which ignores the current deck
+        self.decks_list.remove_all();
+        for path in paths.iter() {
+            let name = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or(String::new());
+            self.decks_list.append(Some(path.to_string_lossy().as_ref()), &name);
+        }
+        match current {
+            Some(ref current) => { self.decks_list.set_active_id(Some(current.to_string_lossy().as_ref())); },
+            None => self.decks_list.set_active(-1)
+        }
     }
 
-    fn on_save_button_clicked(&self) {
-        // TODO: save the current deck
-        if self.deck_filename.is_empty() {
+    /// Opens the deck chosen from the list, unless the user keeps unsaved changes
+    /// to the current one, in which case the current deck is selected again.
+    fn on_decks_list_changed(&self) {
+        let path = match self.decks_list.get_active_id() {
+            Some(id) => PathBuf::from(id),
+            None => return
+        };
+        if Some(&path) == self.deck_path.borrow().as_ref() {
+            return;
+        }
 
+        if !self.confirm_discard_changes("Save changes to the deck before opening another?") || !self.open_deck(&path) {
+            self.update_decks_list();
+        }
+    }
+
+    fn on_open_button_clicked(&self) {
+        if !self.confirm_discard_changes("Save changes to the deck before opening another?") {
+            return;
+        }
+
+        let file_dialog = FileChooserDialog::new(Some("Open a Deck"), Some(&self.window), FileChooserAction::Open);
+        file_dialog.add_buttons(&[
+                                ("Open", ResponseType::Ok.into()),
+                                ("Cancel", ResponseType::Cancel.into())
+        ]);
+
+        let deck_filter = FileFilter::new();
+        deck_filter.set_name(Some("Deck Files"));
+        deck_filter.add_pattern(&format!("*.{}", DECK_EXTENSION));
+        file_dialog.add_filter(&deck_filter);
+        let all_filter = FileFilter::new();
+        all_filter.set_name(Some("All Files"));
+        all_filter.add_pattern("*");
+        file_dialog.add_filter(&all_filter);
+
+        if self.deck_directory.is_dir() {
+            file_dialog.set_current_folder(&self.deck_directory);
+        }
+
+        let response = file_dialog.run();
+        let ok : i32 = ResponseType::Ok.into();
+        let file = if response == ok { file_dialog.get_filename() } else { None };
+        file_dialog.destroy();
+
+        if let Some(file) = file {
+            self.open_deck(&file);
+        }
+    }
+
+    /// Perform file write operation with the current deck,
+    /// and record in the profile that the deck was edited.
+    /// Returns true if the deck was written.
+    fn save(&self, path : &PathBuf) -> bool {
+        let written = self.current_deck.borrow().write_to_file(path);
+        if let Err(e) = written {
+            let dialog = MessageDialog::new(Some(&self.window), DialogFlags::empty(), MessageType::Error,
+                ButtonsType::Ok, "The deck could not be saved.");
+            dialog.set_secondary_text(Some(&format!("{}", e)));
+            dialog.run();
+            dialog.destroy();
+            return false;
+        }
+        self.set_deck_path(path);
+        self.history.borrow_mut().mark_saved();
+        self.update_title();
+
+        let tcg_name = self.current_tcg.get_name();
+        let result = Profile::read_or_new().and_then(|mut profile| {
+            profile.touch_deck(path, tcg_name);
+            profile.write()
+        });
+        if let Err(e) = result {
+            let dialog = MessageDialog::new(Some(&self.window), DialogFlags::empty(), MessageType::Warning,
+                ButtonsType::Ok, "The deck was saved, but the profile could not be updated.");
+            dialog.set_secondary_text(Some(&format!("{}", e)));
+            dialog.run();
+            dialog.destroy();
+        }
+        true
+    }
+
+    /// Saves the deck to the file it came from, or asks for one if it is new.
+    fn on_save_button_clicked(&self) {
+        let path = self.deck_path.borrow().clone();
+        match path {
+            Some(path) => {
+                if self.confirm_save() {
+                    self.save(&path);
+                }
+            },
+            None => {
+                self.save_as();
+            }
         }
     }
 
     fn on_save_as_button_clicked(&self) {
-        // TODO: save the current deck
         self.save_as();
     }
 
     /// Handle events related to the navigation and open/save controls
     /// at the bottom left.
     fn connect_navigation_events(instance : Rc<DeckEditor>) {
+        {
+            let instance_copy = instance.clone();
+            instance.decks_list.connect_changed(move |_| {
+                instance_copy.on_decks_list_changed();
+            });
+        }
+        {
+            let instance_copy = instance.clone();
+            instance.open_button.connect_clicked(move |_| {
+                instance_copy.on_open_button_clicked();
+            });
+        }
         {
             let instance_copy = instance.clone();
             instance.save_button.connect_clicked(move |_| {
@@ -262,6 +715,35 @@ impl DeckEditor {
                 instance_copy.on_save_as_button_clicked();
             });
         }
+        {
+            let instance_copy = instance.clone();
+            instance.window.connect_delete_event(move |_, _| {
+                Inhibit(!instance_copy.confirm_close())
+            });
+        }
+    }
+
+    /// Handle the keyboard shortcuts and the deck name entry.
+    fn connect_edit_events(instance : Rc<DeckEditor>) {
+        {
+            let instance_copy = instance.clone();
+            instance.window.connect_key_press_event(move |_, evt| {
+                instance_copy.on_key_press(evt)
+            });
+        }
+        {
+            let instance_copy = instance.clone();
+            instance.name_entry.connect_activate(move |_| {
+                instance_copy.on_name_entry_changed();
+            });
+        }
+        {
+            let instance_copy = instance.clone();
+            instance.name_entry.connect_focus_out_event(move |_, _| {
+                instance_copy.on_name_entry_changed();
+                Inhibit(false)
+            });
+        }
     }
 
     /// Handle drag and drop and mouse hover events for the various card views.
@@ -275,7 +757,7 @@ impl DeckEditor {
         {
             let instance_copy = instance.clone();
             instance.card_search.connect_card_drag_data_get(move |view, context, data, info, time| {
-                instance_copy.on_card_view_drag_data_get(view, context, data, info, time);
+                instance_copy.on_card_view_drag_data_get(None, view, context, data, info, time);
             });
         }
         {
@@ -288,7 +770,13 @@ impl DeckEditor {
             {
                 let instance_copy = instance.clone();
                 instance.section_views[i].connect_card_drag_data_get(move |view, context, data, info, time| {
-                    instance_copy.on_card_view_drag_data_get(view, context, data, info, time);
+                    instance_copy.on_card_view_drag_data_get(Some(i), view, context, data, info, time);
+                });
+            }
+            {
+                let instance_copy = instance.clone();
+                instance.section_views[i].connect_card_clicked(move |_, name, evt| {
+                    DeckEditor::on_section_view_clicked(&instance_copy, i, name, evt);
                 });
             }
             {
@@ -303,12 +791,68 @@ impl DeckEditor {
 
     fn connect_events(instance : Rc<DeckEditor>) {
        DeckEditor::connect_navigation_events(instance.clone());
+       DeckEditor::connect_edit_events(instance.clone());
        DeckEditor::connect_mouse_events(instance.clone());
    }
 
-    fn on_section_view_clicked(&self, widget : &CardView, name : &String, evt : &EventButton) {
-        if evt.as_ref().button == 3 {
-            widget.remove_card(name);
+    /// Right-clicking a card in a section removes one copy of it,
+    /// while Shift+right-click opens a menu with more options.
+    fn on_section_view_clicked(instance : &Rc<DeckEditor>, index : usize, name : &String, evt : &EventButton) {
+        if evt.get_button() != RIGHT_MOUSE_BUTTON {
+            return;
+        }
+        if evt.get_state().contains(gdk::SHIFT_MASK) {
+            DeckEditor::show_card_menu(instance, index, name, evt);
+        } else {
+            instance.remove_card_from_section(index, name);
         }
     }
+
+    fn show_card_menu(instance : &Rc<DeckEditor>, index : usize, name : &String, evt : &EventButton) {
+        let menu = Menu::new();
+        {
+            let item = MenuItem::new_with_label("Remove One Copy");
+            let instance_copy = instance.clone();
+            let name = name.clone();
+            item.connect_activate(move |_| {
+                instance_copy.remove_card_from_section(index, &name);
+            });
+            menu.append(&item);
+        }
+        {
+            let item = MenuItem::new_with_label("Remove All Copies");
+            let instance_copy = instance.clone();
+            let name = name.clone();
+            item.connect_activate(move |_| {
+                instance_copy.remove_all_copies_from_section(index, &name);
+            });
+            menu.append(&item);
+        }
+
+        for (to, section) in instance.current_deck.borrow().sections.iter().enumerate() {
+            if to == index {
+                continue;
+            }
+            let item = MenuItem::new_with_label(&format!("Move to {}", section.info.name));
+            let instance_copy = instance.clone();
+            let name = name.clone();
+            item.connect_activate(move |_| {
+                instance_copy.move_card_between_sections(index, to, &name);
+            });
+            menu.append(&item);
+        }
+
+        {
+            let section_name = &instance.current_deck.borrow().sections[index].info.name;
+            let item = MenuItem::new_with_label(&format!("Clear {}", section_name));
+            let instance_copy = instance.clone();
+            item.connect_activate(move |_| {
+                instance_copy.clear_section(index);
+            });
+            menu.append(&item);
+        }
+
+        menu.show_all();
+        menu.popup_easy(evt.get_button(), evt.get_time());
+    }
 }
@@ -0,0 +1,587 @@
+// MIT License
+
+// Copyright (c) 2016 Johan Burke
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+extern crate gtk;
+extern crate gdk;
+extern crate gdk_sys as gdk_ffi;
+extern crate gtk_sys as gtk_ffi;
+extern crate glib;
+
+use std::rc::Rc;
+use std::cell::{Cell, RefCell};
+use std::path::PathBuf;
+
+use gtk::prelude::*;
+use gtk::{Window, WindowPosition, Button, Label, Image, EventBox, Frame, FlowBox, Menu, MenuItem,
+    SelectionMode, Orientation, ScrolledWindow, MessageDialog, DialogFlags, MessageType, ButtonsType};
+use gtk::Box as GtkBox;
+use self::gdk::{Screen, EventButton};
+
+use open_tcg::game::action::{Action, CounterTarget};
+use open_tcg::game::log::GameSession;
+use open_tcg::game::profile::Profile;
+use open_tcg::game::random::RandomError;
+use open_tcg::game::settings::Settings;
+use open_tcg::game::state::{CardId, PlayerId, ZoneId, Orientation as CardOrientation, ZoneRole};
+use open_tcg::game::tcg::TCG;
+use open_tcg::game::view::{CardView, GameView};
+use super::card_display::CardDisplay;
+use super::image_manager::ImageManager;
+
+use self::glib::translate::*;
+
+const RIGHT_MOUSE_BUTTON : u32 = 3;
+
+/// Set code of the image shown for face down cards.
+const CARD_BACK_IMAGE : &'static str = "back";
+
+/// Set code of the image shown for cards without an image of their own.
+const PROXY_IMAGE : &'static str = "proxy";
+
+/// Drag target of cards on the table. The name must stay valid while
+/// GTK uses it, so it is kept in a static.
+static DRAG_TARGET : &'static [u8] = b"STRING\0";
+
+/// Window in which a game is played.
+///
+/// Both players play on the same machine, taking turns at the table
+/// (hot-seat). The table shows the game as the current player sees it, and
+/// the other player's hidden cards are only shown once the table has been
+/// passed to them.
+pub struct GameTable {
+    window : Window,
+    zones_box : GtkBox,
+    status_label : Label,
+    viewer_label : Label,
+    draw_button : Button,
+    roll_button : Button,
+    coin_button : Button,
+    pass_button : Button,
+    concede_button : Button,
+    card_display : CardDisplay,
+
+    current_tcg : Rc<TCG>,
+    img_manager : Rc<ImageManager>,
+    session : RefCell<GameSession>,
+
+    /// Where the game's log is saved when the game ends or the window is closed
+    log_path : PathBuf,
+
+    /// The player currently at the table
+    viewer : Cell<PlayerId>,
+
+    /// Card being dragged to another zone, if any
+    dragged_card : Cell<Option<CardId>>
+}
+
+impl GameTable {
+    pub fn new(tcg : Rc<TCG>, session : GameSession, log_path : PathBuf, settings : &Settings) -> Rc<GameTable> {
+        let instance = Rc::new(GameTable::init_controls(tcg, session, log_path, settings));
+
+        GameTable::connect_events(instance.clone());
+        instance.determine_size();
+
+        instance.window.set_title(&format!("OpenTCG - {}", instance.current_tcg.get_name()));
+        instance.window.show_all();
+        GameTable::refresh(&instance);
+        instance
+    }
+
+    fn init_controls(tcg : Rc<TCG>, session : GameSession, log_path : PathBuf, settings : &Settings) -> GameTable {
+        let img_manager = Rc::new(ImageManager::new(settings));
+        let instance = GameTable{window : Window::new(gtk::WindowType::Toplevel),
+            zones_box : GtkBox::new(Orientation::Vertical, 5),
+            status_label : Label::new(None),
+            viewer_label : Label::new(None),
+            draw_button : Button::new_with_label("Draw"),
+            roll_button : Button::new_with_label("Roll Die"),
+            coin_button : Button::new_with_label("Flip Coin"),
+            pass_button : Button::new_with_label("Pass to Opponent"),
+            concede_button : Button::new_with_label("Concede"),
+            card_display : CardDisplay::new(tcg.clone(), img_manager.clone()),
+            current_tcg : tcg,
+            img_manager : img_manager,
+            session : RefCell::new(session),
+            log_path : log_path,
+            viewer : Cell::new(0),
+            dragged_card : Cell::new(None)};
+
+        let controls_box = GtkBox::new(Orientation::Horizontal, 5);
+        controls_box.pack_start(&instance.viewer_label, false, false, 0);
+        controls_box.pack_start(&instance.draw_button, false, false, 0);
+        controls_box.pack_start(&instance.roll_button, false, false, 0);
+        controls_box.pack_start(&instance.coin_button, false, false, 0);
+        controls_box.pack_start(&instance.status_label, true, true, 0);
+        controls_box.pack_end(&instance.concede_button, false, false, 0);
+        controls_box.pack_end(&instance.pass_button, false, false, 0);
+
+        let scrolled = ScrolledWindow::new(None, None);
+        scrolled.add(&instance.zones_box);
+
+        let table_box = GtkBox::new(Orientation::Horizontal, 10);
+        table_box.pack_start(&scrolled, true, true, 0);
+        table_box.pack_start(&instance.card_display.frame, false, false, 0);
+
+        let main_box = GtkBox::new(Orientation::Vertical, 5);
+        main_box.pack_start(&controls_box, false, false, 0);
+        main_box.pack_start(&table_box, true, true, 0);
+        instance.window.add(&main_box);
+
+        instance.img_manager.load_image(&CARD_BACK_IMAGE.to_string());
+        instance.img_manager.load_image(&PROXY_IMAGE.to_string());
+
+        instance
+    }
+
+    fn connect_events(instance : Rc<GameTable>) {
+        {
+            let instance_copy = instance.clone();
+            instance.draw_button.connect_clicked(move |_| {
+                let player = instance_copy.viewer.get();
+                GameTable::perform(&instance_copy, Action::Draw{player : player});
+            });
+        }
+        {
+            let instance_copy = instance.clone();
+            instance.roll_button.connect_clicked(move |_| {
+                let action = instance_copy.session.borrow().roll_action(instance_copy.viewer.get(), 6);
+                GameTable::perform_random(&instance_copy, action);
+            });
+        }
+        {
+            let instance_copy = instance.clone();
+            instance.coin_button.connect_clicked(move |_| {
+                let action = instance_copy.session.borrow().roll_action(instance_copy.viewer.get(), 2);
+                GameTable::perform_random(&instance_copy, action);
+            });
+        }
+        {
+            let instance_copy = instance.clone();
+            instance.pass_button.connect_clicked(move |_| {
+                GameTable::pass_table(&instance_copy);
+            });
+        }
+        {
+            let instance_copy = instance.clone();
+            instance.concede_button.connect_clicked(move |_| {
+                let winner = instance_copy.opponent();
+                GameTable::perform(&instance_copy, Action::EndGame{winner : Some(winner)});
+            });
+        }
+        {
+            let instance_copy = instance.clone();
+            instance.window.connect_delete_event(move |_, _| {
+                instance_copy.save_log();
+                Inhibit(false)
+            });
+        }
+    }
+
+    fn determine_size(&self) {
+        if let Some(scr) = Screen::get_default() {
+            let width = scr.get_width() as f64;
+            let height = scr.get_height() as f64;
+
+            self.window.set_default_size((width * 0.9) as i32, (height * 0.9) as i32);
+            self.window.set_position(WindowPosition::Center);
+        }
+    }
+
+    fn opponent(&self) -> PlayerId {
+        (self.viewer.get() + 1) % self.session.borrow().state.players.len().max(1)
+    }
+
+    /// Applies an action to the game and redraws the table. The table is
+    /// redrawn once GTK is idle, since the action may come from one of the
+    /// widgets that are replaced. Returns false if the action failed.
+    fn perform(instance : &Rc<GameTable>, action : Action) -> bool {
+        let result = instance.session.borrow_mut().perform(action);
+        if let Err(e) = result {
+            instance.status_label.set_text(&format!("{}", e));
+            return false;
+        }
+
+        if instance.session.borrow().state.finished {
+            instance.save_log();
+        }
+        let instance_copy = instance.clone();
+        gtk::idle_add(move || {
+            GameTable::refresh(&instance_copy);
+            Continue(false)
+        });
+        true
+    }
+
+    /// Performs an action that needed random numbers, if they could be had.
+    fn perform_random(instance : &Rc<GameTable>, action : Result<Action, RandomError>) {
+        match action {
+            Ok(action) => {
+                GameTable::perform(instance, action);
+            },
+            Err(e) => instance.status_label.set_text(&format!("{}", e))
+        }
+    }
+
+    /// Lets the current player look at the top cards of a zone. Since only the
+    /// top card of an ordered zone is shown on the table, the cards looked at
+    /// are listed in a dialog.
+    fn look_at_top(instance : &Rc<GameTable>, zone : ZoneId, count : usize) {
+        let viewer = instance.viewer.get();
+        if !GameTable::perform(instance, Action::LookAtTop{player : viewer, zone : zone, count : count}) {
+            return;
+        }
+
+        let view = instance.session.borrow().state.view(viewer);
+        let zone_view = &view.zones[zone];
+        let mut lines = vec![format!("Top of {}:", zone_view.info.name)];
+        for (index, card) in zone_view.cards.iter().rev().take(count).enumerate() {
+            let name = match *card {
+                CardView::Visible(ref instance_info) => instance_info.card_name.clone(),
+                CardView::Hidden{..} => "(hidden)".to_string()
+            };
+            lines.push(format!("{}. {}", index + 1, name));
+        }
+        if zone_view.cards.is_empty() {
+            lines.push("The zone is empty.".to_string());
+        }
+
+        let dialog = MessageDialog::new(Some(&instance.window), DialogFlags::empty(), MessageType::Info,
+            ButtonsType::Ok, &lines.join("\n"));
+        dialog.run();
+        dialog.destroy();
+    }
+
+    /// Hands the table to the other player. The table is hidden until they
+    /// confirm they are sitting at it, so the current player's hidden cards
+    /// aren't shown to them.
+    fn pass_table(instance : &Rc<GameTable>) {
+        let next = instance.opponent();
+        let name = instance.session.borrow().state.players[next].name.clone();
+
+        instance.zones_box.hide();
+        let dialog = MessageDialog::new(Some(&instance.window), DialogFlags::empty(), MessageType::Info,
+            ButtonsType::Ok, &format!("Pass the table to {}.", name));
+        dialog.run();
+        dialog.destroy();
+
+        instance.viewer.set(next);
+        GameTable::refresh(instance);
+        instance.zones_box.show();
+    }
+
+    /// Saves the game's log and records the game in the profile of the first player,
+    /// who is the player the profile belongs to.
+    fn save_log(&self) {
+        let session = self.session.borrow();
+        if let Err(e) = session.log.write_to_file(&self.log_path) {
+            self.status_label.set_text(&format!("Could not save the game log: {}", e));
+            return;
+        }
+
+        let result = Profile::read_or_new().and_then(|mut profile| {
+            profile.record_match(&session.log, &self.log_path, 0);
+            profile.write()
+        });
+        if let Err(e) = result {
+            self.status_label.set_text(&format!("Could not update the profile: {}", e));
+        }
+    }
+
+    /// Redraws the zones and controls from the game as the current player sees it.
+    /// The other player's zones are shown first, then the shared zones and then
+    /// the current player's.
+    fn refresh(instance : &Rc<GameTable>) {
+        for child in instance.zones_box.get_children() {
+            child.destroy();
+        }
+
+        let viewer = instance.viewer.get();
+        let view = instance.session.borrow().state.view(viewer);
+
+        let mut order : Vec<ZoneId> = (0..view.zones.len()).collect();
+        order.sort_by_key(|&z| match view.zones[z].owner {
+            Some(owner) if owner == viewer => 2,
+            Some(_) => 0,
+            None => 1
+        });
+        for zone in order {
+            let zone_widget = GameTable::create_zone(instance, &view, zone);
+            instance.zones_box.pack_start(&zone_widget, false, false, 0);
+        }
+        instance.zones_box.show_all();
+
+        instance.update_status(&view);
+    }
+
+    fn update_status(&self, view : &GameView) {
+        let player_name = |player : PlayerId| view.players[player].name.clone();
+        self.viewer_label.set_text(&player_name(view.viewer));
+
+        let mut status = Vec::new();
+        for player in view.players.iter() {
+            let mut counters : Vec<String> = player.counters.iter().map(|(name, value)| format!("{} {}", name, value)).collect();
+            counters.sort();
+            if !counters.is_empty() {
+                status.push(format!("{}: {}", player.name, counters.join(", ")));
+            }
+        }
+        if let Some(ref roll) = view.last_roll {
+            if roll.sides == 2 {
+                let side = if roll.value == 1 { "heads" } else { "tails" };
+                status.push(format!("{} flipped {}", player_name(roll.player), side));
+            } else {
+                status.push(format!("{} rolled {} (d{})", player_name(roll.player), roll.value, roll.sides));
+            }
+        }
+        if view.finished {
+            status.push(match view.winner {
+                Some(winner) => format!("{} won the game", player_name(winner)),
+                None => "The game is a draw".to_string()
+            });
+        }
+        self.status_label.set_text(&status.join(" - "));
+
+        let playing = !view.finished;
+        self.draw_button.set_sensitive(playing);
+        self.roll_button.set_sensitive(playing);
+        self.coin_button.set_sensitive(playing);
+        self.pass_button.set_sensitive(playing);
+        self.concede_button.set_sensitive(playing);
+    }
+
+    /// Creates the widget showing a zone, onto which cards can be dragged.
+    fn create_zone(instance : &Rc<GameTable>, view : &GameView, zone : ZoneId) -> EventBox {
+        let zone_view = &view.zones[zone];
+        let label = match zone_view.owner {
+            Some(owner) => format!("{}: {} ({})", view.players[owner].name, zone_view.info.name, zone_view.cards.len()),
+            None => format!("{} ({})", zone_view.info.name, zone_view.cards.len())
+        };
+        let frame = Frame::new(Some(label.as_str()));
+        let flow_box = FlowBox::new();
+        flow_box.set_selection_mode(SelectionMode::None);
+        flow_box.set_min_children_per_line(1);
+
+        // of ordered zones such as decks, only the top card is shown
+        let owned = zone_view.owner.map_or(true, |owner| owner == view.viewer);
+        if zone_view.info.ordered {
+            if let Some(index) = zone_view.cards.len().checked_sub(1) {
+                let card_box = GameTable::create_card(instance, zone, index, &zone_view.cards[index], owned);
+                flow_box.insert(&card_box, -1);
+            }
+        } else {
+            for (index, card) in zone_view.cards.iter().enumerate() {
+                let card_box = GameTable::create_card(instance, zone, index, card, owned);
+                flow_box.insert(&card_box, -1);
+            }
+        }
+        frame.add(&flow_box);
+
+        let zone_box = EventBox::new();
+        zone_box.add(&frame);
+        let mut targets = vec![GameTable::drag_target()];
+        unsafe {
+            gtk_ffi::gtk_drag_dest_set(zone_box.to_glib_none().0, gtk_ffi::GTK_DEST_DEFAULT_ALL, targets.as_mut_ptr(), 1, gdk_ffi::GDK_ACTION_MOVE);
+        }
+        {
+            let instance_copy = instance.clone();
+            zone_box.connect_drag_drop(move |_, _, _, _, _| {
+                if let Some(card) = instance_copy.dragged_card.get() {
+                    instance_copy.dragged_card.set(None);
+                    GameTable::perform(&instance_copy, Action::Move{card : card, to : zone, position : None});
+                }
+                Inhibit(true)
+            });
+        }
+        {
+            let instance_copy = instance.clone();
+            zone_box.connect_button_press_event(move |_, evt| {
+                if evt.get_button() == RIGHT_MOUSE_BUTTON {
+                    GameTable::show_zone_menu(&instance_copy, zone, evt);
+                    return Inhibit(true);
+                }
+                Inhibit(false)
+            });
+        }
+        zone_box
+    }
+
+    fn drag_target() -> gtk_ffi::GtkTargetEntry {
+        gtk_ffi::GtkTargetEntry{target : DRAG_TARGET.as_ptr() as *mut _,
+            flags : (gtk_ffi::GTK_TARGET_SAME_APP).bits(), info : 0}
+    }
+
+    /// Creates the widget showing a card. Only the viewer's own cards and
+    /// cards they can see can be dragged or acted on.
+    fn create_card(instance : &Rc<GameTable>, zone : ZoneId, index : usize, card : &CardView, owned : bool) -> EventBox {
+        let card_id = instance.session.borrow().state.zones[zone].cards[index];
+        let image = Image::new();
+        let caption = Label::new(None);
+
+        let (image_code, hover_name) = match *card {
+            CardView::Visible(ref instance_info) => {
+                let set_code = match instance_info.info(&instance.current_tcg) {
+                    Some(info) => info.set_code.clone(),
+                    None => String::new()
+                };
+                instance.img_manager.load_image(&set_code);
+                let code = if instance.img_manager.get_small_image(&set_code).is_some() {
+                    set_code
+                } else {
+                    caption.set_text(&instance_info.card_name);
+                    PROXY_IMAGE.to_string()
+                };
+                (code, Some(instance_info.card_name.clone()))
+            },
+            CardView::Hidden{..} => (CARD_BACK_IMAGE.to_string(), None)
+        };
+        if let Some(pixbuf) = instance.img_manager.get_small_image(&image_code) {
+            image.set_from_pixbuf(Some(&*pixbuf));
+        } else if hover_name.is_none() {
+            caption.set_text("Face down");
+        }
+
+        let mut details = Vec::new();
+        let (orientation, counters) = match *card {
+            CardView::Visible(ref i) => (i.orientation, &i.counters),
+            CardView::Hidden{orientation, ref counters, ..} => (orientation, counters)
+        };
+        if orientation == CardOrientation::Sideways {
+            details.push("Sideways".to_string());
+        }
+        for (name, value) in counters.iter() {
+            details.push(format!("{}: {}", name, value));
+        }
+        if !details.is_empty() {
+            let text = caption.get_text().unwrap_or(String::new());
+            let separator = if text.is_empty() { "" } else { "\n" };
+            caption.set_text(&format!("{}{}{}", text, separator, details.join("\n")));
+        }
+
+        let card_box = EventBox::new();
+        let content = GtkBox::new(Orientation::Vertical, 2);
+        content.pack_start(&image, false, false, 0);
+        content.pack_start(&caption, false, false, 0);
+        card_box.add(&content);
+
+        let usable = owned || hover_name.is_some();
+        if usable {
+            let mut targets = vec![GameTable::drag_target()];
+            unsafe {
+                gtk_ffi::gtk_drag_source_set(card_box.to_glib_none().0, gdk_ffi::GDK_BUTTON1_MASK, targets.as_mut_ptr(), 1, gdk_ffi::GDK_ACTION_MOVE);
+            }
+            let instance_copy = instance.clone();
+            card_box.connect_drag_begin(move |_, _| {
+                instance_copy.dragged_card.set(Some(card_id));
+            });
+        }
+        if let Some(name) = hover_name {
+            let instance_copy = instance.clone();
+            card_box.connect_motion_notify_event(move |_, _| {
+                instance_copy.card_display.set_card(&name);
+                Inhibit(false)
+            });
+        }
+        {
+            let instance_copy = instance.clone();
+            let visible = card.id().is_some();
+            card_box.connect_button_press_event(move |_, evt| {
+                if usable && evt.get_button() == RIGHT_MOUSE_BUTTON {
+                    GameTable::show_card_menu(&instance_copy, card_id, visible, evt);
+                    return Inhibit(true);
+                }
+                Inhibit(false)
+            });
+        }
+        card_box
+    }
+
+    fn add_menu_item(instance : &Rc<GameTable>, menu : &Menu, label : &str, action : Action) {
+        let item = MenuItem::new_with_label(label);
+        let instance_copy = instance.clone();
+        item.connect_activate(move |_| {
+            GameTable::perform(&instance_copy, action.clone());
+        });
+        menu.append(&item);
+    }
+
+    fn show_card_menu(instance : &Rc<GameTable>, card : CardId, visible : bool, evt : &EventButton) {
+        let (orientation, face_up) = match instance.session.borrow().state.card(card) {
+            Some(c) => (c.orientation, c.face_up),
+            None => return
+        };
+        let viewer = instance.viewer.get();
+        let menu = Menu::new();
+
+        let rotated = match orientation {
+            CardOrientation::Upright => ("Turn Sideways", CardOrientation::Sideways),
+            CardOrientation::Sideways => ("Turn Upright", CardOrientation::Upright)
+        };
+        GameTable::add_menu_item(instance, &menu, rotated.0, Action::Rotate{card : card, orientation : rotated.1});
+        let flip_label = if face_up { "Turn Face Down" } else { "Turn Face Up" };
+        GameTable::add_menu_item(instance, &menu, flip_label, Action::Flip{card : card, face_up : !face_up});
+        if visible {
+            GameTable::add_menu_item(instance, &menu, "Reveal", Action::Reveal{card : card});
+        } else {
+            GameTable::add_menu_item(instance, &menu, "Look At", Action::Peek{player : viewer, card : card});
+        }
+        GameTable::add_menu_item(instance, &menu, "Add Counter",
+            Action::ChangeCounter{target : CounterTarget::Card(card), name : "Counter".to_string(), delta : 1});
+        GameTable::add_menu_item(instance, &menu, "Remove Counter",
+            Action::ChangeCounter{target : CounterTarget::Card(card), name : "Counter".to_string(), delta : -1});
+
+        menu.show_all();
+        menu.popup_easy(evt.get_button(), evt.get_time());
+    }
+
+    fn show_zone_menu(instance : &Rc<GameTable>, zone : ZoneId, evt : &EventButton) {
+        let viewer = instance.viewer.get();
+        let is_draw_pile = {
+            let session = instance.session.borrow();
+            let zone = &session.state.zones[zone];
+            zone.info.role == Some(ZoneRole::DrawPile) && zone.owner.map_or(true, |owner| owner == viewer)
+        };
+        let menu = Menu::new();
+        {
+            // each shuffle needs a new contribution, so the action is created when it's chosen
+            let item = MenuItem::new_with_label("Shuffle");
+            let instance_copy = instance.clone();
+            item.connect_activate(move |_| {
+                let action = instance_copy.session.borrow().shuffle_action(zone, viewer);
+                GameTable::perform_random(&instance_copy, action);
+            });
+            menu.append(&item);
+        }
+        for &(label, count) in [("Look at Top Card", 1), ("Look at Top 3 Cards", 3)].iter() {
+            let item = MenuItem::new_with_label(label);
+            let instance_copy = instance.clone();
+            item.connect_activate(move |_| {
+                GameTable::look_at_top(&instance_copy, zone, count);
+            });
+            menu.append(&item);
+        }
+        if is_draw_pile {
+            GameTable::add_menu_item(instance, &menu, "Draw", Action::Draw{player : viewer});
+        }
+
+        menu.show_all();
+        menu.popup_easy(evt.get_button(), evt.get_time());
+    }
+}
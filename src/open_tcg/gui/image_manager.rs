@@ -25,47 +25,69 @@ extern crate gdk;
 extern crate gdk_pixbuf;
 
 use std::rc::Rc;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::path::PathBuf;
 
 use self::gdk_pixbuf::Pixbuf;
 use std::collections::HashMap;
 
+use open_tcg::game::settings::Settings;
+
 struct ImageSizes {
     pub small : Rc<Pixbuf>,
-    pub large : Rc<Pixbuf>
+    pub large : Rc<Pixbuf>,
+    /// Number of load_image calls not yet matched by an unload_image
+    users : Cell<usize>
 }
 
-const DEFAULT_SMALL_SCALE : i32 = 100;
-const DEFAULT_LARGE_SCALE : i32 = 250;
-
 pub struct ImageManager {
     images : RefCell<HashMap<String, Rc<ImageSizes>>>,
+    image_directory : PathBuf,
     pub small_scale : i32,
     pub large_scale : i32
 }
 
 impl ImageManager {
-    pub fn new() -> ImageManager {
+    pub fn new(settings : &Settings) -> ImageManager {
         ImageManager{images : RefCell::new(HashMap::new()),
-            small_scale : DEFAULT_SMALL_SCALE,
-            large_scale : DEFAULT_LARGE_SCALE}
+            image_directory : settings.image_directory.clone(),
+            small_scale : settings.small_image_scale,
+            large_scale : settings.large_image_scale}
     }
 
+    /// Releases an image loaded with load_image
+    ///
+    /// Several views share the manager, so the image is only freed once every load_image call
+    /// for it has been matched by a call to this function.
     pub fn unload_image(&self, set_code : &String) {
-        self.images.borrow_mut().remove(set_code);
+        let mut images = self.images.borrow_mut();
+        let unused = match images.get(set_code) {
+            Some(img) => {
+                img.users.set(img.users.get() - 1);
+                img.users.get() == 0
+            },
+            None => false
+        };
+        if unused {
+            images.remove(set_code);
+        }
     }
 
     pub fn load_image(&self, set_code : &String) {
         let mut images = self.images.borrow_mut();
-        if !images.contains_key(set_code) {
-            let filename = "images/".to_string() + set_code + ".png";
-            if let Ok(small) = Pixbuf::new_from_file_at_size(&filename,
-                self.small_scale, self.small_scale) {
-                if let Ok(large) = Pixbuf::new_from_file_at_size(&filename,
-                    self.large_scale, self.large_scale) {
-                    let result = ImageSizes{small : Rc::new(small), large : Rc::new(large)};
-                    images.insert(set_code.clone(), Rc::new(result));
-                }
+        if let Some(img) = images.get(set_code) {
+            img.users.set(img.users.get() + 1);
+            return;
+        }
+        let path = self.image_directory.join(set_code.clone() + ".png");
+        let filename = path.to_string_lossy();
+        if let Ok(small) = Pixbuf::new_from_file_at_size(&filename,
+            self.small_scale, self.small_scale) {
+            if let Ok(large) = Pixbuf::new_from_file_at_size(&filename,
+                self.large_scale, self.large_scale) {
+                let result = ImageSizes{small : Rc::new(small), large : Rc::new(large),
+                    users : Cell::new(1)};
+                images.insert(set_code.clone(), Rc::new(result));
             }
         }
     }
@@ -24,38 +24,101 @@ extern crate gtk;
 extern crate gdk;
 
 use std::rc::Rc;
+use std::cell::RefCell;
 use std::path::PathBuf;
 
 use gtk::prelude::*;
-use gtk::{Builder, Window, Button};
+use gtk::{Builder, Window, Button, MessageDialog, DialogFlags, MessageType, ButtonsType,
+    FileChooserDialog, FileChooserAction, ResponseType};
 
 use open_tcg::gui::deck_editor::DeckEditor;
+use open_tcg::gui::game_table::GameTable;
+use open_tcg::gui::profile_window::ProfileWindow;
+use open_tcg::gui::tcg_picker::TcgPicker;
+use open_tcg::game::deck::Deck;
+use open_tcg::game::log::{GameLog, GameSession};
+use open_tcg::game::profile::{self, Profile};
+use open_tcg::game::settings::Settings;
 use open_tcg::game::tcg::TCG;
+use open_tcg::util::error::OpenTcgError;
 
 pub struct MainWindow {
     window : Window,
     play_button : Button,
     deck_edit_button : Button,
     view_profile_button : Button,
-    current_tcg : Rc<TCG>
+    choose_tcg_button : Button,
+    settings : RefCell<Settings>,
+    current_tcg : RefCell<Rc<TCG>>,
+
+    /// The deck editor opened last, which is reused while it is open
+    deck_editor : RefCell<Option<Rc<DeckEditor>>>
 }
 
 impl MainWindow {
     pub fn new() -> Rc<MainWindow> {
-        // TODO: read settings from file for default TCGs directory
-        let path = PathBuf::from("example.xml");
-        let tcg = Rc::new(TCG::new_from_file(&path));
-        let instance = Rc::new(MainWindow::init_controls(tcg));
+        let mut errors = Vec::new();
+        let settings = match Settings::read_or_new() {
+            Ok(settings) => settings,
+            Err(e) => {
+                errors.push(e);
+                Settings::new()
+            }
+        };
+        let path = settings.find_default_tcg();
+        let tcg = match path {
+            Some(ref path) => match TCG::new_from_file(path) {
+                Ok(mut tcg) => {
+                    errors.append(&mut tcg.load_errors);
+                    tcg
+                },
+                Err(e) => {
+                    errors.push(e);
+                    TCG::new()
+                }
+            },
+            None => TCG::new()
+        };
+        let instance = Rc::new(MainWindow::init_controls(settings, Rc::new(tcg)));
         
         instance.determine_size();
         MainWindow::connect_events(instance.clone());
         
-        instance.window.set_title("OpenTCG");
+        instance.update_title();
         instance.window.show_all();
+
+        if path.is_none() {
+            instance.show_no_tcgs();
+        }
+        if !errors.is_empty() {
+            instance.show_load_errors(&errors);
+        }
         instance
     }
 
-    fn init_controls(tcg : Rc<TCG>) -> MainWindow {
+    /// Tells the user that no TCG is installed in any of the directories searched.
+    fn show_no_tcgs(&self) {
+        let dialog = MessageDialog::new(Some(&self.window), DialogFlags::empty(), MessageType::Warning,
+            ButtonsType::Ok, "No TCG definitions were found.");
+        let directories : Vec<String> = self.settings.borrow().tcg_directories.iter()
+            .map(|d| d.display().to_string()).collect();
+        dialog.set_secondary_text(Some(&format!("The following directories were searched:\n{}\n\n\
+            They can be changed in {}.", directories.join("\n"), Settings::default_path().display())));
+        dialog.run();
+        dialog.destroy();
+    }
+
+    /// Shows a dialog listing the files that failed to load or were loaded with problems.
+    fn show_load_errors(&self, errors : &Vec<OpenTcgError>) {
+        let dialog = MessageDialog::new(Some(&self.window), DialogFlags::empty(), MessageType::Warning,
+            ButtonsType::Ok, "Problems were found while loading the game files.");
+        let details : Vec<String> = errors.iter().map(|e| format!("{}", e)).collect();
+        dialog.set_secondary_text(Some(&details.join("\n")));
+        dialog.run();
+        dialog.destroy();
+    }
+
+    fn init_controls(settings : Settings, tcg : Rc<TCG>) -> MainWindow {
         let glade_src = include_str!("main_window.glade");
         let builder = Builder::new_from_string(glade_src);
 
@@ -63,7 +126,10 @@ impl MainWindow {
             play_button : builder.get_object("play_button").unwrap(),
             deck_edit_button : builder.get_object("deck_edit_button").unwrap(),
             view_profile_button : builder.get_object("view_profile_button").unwrap(),
-            current_tcg : tcg};
+            choose_tcg_button : builder.get_object("choose_tcg_button").unwrap(),
+            settings : RefCell::new(settings),
+            current_tcg : RefCell::new(tcg),
+            deck_editor : RefCell::new(None)};
 
         instance
     }
@@ -88,20 +154,162 @@ impl MainWindow {
                 instance_copy.on_view_profile_clicked();
             });
         }
+        {
+            let instance_copy = instance.clone();
+            instance.choose_tcg_button.connect_clicked(move |_| {
+                MainWindow::on_choose_tcg_clicked(&instance_copy);
+            });
+        }
     }
 
     fn on_view_profile_clicked(&self) {
+        ProfileWindow::new();
+    }
+
+    fn get_tcg(&self) -> Rc<TCG> {
+        self.current_tcg.borrow().clone()
+    }
+
+    fn update_title(&self) {
+        let tcg = self.get_tcg();
+        if tcg.get_name().is_empty() {
+            self.window.set_title("OpenTCG");
+        } else {
+            self.window.set_title(&format!("OpenTCG - {}", tcg.get_name()));
+        }
+    }
+
+    fn on_choose_tcg_clicked(instance : &Rc<MainWindow>) {
+        let picker = TcgPicker::new(&instance.settings.borrow(), instance.get_tcg().get_name());
+        let instance_copy = instance.clone();
+        picker.connect_tcg_selected(move |_, tcg| {
+            instance_copy.switch_tcg(tcg);
+        });
+    }
+
+    /// Makes the given TCG the active one and remembers it as the default.
+    /// An open deck editor is reopened with the new TCG.
+    fn switch_tcg(&self, tcg : Rc<TCG>) {
+        let open_editor = match *self.deck_editor.borrow() {
+            Some(ref editor) if editor.is_open() => Some(editor.clone()),
+            _ => None
+        };
+        if let Some(ref editor) = open_editor {
+            if !editor.confirm_close() {
+                return;
+            }
+            editor.close();
+        }
+
+        *self.current_tcg.borrow_mut() = tcg.clone();
+        self.update_title();
+
+        let result = {
+            let mut settings = self.settings.borrow_mut();
+            settings.default_tcg = Some(tcg.get_name().clone());
+            settings.write()
+        };
+        if let Err(e) = result {
+            let dialog = MessageDialog::new(Some(&self.window), DialogFlags::empty(), MessageType::Warning,
+                ButtonsType::Ok, "The game was changed, but the settings could not be saved.");
+            dialog.set_secondary_text(Some(&format!("{}", e)));
+            dialog.run();
+            dialog.destroy();
+        }
 
+        if open_editor.is_some() {
+            self.on_constructor_clicked();
+        }
     }
 
     fn on_play_clicked(&self) {
         // TODO: display interface to enter rated pool/challenge a friend/spectate
+        // for now both players play on this machine
+        let first_path = match self.choose_deck("Choose the Deck of Player 1") {
+            Some(path) => path,
+            None => return
+        };
+        let second_path = match self.choose_deck("Choose the Deck of Player 2") {
+            Some(path) => path,
+            None => return
+        };
+        let (first, second) = match (self.load_deck(&first_path), self.load_deck(&second_path)) {
+            (Some(first), Some(second)) => (first, second),
+            _ => return
+        };
+
+        // the player whose profile this is plays first
+        let first_name = Profile::read_or_new().map(|p| p.name).unwrap_or("Player 1".to_string());
+        let started = profile::now();
+        let tcg = self.get_tcg();
+        let session = match GameSession::start(&tcg, started, [(&first_name, &first), ("Player 2", &second)]) {
+            Ok(session) => session,
+            Err(e) => {
+                let dialog = MessageDialog::new(Some(&self.window), DialogFlags::empty(), MessageType::Error,
+                    ButtonsType::Ok, &format!("The game could not be started: {}", e));
+                dialog.run();
+                dialog.destroy();
+                return;
+            }
+        };
+        let deck_directory = first_path.parent().map(|p| p.to_path_buf()).unwrap_or(PathBuf::new());
+        let log_path = GameLog::default_path(&deck_directory, started);
+
+        GameTable::new(tcg.clone(), session, log_path, &self.settings.borrow());
+    }
+
+    /// Asks the user for a deck file, returning None if they cancel.
+    fn choose_deck(&self, title : &str) -> Option<PathBuf> {
+        let file_dialog = FileChooserDialog::new(Some(title), Some(&self.window), FileChooserAction::Open);
+        file_dialog.add_buttons(&[
+                                ("Open", ResponseType::Ok.into()),
+                                ("Cancel", ResponseType::Cancel.into())
+        ]);
+        let deck_directory = self.settings.borrow().deck_directory.clone();
+        if deck_directory.is_dir() {
+            file_dialog.set_current_folder(&deck_directory);
+        }
+
+        let response = file_dialog.run();
+        let ok : i32 = ResponseType::Ok.into();
+        let result = if response == ok { file_dialog.get_filename() } else { None };
+        file_dialog.destroy();
+        result
+    }
+
+    /// Loads a deck, telling the user about any problems with it.
+    fn load_deck(&self, path : &PathBuf) -> Option<Deck> {
+        match Deck::read_from_file(path, &self.get_tcg()) {
+            Ok((deck, problems)) => {
+                if !problems.is_empty() {
+                    let dialog = MessageDialog::new(Some(&self.window), DialogFlags::empty(), MessageType::Warning,
+                        ButtonsType::Ok, &format!("Some cards of {} could not be loaded.", deck.name));
+                    let details : Vec<String> = problems.iter().map(|p| format!("{}", p)).collect();
+                    dialog.set_secondary_text(Some(&details.join("\n")));
+                    dialog.run();
+                    dialog.destroy();
+                }
+                Some(deck)
+            },
+            Err(e) => {
+                let dialog = MessageDialog::new(Some(&self.window), DialogFlags::empty(), MessageType::Error,
+                    ButtonsType::Ok, &format!("{}", e));
+                dialog.run();
+                dialog.destroy();
+                None
+            }
+        }
     }
 
     fn on_constructor_clicked(&self) {
-        // TODO: display deck editor
-        // for now construct a new instance each time (should probably change later)
-        let editor = DeckEditor::new(self.current_tcg.clone());
+        let mut deck_editor = self.deck_editor.borrow_mut();
+        if let Some(ref editor) = *deck_editor {
+            if editor.is_open() {
+                editor.present();
+                return;
+            }
+        }
+        *deck_editor = Some(DeckEditor::new(self.get_tcg(), &self.settings.borrow()));
     }
 
     /// Determines the size and location of the MainWindow based on
@@ -119,11 +327,24 @@ impl MainWindow {
         }
     }
 
-    /// Exit the application when the user closes the window
-    pub fn exit_on_close(&self) {
-        self.window.connect_delete_event(|_, _| {
-            gtk::main_quit();
+    /// Exit the application when the user closes the window,
+    /// unless they choose to keep editing an unsaved deck
+    pub fn exit_on_close(instance : &Rc<MainWindow>) {
+        let instance_copy = instance.clone();
+        instance.window.connect_delete_event(move |_, _| {
+            if instance_copy.confirm_exit() {
+                gtk::main_quit();
+            }
             Inhibit(true)
         });
     }
+
+    /// Asks an open deck editor to confirm closing before the application exits.
+    /// Returns true if the application may exit.
+    fn confirm_exit(&self) -> bool {
+        match *self.deck_editor.borrow() {
+            Some(ref editor) if editor.is_open() => editor.confirm_close(),
+            _ => true
+        }
+    }
 }
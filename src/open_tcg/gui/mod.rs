@@ -25,3 +25,6 @@ pub mod card_display;
 pub mod card_search;
 pub mod card_view;
 pub mod image_manager;
+pub mod game_table;
+pub mod profile_window;
+pub mod tcg_picker;
@@ -0,0 +1,222 @@
+// MIT License
+
+// Copyright (c) 2016 Johan Burke
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+extern crate gtk;
+extern crate gdk;
+extern crate gdk_pixbuf;
+
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use gtk::prelude::*;
+use gtk::{Window, WindowPosition, Button, Label, Image, Entry, Frame, Grid, Align, Orientation,
+    ScrolledWindow, FileChooserDialog, FileChooserAction, ResponseType, MessageDialog, DialogFlags,
+    MessageType, ButtonsType};
+use gtk::Box as GtkBox;
+use self::gdk_pixbuf::Pixbuf;
+
+use open_tcg::game::profile::{self, Profile};
+
+const AVATAR_SIZE : i32 = 96;
+
+/// Window showing the player's profile: their name and avatar, the decks
+/// they edited, the games they played and how well they did in each TCG.
+pub struct ProfileWindow {
+    window : Window,
+    avatar_image : Image,
+    name_entry : Entry,
+    avatar_button : Button,
+    save_button : Button,
+    decks_grid : Grid,
+    matches_grid : Grid,
+    statistics_grid : Grid,
+    profile : RefCell<Profile>
+}
+
+impl ProfileWindow {
+    pub fn new() -> Rc<ProfileWindow> {
+        let (profile, error) = match Profile::read_or_new() {
+            Ok(profile) => (profile, None),
+            Err(e) => (Profile::new(), Some(e))
+        };
+        let instance = Rc::new(ProfileWindow::init_controls(profile));
+
+        ProfileWindow::connect_events(instance.clone());
+        instance.update();
+
+        instance.window.set_title("Profile");
+        instance.window.set_default_size(600, 500);
+        instance.window.set_position(WindowPosition::Center);
+        instance.window.show_all();
+
+        if let Some(e) = error {
+            instance.show_error("The profile could not be loaded.", &format!("{}", e));
+        }
+        instance
+    }
+
+    fn init_controls(profile : Profile) -> ProfileWindow {
+        let instance = ProfileWindow{window : Window::new(gtk::WindowType::Toplevel),
+            avatar_image : Image::new(),
+            name_entry : Entry::new(),
+            avatar_button : Button::new_with_label("Change Avatar..."),
+            save_button : Button::new_with_label("Save"),
+            decks_grid : Grid::new(),
+            matches_grid : Grid::new(),
+            statistics_grid : Grid::new(),
+            profile : RefCell::new(profile)};
+
+        let identity_box = GtkBox::new(Orientation::Vertical, 5);
+        identity_box.pack_start(&instance.name_entry, false, false, 0);
+        identity_box.pack_start(&instance.avatar_button, false, false, 0);
+        identity_box.pack_start(&instance.save_button, false, false, 0);
+
+        let header_box = GtkBox::new(Orientation::Horizontal, 10);
+        header_box.pack_start(&instance.avatar_image, false, false, 0);
+        header_box.pack_start(&identity_box, false, false, 0);
+
+        let main_box = GtkBox::new(Orientation::Vertical, 10);
+        main_box.pack_start(&header_box, false, false, 0);
+        for &(title, grid) in [("Statistics", &instance.statistics_grid), ("Decks", &instance.decks_grid),
+                               ("Match History", &instance.matches_grid)].iter() {
+            grid.set_column_spacing(15);
+            let frame = Frame::new(Some(title));
+            frame.add(grid);
+            main_box.pack_start(&frame, false, false, 0);
+        }
+
+        let scrolled = ScrolledWindow::new(None, None);
+        scrolled.add(&main_box);
+        instance.window.add(&scrolled);
+
+        instance
+    }
+
+    fn connect_events(instance : Rc<ProfileWindow>) {
+        {
+            let instance_copy = instance.clone();
+            instance.avatar_button.connect_clicked(move |_| {
+                instance_copy.on_avatar_button_clicked();
+            });
+        }
+        {
+            let instance_copy = instance.clone();
+            instance.save_button.connect_clicked(move |_| {
+                instance_copy.on_save_button_clicked();
+            });
+        }
+    }
+
+    fn show_error(&self, message : &str, details : &str) {
+        let dialog = MessageDialog::new(Some(&self.window), DialogFlags::empty(), MessageType::Warning,
+            ButtonsType::Ok, message);
+        dialog.set_secondary_text(Some(details));
+        dialog.run();
+        dialog.destroy();
+    }
+
+    fn on_avatar_button_clicked(&self) {
+        let file_dialog = FileChooserDialog::new(Some("Choose an Image"), Some(&self.window), FileChooserAction::Open);
+        file_dialog.add_buttons(&[
+                                ("Open", ResponseType::Ok.into()),
+                                ("Cancel", ResponseType::Cancel.into())
+        ]);
+
+        let response = file_dialog.run();
+        let ok : i32 = ResponseType::Ok.into();
+        if response == ok {
+            if let Some(file) = file_dialog.get_filename() {
+                self.profile.borrow_mut().avatar = Some(file);
+                self.update_avatar();
+            }
+        }
+        file_dialog.destroy();
+    }
+
+    fn on_save_button_clicked(&self) {
+        if let Some(name) = self.name_entry.get_text() {
+            self.profile.borrow_mut().name = name;
+        }
+        if let Err(e) = self.profile.borrow().write() {
+            self.show_error("The profile could not be saved.", &format!("{}", e));
+        }
+    }
+
+    fn update_avatar(&self) {
+        let profile = self.profile.borrow();
+        let pixbuf = profile.avatar.as_ref()
+            .and_then(|path| Pixbuf::new_from_file_at_size(&path.to_string_lossy(), AVATAR_SIZE, AVATAR_SIZE).ok());
+        match pixbuf {
+            Some(pixbuf) => self.avatar_image.set_from_pixbuf(Some(&pixbuf)),
+            None => self.avatar_image.clear()
+        }
+    }
+
+    /// Fills a grid with a header row followed by the given rows.
+    fn fill_grid(grid : &Grid, headers : &[&str], rows : Vec<Vec<String>>) {
+        for child in grid.get_children() {
+            child.destroy();
+        }
+        for (column, header) in headers.iter().enumerate() {
+            let label = Label::new(None);
+            label.set_markup(&format!("<b>{}</b>", header));
+            label.set_halign(Align::Start);
+            grid.attach(&label, column as i32, 0, 1, 1);
+        }
+        for (row, values) in rows.iter().enumerate() {
+            for (column, value) in values.iter().enumerate() {
+                let label = Label::new(Some(value.as_str()));
+                label.set_halign(Align::Start);
+                grid.attach(&label, column as i32, row as i32 + 1, 1, 1);
+            }
+        }
+        grid.show_all();
+    }
+
+    /// Shows the contents of the profile.
+    fn update(&self) {
+        self.update_avatar();
+        let profile = self.profile.borrow();
+        self.name_entry.set_text(&profile.name);
+
+        let statistics = profile.statistics().into_iter().map(|(tcg_name, stats)| {
+            let win_rate = match stats.win_rate() {
+                Some(rate) => format!("{:.0}%", rate * 100.0),
+                None => "-".to_string()
+            };
+            vec![tcg_name, stats.games.to_string(), stats.wins.to_string(), stats.losses.to_string(),
+                stats.draws.to_string(), win_rate]
+        }).collect();
+        ProfileWindow::fill_grid(&self.statistics_grid, &["TCG", "Games", "Wins", "Losses", "Draws", "Win Rate"], statistics);
+
+        let decks = profile.decks.iter().map(|deck| {
+            vec![deck.name(), deck.tcg_name.clone(), profile::format_time(deck.last_edited)]
+        }).collect();
+        ProfileWindow::fill_grid(&self.decks_grid, &["Deck", "TCG", "Last Edited"], decks);
+
+        // most recent games first
+        let matches = profile.matches.iter().rev().map(|record| {
+            vec![profile::format_time(record.started), record.tcg_name.clone(), record.deck_name.clone(),
+                record.opponent.clone(), record.result.name().to_string()]
+        }).collect();
+        ProfileWindow::fill_grid(&self.matches_grid, &["Date", "TCG", "Deck", "Opponent", "Result"], matches);
+    }
+}
@@ -0,0 +1,200 @@
+// MIT License
+
+// Copyright (c) 2016 Johan Burke
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+extern crate gtk;
+
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::path::PathBuf;
+
+use gtk::prelude::*;
+use gtk::{Window, WindowPosition, Button, Label, ListBox, ListBoxRow, Orientation, ScrolledWindow,
+    SelectionMode};
+use gtk::Box as GtkBox;
+
+use open_tcg::game::settings::Settings;
+use open_tcg::game::tcg::TCG;
+use open_tcg::util::error::OpenTcgError;
+
+/// A TCG definition found in one of the search directories.
+enum TcgEntry {
+    Loaded{path : PathBuf, tcg : Rc<TCG>},
+    Failed{path : PathBuf, error : OpenTcgError}
+}
+
+/// Window listing every installed TCG, from which the active one is chosen.
+pub struct TcgPicker {
+    window : Window,
+    tcg_list : ListBox,
+    select_button : Button,
+    cancel_button : Button,
+    entries : Vec<TcgEntry>,
+
+    tcg_selected_events : RefCell<Vec<Box<Fn(&PathBuf, Rc<TCG>)>>>
+}
+
+impl TcgPicker {
+    /// Loads every TCG found in the search directories and lists them.
+    /// `current` is the name of the active TCG, which is selected initially.
+    pub fn new(settings : &Settings, current : &str) -> Rc<TcgPicker> {
+        let entries = settings.discover_tcgs().into_iter().map(|path| {
+            match TCG::new_from_file(&path) {
+                Ok(tcg) => TcgEntry::Loaded{path : path, tcg : Rc::new(tcg)},
+                Err(e) => TcgEntry::Failed{path : path, error : e}
+            }
+        }).collect();
+        let instance = Rc::new(TcgPicker::init_controls(entries, current));
+
+        TcgPicker::connect_events(instance.clone());
+
+        instance.window.set_title("Choose a Game");
+        instance.window.set_default_size(500, 400);
+        instance.window.set_position(WindowPosition::Center);
+        instance.window.show_all();
+        instance
+    }
+
+    fn init_controls(entries : Vec<TcgEntry>, current : &str) -> TcgPicker {
+        let instance = TcgPicker{window : Window::new(gtk::WindowType::Toplevel),
+            tcg_list : ListBox::new(),
+            select_button : Button::new_with_label("Select"),
+            cancel_button : Button::new_with_label("Cancel"),
+            entries : entries,
+            tcg_selected_events : RefCell::new(Vec::new())};
+
+        instance.tcg_list.set_selection_mode(SelectionMode::Single);
+        instance.tcg_list.set_placeholder(Some(&Label::new(Some("No TCG definitions were found."))));
+        for entry in instance.entries.iter() {
+            let row = ListBoxRow::new();
+            row.add(&TcgPicker::describe(entry));
+            instance.tcg_list.add(&row);
+
+            match *entry {
+                TcgEntry::Loaded{ref tcg, ..} => {
+                    if tcg.get_name() == current {
+                        instance.tcg_list.select_row(Some(&row));
+                    }
+                },
+                TcgEntry::Failed{..} => {
+                    row.set_selectable(false);
+                    row.set_activatable(false);
+                }
+            }
+        }
+
+        let scrolled = ScrolledWindow::new(None, None);
+        scrolled.add(&instance.tcg_list);
+
+        let button_box = GtkBox::new(Orientation::Horizontal, 5);
+        button_box.pack_end(&instance.select_button, false, false, 0);
+        button_box.pack_end(&instance.cancel_button, false, false, 0);
+
+        let main_box = GtkBox::new(Orientation::Vertical, 10);
+        main_box.pack_start(&scrolled, true, true, 0);
+        main_box.pack_start(&button_box, false, false, 0);
+        instance.window.add(&main_box);
+
+        instance
+    }
+
+    /// Creates the contents of the row for a TCG: its name, card count and sets.
+    fn describe(entry : &TcgEntry) -> GtkBox {
+        let row_box = GtkBox::new(Orientation::Vertical, 2);
+        let mut lines = Vec::new();
+
+        match *entry {
+            TcgEntry::Loaded{ref path, ref tcg} => {
+                let name = Label::new(None);
+                name.set_markup(&format!("<b>{}</b>", escape_markup(tcg.get_name())));
+                lines.push(name);
+
+                let mut count = format!("{} cards", tcg.cards.len());
+                if !tcg.load_errors.is_empty() {
+                    count += &format!(" ({} files could not be loaded)", tcg.load_errors.len());
+                }
+                lines.push(Label::new(Some(count.as_str())));
+
+                let sets = tcg.set_names();
+                let sets = if sets.is_empty() { "No sets".to_string() } else { format!("Sets: {}", sets.join(", ")) };
+                lines.push(Label::new(Some(sets.as_str())));
+                lines.push(Label::new(Some(path.to_string_lossy().as_ref())));
+            },
+            TcgEntry::Failed{ref path, ref error} => {
+                lines.push(Label::new(Some(path.to_string_lossy().as_ref())));
+                lines.push(Label::new(Some(format!("{}", error).as_str())));
+            }
+        }
+
+        for line in lines {
+            line.set_xalign(0.0);
+            line.set_line_wrap(true);
+            row_box.pack_start(&line, false, false, 0);
+        }
+        row_box
+    }
+
+    fn connect_events(instance : Rc<TcgPicker>) {
+        {
+            let instance_copy = instance.clone();
+            instance.select_button.connect_clicked(move |_| {
+                instance_copy.select();
+            });
+        }
+        {
+            let instance_copy = instance.clone();
+            instance.tcg_list.connect_row_activated(move |_, _| {
+                instance_copy.select();
+            });
+        }
+        {
+            let instance_copy = instance.clone();
+            instance.cancel_button.connect_clicked(move |_| {
+                instance_copy.window.destroy();
+            });
+        }
+    }
+
+    /// Makes the selected TCG the active one and closes the picker.
+    fn select(&self) {
+        let selected = match self.tcg_list.get_selected_row() {
+            Some(row) => row.get_index(),
+            None => return
+        };
+        if let Some(&TcgEntry::Loaded{ref path, ref tcg}) = self.entries.get(selected as usize) {
+            self.fire_tcg_selected(path, tcg.clone());
+            self.window.destroy();
+        }
+    }
+
+    pub fn connect_tcg_selected<F : Fn(&PathBuf, Rc<TCG>) + 'static>(&self, f : F) {
+        self.tcg_selected_events.borrow_mut().push(Box::new(f));
+    }
+
+    fn fire_tcg_selected(&self, path : &PathBuf, tcg : Rc<TCG>) {
+        for f in self.tcg_selected_events.borrow().iter() {
+            f(path, tcg.clone());
+        }
+    }
+}
+
+fn escape_markup(text : &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
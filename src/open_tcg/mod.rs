@@ -23,3 +23,4 @@
 pub mod gui;
 pub mod game;
 pub mod util;
+pub mod net;
@@ -0,0 +1,531 @@
+// MIT License
+
+// Copyright (c) 2016 Johan Burke
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+use std::io::{self, Read};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use open_tcg::game::action::Action;
+use open_tcg::game::fingerprint::TcgFingerprint;
+use open_tcg::game::random;
+use open_tcg::game::tcg::TCG;
+use open_tcg::net::error::{NetError, NetResult};
+use open_tcg::net::protocol::{Hello, Message};
+
+/// Seconds between heartbeats when no other message is sent.
+pub const HEARTBEAT_SECONDS : u64 = 2;
+
+/// Seconds without hearing from the other player after which the connection is considered lost.
+pub const TIMEOUT_SECONDS : u64 = 10;
+
+/// The part of a connection that survives reconnecting.
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub id : u64,
+
+    /// Actions sent so far, kept so they can be sent again after reconnecting
+    sent : Vec<Action>,
+
+    /// Number of actions received so far
+    received : usize
+}
+
+impl Session {
+    fn new(id : u64) -> Session {
+        Session{id : id, sent : Vec::new(), received : 0}
+    }
+
+    /// Number of actions received from the other player during the session.
+    pub fn get_received(&self) -> usize {
+        self.received
+    }
+
+    /// Actions sent to the other player during the session.
+    pub fn get_sent(&self) -> &Vec<Action> {
+        &self.sent
+    }
+}
+
+/// Reads the other player's fingerprints after the card pools turned out to differ.
+fn read_fingerprints(stream : &mut TcpStream) -> NetResult<TcgFingerprint> {
+    match Message::read_from(stream).map_err(map_timeout)? {
+        Message::Fingerprints(fingerprint) => Ok(fingerprint),
+        _ => Err(NetError::Malformed("expected fingerprints".to_string()))
+    }
+}
+
+/// Converts the errors of a read that timed out into `NetError::TimedOut`.
+fn map_timeout(error : NetError) -> NetError {
+    match error {
+        NetError::Io(ref e) if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut =>
+            NetError::TimedOut,
+        e => e
+    }
+}
+
+/// Waits for the other player to connect.
+pub struct Listener {
+    listener : TcpListener,
+    fingerprint : TcgFingerprint,
+    hello : Hello
+}
+
+impl Listener {
+    /// Listens on the given address for a player to play the given TCG with.
+    pub fn bind<A : ToSocketAddrs>(address : A, tcg : &TCG, player_name : &str) -> NetResult<Listener> {
+        let fingerprint = TcgFingerprint::new(tcg);
+        let hello = Hello::new(tcg, &fingerprint, player_name);
+        Ok(Listener{listener : TcpListener::bind(address)?, fingerprint : fingerprint, hello : hello})
+    }
+
+    pub fn local_addr(&self) -> NetResult<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Waits for the other player to connect. To let a player reconnect to a game
+    /// in progress, pass the session of the lost connection; only that player is
+    /// then accepted. Actions they missed are sent again.
+    pub fn accept(&self, session : Option<Session>) -> NetResult<Connection> {
+        let (mut stream, _) = self.listener.accept()?;
+        stream.set_read_timeout(Some(Duration::from_secs(TIMEOUT_SECONDS)))?;
+
+        Message::Hello(self.hello.clone()).write_to(&mut stream)?;
+        let theirs = match Message::read_from(&mut stream).map_err(map_timeout)? {
+            Message::Hello(hello) => hello,
+            _ => return Err(NetError::Malformed("expected a hello message".to_string()))
+        };
+
+        let check = self.hello.check(&theirs).or_else(|error| {
+            match error {
+                NetError::CardPoolMismatch(_) => {
+                    let their_fingerprint = read_fingerprints(&mut stream)?;
+                    Message::Fingerprints(self.fingerprint.clone()).write_to(&mut stream)?;
+                    Err(NetError::CardPoolMismatch(Some(self.fingerprint.diff(&their_fingerprint))))
+                },
+                e => Err(e)
+            }
+        }).and_then(|_| {
+            match (session.as_ref(), theirs.session) {
+                (Some(session), Some(id)) if session.id == id => Ok(()),
+                (None, None) => Ok(()),
+                _ => Err(NetError::UnknownSession)
+            }
+        });
+        if let Err(error) = check {
+            Message::Reject{reason : error.to_string()}.write_to(&mut stream)?;
+            return Err(error);
+        }
+
+        let session = match session {
+            Some(session) => session,
+            None => Session::new(random::random_seed()?)
+        };
+        Message::Welcome{session : session.id, received : session.received}.write_to(&mut stream)?;
+
+        let mut result = Connection::new(stream, session, theirs.clone())?;
+        result.resend_from(theirs.received)?;
+        Ok(result)
+    }
+}
+
+/// A connection to the other player over which the actions of a game are exchanged.
+///
+/// `poll` must be called regularly: besides returning the actions received,
+/// it sends heartbeats and notices when the other player is gone.
+pub struct Connection {
+    stream : TcpStream,
+    session : Session,
+
+    /// What the other player told us about themselves
+    pub peer : Hello,
+
+    /// Bytes received that don't make up a whole message yet
+    buffer : Vec<u8>,
+
+    last_sent : Instant,
+    last_received : Instant,
+
+    pub heartbeat_interval : Duration,
+    pub timeout : Duration
+}
+
+impl Connection {
+    fn new(stream : TcpStream, session : Session, peer : Hello) -> NetResult<Connection> {
+        stream.set_read_timeout(None)?;
+        stream.set_nodelay(true)?;
+        let now = Instant::now();
+        Ok(Connection{stream : stream, session : session, peer : peer, buffer : Vec::new(),
+            last_sent : now, last_received : now,
+            heartbeat_interval : Duration::from_secs(HEARTBEAT_SECONDS),
+            timeout : Duration::from_secs(TIMEOUT_SECONDS)})
+    }
+
+    /// Connects to a player waiting with a `Listener` to play the given TCG.
+    /// To reconnect to a game in progress, pass the session of the lost connection.
+    /// Actions the other player missed are sent again.
+    pub fn connect<A : ToSocketAddrs>(address : A, tcg : &TCG, player_name : &str,
+                                      session : Option<Session>) -> NetResult<Connection> {
+        let mut stream = TcpStream::connect(address)?;
+        stream.set_read_timeout(Some(Duration::from_secs(TIMEOUT_SECONDS)))?;
+
+        let fingerprint = TcgFingerprint::new(tcg);
+        let mut hello = Hello::new(tcg, &fingerprint, player_name);
+        hello.session = session.as_ref().map(|s| s.id);
+        hello.received = session.as_ref().map_or(0, |s| s.received);
+        Message::Hello(hello.clone()).write_to(&mut stream)?;
+
+        let theirs = match Message::read_from(&mut stream).map_err(map_timeout)? {
+            Message::Hello(theirs) => theirs,
+            _ => return Err(NetError::Malformed("expected a hello message".to_string()))
+        };
+        if let Err(error) = hello.check(&theirs) {
+            if let NetError::CardPoolMismatch(_) = error {
+                // The listening side answers with its own fingerprints before rejecting us.
+                Message::Fingerprints(fingerprint.clone()).write_to(&mut stream)?;
+                let their_fingerprint = read_fingerprints(&mut stream)?;
+                return Err(NetError::CardPoolMismatch(Some(fingerprint.diff(&their_fingerprint))));
+            }
+            Message::Disconnect{reason : Some(error.to_string())}.write_to(&mut stream)?;
+            return Err(error);
+        }
+
+        match Message::read_from(&mut stream).map_err(map_timeout)? {
+            Message::Welcome{session : id, received} => {
+                let session = match session {
+                    Some(session) => {
+                        if session.id != id {
+                            return Err(NetError::UnknownSession);
+                        }
+                        session
+                    },
+                    None => Session::new(id)
+                };
+                let mut result = Connection::new(stream, session, theirs)?;
+                result.resend_from(received)?;
+                Ok(result)
+            },
+            Message::Reject{reason} => Err(NetError::Rejected(reason)),
+            _ => Err(NetError::Malformed("expected a welcome message".to_string()))
+        }
+    }
+
+    pub fn get_session(&self) -> &Session {
+        &self.session
+    }
+
+    /// Gives up the connection, keeping the session so it can be resumed.
+    pub fn into_session(self) -> Session {
+        self.session
+    }
+
+    fn send(&mut self, message : &Message) -> NetResult<()> {
+        message.write_to(&mut self.stream)?;
+        self.last_sent = Instant::now();
+        Ok(())
+    }
+
+    /// Sends the actions from the given index on again.
+    fn resend_from(&mut self, index : usize) -> NetResult<()> {
+        for i in index..self.session.sent.len() {
+            let message = Message::Action{index : i, action : self.session.sent[i].clone()};
+            self.send(&message)?;
+        }
+        Ok(())
+    }
+
+    /// Sends an action to the other player. If sending fails, the action is
+    /// still sent after reconnecting.
+    pub fn send_action(&mut self, action : Action) -> NetResult<()> {
+        let index = self.session.sent.len();
+        self.session.sent.push(action.clone());
+        self.send(&Message::Action{index : index, action : action})
+    }
+
+    /// Returns the actions received since the last call, without waiting for more.
+    /// Sends a heartbeat if nothing was sent for a while.
+    pub fn poll(&mut self) -> NetResult<Vec<Action>> {
+        self.stream.set_nonblocking(true)?;
+        let read = self.read_available();
+        self.stream.set_nonblocking(false)?;
+        let closed = read?;
+
+        let mut result = Vec::new();
+        while let Some(message) = Message::take_from_buffer(&mut self.buffer)? {
+            self.last_received = Instant::now();
+            match message {
+                Message::Action{index, action} => {
+                    if index > self.session.received {
+                        return Err(NetError::Malformed(format!("action {} arrived before action {}",
+                            index, self.session.received)));
+                    }
+                    // Actions received before reconnecting may be sent again.
+                    if index == self.session.received {
+                        self.session.received += 1;
+                        result.push(action);
+                    }
+                },
+                Message::Heartbeat => (),
+                Message::Disconnect{reason} => return Err(NetError::Disconnected(reason)),
+                _ => return Err(NetError::Malformed("unexpected message during the game".to_string()))
+            }
+        }
+
+        if closed {
+            return Err(NetError::Io(io::Error::new(io::ErrorKind::UnexpectedEof, "the connection was closed")));
+        }
+        if self.last_received.elapsed() > self.timeout {
+            return Err(NetError::TimedOut);
+        }
+        if self.last_sent.elapsed() >= self.heartbeat_interval {
+            self.send(&Message::Heartbeat)?;
+        }
+        Ok(result)
+    }
+
+    /// Moves all bytes that have arrived into the buffer.
+    /// Returns whether the other player has closed the connection.
+    fn read_available(&mut self) -> NetResult<bool> {
+        let mut chunk = [0; 4096];
+        loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return Ok(true),
+                Ok(len) => self.buffer.extend_from_slice(&chunk[..len]),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(false),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(NetError::Io(e))
+            }
+        }
+    }
+
+    /// Tells the other player the connection is ending on purpose and closes it.
+    pub fn disconnect(mut self, reason : Option<&str>) -> NetResult<()> {
+        self.send(&Message::Disconnect{reason : reason.map(|r| r.to_string())})?;
+        self.stream.shutdown(Shutdown::Both)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process;
+    use std::thread::{self, JoinHandle};
+    use open_tcg::game::card::CardInfo;
+    use open_tcg::game::fingerprint::Difference;
+
+    /// Loads a TCG with the given name and no cards. Its name can only be set
+    /// by reading a definition, so one is written to a temporary file.
+    fn test_tcg(name : &str) -> TCG {
+        let path = env::temp_dir().join(format!("open_tcg_net_{}_{}.xml", name, process::id()));
+        fs::write(&path, format!("<?xml version=\"1.0\"?><TCG><Name>{}</Name></TCG>", name)).unwrap();
+        let tcg = TCG::new_from_file(&PathBuf::from(&path)).unwrap();
+        fs::remove_file(&path).unwrap();
+        tcg
+    }
+
+    fn add_card(tcg : &mut TCG, name : &str) {
+        let mut card = CardInfo::new();
+        card.name = name.to_string();
+        tcg.cards.insert(card.name.clone(), card);
+    }
+
+    fn bind(tcg : &TCG) -> (Listener, SocketAddr) {
+        let listener = Listener::bind("127.0.0.1:0", tcg, "Host").unwrap();
+        let address = listener.local_addr().unwrap();
+        (listener, address)
+    }
+
+    /// Accepts a connection on another thread, giving the listener back along with the result.
+    fn accept(listener : Listener, session : Option<Session>) -> JoinHandle<(Listener, NetResult<Connection>)> {
+        thread::spawn(move || {
+            let result = listener.accept(session);
+            (listener, result)
+        })
+    }
+
+    /// Polls until the given number of actions has arrived, failing after a few seconds.
+    fn receive(connection : &mut Connection, count : usize) -> Vec<Action> {
+        let started = Instant::now();
+        let mut result = Vec::new();
+        while result.len() < count {
+            assert!(started.elapsed() < Duration::from_secs(5), "only received {:?}", result);
+            result.extend(connection.poll().unwrap());
+            thread::sleep(Duration::from_millis(10));
+        }
+        result
+    }
+
+    fn connect_pair(tcg : &TCG) -> (Listener, Connection, Connection) {
+        let (listener, address) = bind(tcg);
+        let accepting = accept(listener, None);
+        let guest = Connection::connect(address, tcg, "Guest", None).unwrap();
+        let (listener, host) = accepting.join().unwrap();
+        (listener, host.unwrap(), guest)
+    }
+
+    #[test]
+    fn handshake() {
+        let tcg = test_tcg("Handshake");
+        let (_, host, guest) = connect_pair(&tcg);
+        assert_eq!(host.peer.player_name, "Guest");
+        assert_eq!(guest.peer.player_name, "Host");
+        assert_eq!(host.get_session().id, guest.get_session().id);
+    }
+
+    #[test]
+    fn tcg_mismatch() {
+        let (listener, address) = bind(&test_tcg("Alpha"));
+        let accepting = accept(listener, None);
+        match Connection::connect(address, &test_tcg("Beta"), "Guest", None) {
+            Err(NetError::TcgMismatch{ours, theirs}) => {
+                assert_eq!(ours, "Beta");
+                assert_eq!(theirs, "Alpha");
+            },
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("connected to a different TCG")
+        }
+        // the host may fail to send its rejection once the guest has hung up
+        assert!(accepting.join().unwrap().1.is_err());
+    }
+
+    #[test]
+    fn card_pool_mismatch() {
+        let mut ours = test_tcg("Pool");
+        add_card(&mut ours, "Goblin");
+        let mut theirs = test_tcg("Pool");
+        add_card(&mut theirs, "Knight");
+
+        let (listener, address) = bind(&theirs);
+        let accepting = accept(listener, None);
+        match Connection::connect(address, &ours, "Guest", None) {
+            Err(NetError::CardPoolMismatch(Some(diff))) => {
+                assert!(!diff.rules_differ);
+                assert_eq!(diff.cards, vec![("Goblin".to_string(), Difference::OnlyOurs),
+                                            ("Knight".to_string(), Difference::OnlyTheirs)]);
+            },
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("connected with a different card pool")
+        }
+        assert!(accepting.join().unwrap().1.is_err());
+    }
+
+    #[test]
+    fn exchange_actions() {
+        let tcg = test_tcg("Actions");
+        let (_, mut host, mut guest) = connect_pair(&tcg);
+
+        guest.send_action(Action::Draw{player : 1}).unwrap();
+        guest.send_action(Action::Roll{player : 1, sides : 6, seed : 7}).unwrap();
+        assert_eq!(receive(&mut host, 2), vec![Action::Draw{player : 1}, Action::Roll{player : 1, sides : 6, seed : 7}]);
+
+        host.send_action(Action::EndGame{winner : Some(0)}).unwrap();
+        assert_eq!(receive(&mut guest, 1), vec![Action::EndGame{winner : Some(0)}]);
+        assert_eq!(guest.get_session().get_received(), 1);
+        assert_eq!(host.get_session().get_received(), 2);
+
+        guest.disconnect(Some("done")).unwrap();
+        let started = Instant::now();
+        loop {
+            match host.poll() {
+                Err(NetError::Disconnected(reason)) => {
+                    assert_eq!(reason, Some("done".to_string()));
+                    break;
+                },
+                Err(e) => panic!("unexpected error: {}", e),
+                Ok(_) => assert!(started.elapsed() < Duration::from_secs(5))
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn heartbeats() {
+        let tcg = test_tcg("Heartbeat");
+        let (_, mut host, mut guest) = connect_pair(&tcg);
+        for connection in [&mut host, &mut guest].iter_mut() {
+            connection.heartbeat_interval = Duration::from_millis(20);
+            connection.timeout = Duration::from_millis(200);
+        }
+
+        // heartbeats keep a quiet connection alive
+        let started = Instant::now();
+        while started.elapsed() < Duration::from_millis(500) {
+            assert!(host.poll().unwrap().is_empty());
+            assert!(guest.poll().unwrap().is_empty());
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        // once the host stops polling, it sends no more heartbeats
+        let started = Instant::now();
+        loop {
+            match guest.poll() {
+                Err(NetError::TimedOut) => break,
+                Err(e) => panic!("unexpected error: {}", e),
+                Ok(_) => assert!(started.elapsed() < Duration::from_secs(5))
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn reconnect() {
+        let tcg = test_tcg("Reconnect");
+        let (listener, mut host, mut guest) = connect_pair(&tcg);
+
+        host.send_action(Action::Draw{player : 0}).unwrap();
+        assert_eq!(receive(&mut guest, 1), vec![Action::Draw{player : 0}]);
+        // the connection drops before the host reads this
+        guest.send_action(Action::Draw{player : 1}).unwrap();
+        let host_session = host.into_session();
+        let guest_session = guest.into_session();
+        assert_eq!(host_session.get_received(), 0);
+
+        let address = listener.local_addr().unwrap();
+        let accepting = accept(listener, Some(host_session));
+        let mut guest = Connection::connect(address, &tcg, "Guest", Some(guest_session)).unwrap();
+        let (listener, host) = accepting.join().unwrap();
+        let mut host = host.unwrap();
+
+        // only the action the host missed is sent again
+        guest.send_action(Action::Roll{player : 1, sides : 2, seed : 7}).unwrap();
+        assert_eq!(receive(&mut host, 2), vec![Action::Draw{player : 1}, Action::Roll{player : 1, sides : 2, seed : 7}]);
+        host.send_action(Action::Draw{player : 0}).unwrap();
+        assert_eq!(receive(&mut guest, 1), vec![Action::Draw{player : 0}]);
+        assert_eq!(guest.get_session().get_received(), 2);
+
+        // a session the host doesn't know is rejected
+        let accepting = accept(listener, None);
+        let mut unknown = guest.into_session();
+        unknown.id += 1;
+        match Connection::connect(address, &tcg, "Guest", Some(unknown)) {
+            Err(NetError::Rejected(_)) => (),
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("resumed an unknown session")
+        }
+        match accepting.join().unwrap().1 {
+            Err(NetError::UnknownSession) => (),
+            _ => panic!("accepted an unknown session")
+        }
+    }
+}
@@ -0,0 +1,135 @@
+// MIT License
+
+// Copyright (c) 2016 Johan Burke
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use open_tcg::game::fingerprint::FingerprintDiff;
+use open_tcg::game::random::RandomError;
+use open_tcg::util::error::OpenTcgError;
+
+/// Errors that can occur while talking to the other player.
+#[derive(Debug)]
+pub enum NetError {
+    /// Reading from or writing to the connection failed.
+    Io(io::Error),
+
+    /// The other player sent something that isn't a valid message.
+    Malformed(String),
+
+    /// The other player's message couldn't be parsed.
+    Parse(OpenTcgError),
+
+    /// The other player speaks a different version of the protocol.
+    VersionMismatch{ours : u32, theirs : u32},
+
+    /// The other player is playing a different TCG.
+    TcgMismatch{ours : String, theirs : String},
+
+    /// Both players play the same TCG, but with different cards.
+    /// Holds the differences once they are known.
+    CardPoolMismatch(Option<FingerprintDiff>),
+
+    /// The other player refused the connection.
+    Rejected(String),
+
+    /// The other player tried to resume a session that doesn't exist here.
+    UnknownSession,
+
+    /// No random id could be made for a new session.
+    Random(RandomError),
+
+    /// Nothing was heard from the other player for too long.
+    TimedOut,
+
+    /// The other player ended the connection, giving the reason if any.
+    Disconnected(Option<String>)
+}
+
+pub type NetResult<T> = Result<T, NetError>;
+
+impl From<io::Error> for NetError {
+    fn from(error : io::Error) -> NetError {
+        NetError::Io(error)
+    }
+}
+
+impl From<OpenTcgError> for NetError {
+    fn from(error : OpenTcgError) -> NetError {
+        NetError::Parse(error)
+    }
+}
+
+impl From<RandomError> for NetError {
+    fn from(error : RandomError) -> NetError {
+        NetError::Random(error)
+    }
+}
+
+impl fmt::Display for NetError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            NetError::Io(ref error) => write!(f, "connection error: {}", error),
+            NetError::Malformed(ref message) => write!(f, "invalid message: {}", message),
+            NetError::Parse(ref error) => write!(f, "invalid message: {}", error),
+            NetError::VersionMismatch{ours, theirs} =>
+                write!(f, "the other player uses protocol version {}, but this is version {}", theirs, ours),
+            NetError::TcgMismatch{ref ours, ref theirs} =>
+                write!(f, "the other player is playing \"{}\" instead of \"{}\"", theirs, ours),
+            NetError::CardPoolMismatch(Some(ref diff)) =>
+                write!(f, "the other player's cards differ from yours:\n{}", diff),
+            NetError::CardPoolMismatch(None) => write!(f, "the other player's cards differ from yours"),
+            NetError::Rejected(ref reason) => write!(f, "the other player refused the connection: {}", reason),
+            NetError::UnknownSession => write!(f, "the game being resumed is not in progress"),
+            NetError::Random(ref error) => write!(f, "can't start a session: {}", error),
+            NetError::TimedOut => write!(f, "the other player stopped responding"),
+            NetError::Disconnected(Some(ref reason)) => write!(f, "the other player disconnected: {}", reason),
+            NetError::Disconnected(None) => write!(f, "the other player disconnected")
+        }
+    }
+}
+
+impl Error for NetError {
+    fn description(&self) -> &str {
+        match *self {
+            NetError::Io(..) => "connection error",
+            NetError::Malformed(..) => "malformed message",
+            NetError::Parse(..) => "unparsable message",
+            NetError::VersionMismatch{..} => "protocol version mismatch",
+            NetError::TcgMismatch{..} => "TCG mismatch",
+            NetError::CardPoolMismatch(..) => "card pool mismatch",
+            NetError::Rejected(..) => "connection rejected",
+            NetError::UnknownSession => "unknown session",
+            NetError::Random(..) => "no random numbers",
+            NetError::TimedOut => "connection timed out",
+            NetError::Disconnected(..) => "disconnected"
+        }
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        match *self {
+            NetError::Io(ref error) => Some(error),
+            NetError::Parse(ref error) => Some(error),
+            _ => None
+        }
+    }
+}
@@ -0,0 +1,258 @@
+// MIT License
+
+// Copyright (c) 2016 Johan Burke
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+extern crate sxd_document;
+
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use self::sxd_document::Package;
+use self::sxd_document::dom::{Document, Element};
+use self::sxd_document::writer::format_document;
+
+use open_tcg::game::action::Action;
+use open_tcg::game::fingerprint::TcgFingerprint;
+use open_tcg::game::tcg::TCG;
+use open_tcg::net::error::{NetError, NetResult};
+use open_tcg::util::xml;
+use open_tcg::util::files::XmlFile;
+
+/// Version of the wire protocol. Peers only talk to peers with the same version.
+pub const PROTOCOL_VERSION : u32 = 1;
+
+/// Largest message accepted, so a bad length can't make us allocate huge buffers.
+pub const MAX_MESSAGE_LEN : usize = 1 << 24;
+
+/// Messages are XML documents, each preceded by its length in bytes
+/// as a 4 byte big-endian integer.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    /// First message sent by each side of a new connection.
+    Hello(Hello),
+
+    /// Sent by the listening side once it has accepted the other side's `Hello`.
+    Welcome{session : u64, received : usize},
+
+    /// Sent during the handshake if the card pools differ, so that both sides
+    /// can tell which cards differ.
+    Fingerprints(TcgFingerprint),
+
+    /// Sent instead of `Welcome` if the connection is refused.
+    Reject{reason : String},
+
+    /// An action of the game. `index` counts the actions sent by one side,
+    /// so that actions sent again after reconnecting can be recognized.
+    Action{index : usize, action : Action},
+
+    /// Sent regularly so each side knows the other is still there.
+    Heartbeat,
+
+    /// Ends the connection on purpose.
+    Disconnect{reason : Option<String>}
+}
+
+/// What each side tells the other about itself when connecting.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hello {
+    pub version : u32,
+    pub tcg_name : String,
+
+    /// Fingerprint of the whole TCG, see `TcgFingerprint`
+    pub card_pool : String,
+
+    pub player_name : String,
+
+    /// The session being resumed, if reconnecting
+    pub session : Option<u64>,
+
+    /// Number of actions received from the other side during the session being resumed
+    pub received : usize
+}
+
+impl Hello {
+    pub fn new(tcg : &TCG, fingerprint : &TcgFingerprint, player_name : &str) -> Hello {
+        Hello{version : PROTOCOL_VERSION, tcg_name : tcg.get_name().clone(), card_pool : fingerprint.whole.to_hex(),
+            player_name : player_name.to_string(), session : None, received : 0}
+    }
+
+    /// Checks that both sides can play together. If only the card pools differ,
+    /// the error holds no differences yet; they are found by exchanging `Fingerprints`.
+    pub fn check(&self, theirs : &Hello) -> NetResult<()> {
+        if self.version != theirs.version {
+            Err(NetError::VersionMismatch{ours : self.version, theirs : theirs.version})
+        } else if self.tcg_name != theirs.tcg_name {
+            Err(NetError::TcgMismatch{ours : self.tcg_name.clone(), theirs : theirs.tcg_name.clone()})
+        } else if self.card_pool != theirs.card_pool {
+            Err(NetError::CardPoolMismatch(None))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Message {
+    fn to_element<'d>(&self, doc : &Document<'d>) -> Element<'d> {
+        let element;
+        match *self {
+            Message::Hello(ref hello) => {
+                element = doc.create_element("Hello");
+                xml::append_text_element(doc, &element, "Version", &hello.version.to_string());
+                xml::append_text_element(doc, &element, "TCG", &hello.tcg_name);
+                xml::append_text_element(doc, &element, "CardPool", &hello.card_pool);
+                xml::append_text_element(doc, &element, "Player", &hello.player_name);
+                if let Some(session) = hello.session {
+                    xml::append_text_element(doc, &element, "Session", &session.to_string());
+                }
+                xml::append_text_element(doc, &element, "Received", &hello.received.to_string());
+            },
+            Message::Welcome{session, received} => {
+                element = doc.create_element("Welcome");
+                xml::append_text_element(doc, &element, "Session", &session.to_string());
+                xml::append_text_element(doc, &element, "Received", &received.to_string());
+            },
+            Message::Fingerprints(ref fingerprint) => {
+                element = fingerprint.to_element(doc);
+            },
+            Message::Reject{ref reason} => {
+                element = doc.create_element("Reject");
+                xml::append_text_element(doc, &element, "Reason", reason);
+            },
+            Message::Action{index, ref action} => {
+                element = doc.create_element("Action");
+                xml::append_text_element(doc, &element, "Index", &index.to_string());
+                element.append_child(action.to_element(doc));
+            },
+            Message::Heartbeat => {
+                element = doc.create_element("Heartbeat");
+            },
+            Message::Disconnect{ref reason} => {
+                element = doc.create_element("Disconnect");
+                if let Some(ref reason) = *reason {
+                    xml::append_text_element(doc, &element, "Reason", reason);
+                }
+            }
+        }
+        element
+    }
+
+    fn new_from_element(element : &Element, file : &XmlFile) -> NetResult<Message> {
+        let text = |name : &str| -> NetResult<String> {
+            Ok(xml::read_text_from_element(&xml::required_child(element, name, file)?))
+        };
+        let num = |name : &str| -> NetResult<u64> {
+            Ok(xml::read_parsed_from_element(&xml::required_child(element, name, file)?, file)?)
+        };
+
+        match element.name().local_part() {
+            "Hello" => {
+                let session = match xml::find_child(element, "Session") {
+                    Some(s) => Some(xml::read_parsed_from_element(&s, file)?),
+                    None => None
+                };
+                Ok(Message::Hello(Hello{version : num("Version")? as u32, tcg_name : text("TCG")?,
+                    card_pool : text("CardPool")?, player_name : text("Player")?,
+                    session : session, received : num("Received")? as usize}))
+            },
+            "Welcome" => Ok(Message::Welcome{session : num("Session")?, received : num("Received")? as usize}),
+            "Fingerprints" => Ok(Message::Fingerprints(TcgFingerprint::new_from_element(element, file)?)),
+            "Reject" => Ok(Message::Reject{reason : text("Reason")?}),
+            "Action" => {
+                let index = num("Index")? as usize;
+                for child in element.children() {
+                    if let Some(child) = child.element() {
+                        if child.name().local_part() != "Index" {
+                            return Ok(Message::Action{index : index, action : Action::new_from_element(&child, file)?});
+                        }
+                    }
+                }
+                Err(NetError::Malformed("action message without an action".to_string()))
+            },
+            "Heartbeat" => Ok(Message::Heartbeat),
+            "Disconnect" => {
+                let reason = xml::find_child(element, "Reason").map(|r| xml::read_text_from_element(&r));
+                Ok(Message::Disconnect{reason : reason})
+            },
+            _ => Err(xml::unexpected_element(element, file).into())
+        }
+    }
+
+    /// Encodes the message, including its length prefix.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let package = Package::new();
+        let doc = package.as_document();
+        doc.root().append_child(self.to_element(&doc));
+
+        let mut body = Vec::new();
+        format_document(&doc, &mut body).expect("writing to memory can't fail");
+
+        let len = body.len() as u32;
+        let mut result = vec![(len >> 24) as u8, (len >> 16) as u8, (len >> 8) as u8, len as u8];
+        result.append(&mut body);
+        result
+    }
+
+    /// Decodes a message without its length prefix.
+    pub fn from_bytes(body : &[u8]) -> NetResult<Message> {
+        let source = String::from_utf8(body.to_vec())
+            .map_err(|_| NetError::Malformed("message is not valid UTF-8".to_string()))?;
+        let file = XmlFile::new_from_string(&PathBuf::from("<network>"), source)?;
+        let root = file.root()?;
+        Message::new_from_element(&root, &file)
+    }
+
+    /// Takes the first complete message out of `buffer`, leaving any bytes after it.
+    /// Returns None if the buffer doesn't hold a complete message yet.
+    pub fn take_from_buffer(buffer : &mut Vec<u8>) -> NetResult<Option<Message>> {
+        if buffer.len() < 4 {
+            return Ok(None);
+        }
+        let len = buffer[..4].iter().fold(0, |len, &b| (len << 8) | b as usize);
+        if len > MAX_MESSAGE_LEN {
+            return Err(NetError::Malformed(format!("message of {} bytes is too long", len)));
+        }
+        if buffer.len() < 4 + len {
+            return Ok(None);
+        }
+        let rest = buffer.split_off(4 + len);
+        let message = Message::from_bytes(&buffer[4..]);
+        *buffer = rest;
+        message.map(Some)
+    }
+
+    pub fn write_to<W : Write>(&self, writer : &mut W) -> NetResult<()> {
+        writer.write_all(&self.to_bytes())?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Reads one whole message, blocking until it has arrived.
+    pub fn read_from<R : Read>(reader : &mut R) -> NetResult<Message> {
+        let mut header = [0; 4];
+        reader.read_exact(&mut header)?;
+        let len = header.iter().fold(0, |len, &b| (len << 8) | b as usize);
+        if len > MAX_MESSAGE_LEN {
+            return Err(NetError::Malformed(format!("message of {} bytes is too long", len)));
+        }
+        let mut body = vec![0; len];
+        reader.read_exact(&mut body)?;
+        Message::from_bytes(&body)
+    }
+}
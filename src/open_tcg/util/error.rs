@@ -0,0 +1,173 @@
+// MIT License
+
+// Copyright (c) 2016 Johan Burke
+
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Errors that can occur while loading OpenTCG data files.
+///
+/// Every variant carries the path of the file that caused it so that
+/// callers loading many files can report exactly which ones failed.
+#[derive(Debug)]
+pub enum OpenTcgError {
+    /// The file could not be opened or read.
+    Io{path : PathBuf, error : io::Error},
+
+    /// The file is not well-formed XML.
+    Parse{path : PathBuf, line : usize, message : String},
+
+    /// The document has no root element, or the root element has the wrong name.
+    UnexpectedRoot{path : PathBuf, expected : String},
+
+    /// An element that must hold a value is empty.
+    EmptyElement{path : PathBuf, element : String, line : Option<usize>},
+
+    /// An element that must hold an integer holds something else.
+    InvalidNumber{path : PathBuf, element : String, line : Option<usize>, value : String},
+
+    /// A required child element is missing.
+    MissingElement{path : PathBuf, element : String, line : Option<usize>},
+
+    /// An element appears where it isn't allowed.
+    UnexpectedElement{path : PathBuf, element : String, line : Option<usize>},
+
+    /// An element holds something other than one of the values it accepts.
+    InvalidValue{path : PathBuf, element : String, line : Option<usize>, value : String},
+
+    /// A card declares a type that the TCG does not define.
+    UnknownCardType{path : PathBuf, card_type : String, line : Option<usize>},
+
+    /// A card doesn't give a value for one of its type's parameters.
+    MissingParameter{path : PathBuf, parameter : String},
+
+    /// A card gives a value for a parameter that its type doesn't have.
+    UnexpectedParameter{path : PathBuf, parameter : String, line : Option<usize>},
+
+    /// A card type names a parent type that the TCG does not define.
+    MissingParentType{path : PathBuf, card_type : String, parent : String},
+
+    /// A card type is, directly or indirectly, its own parent.
+    CardTypeCycle{path : PathBuf, card_type : String}
+}
+
+pub type OpenTcgResult<T> = Result<T, OpenTcgError>;
+
+impl OpenTcgError {
+    /// The file in which the error occurred.
+    pub fn path(&self) -> &PathBuf {
+        match *self {
+            OpenTcgError::Io{ref path, ..} => path,
+            OpenTcgError::Parse{ref path, ..} => path,
+            OpenTcgError::UnexpectedRoot{ref path, ..} => path,
+            OpenTcgError::EmptyElement{ref path, ..} => path,
+            OpenTcgError::InvalidNumber{ref path, ..} => path,
+            OpenTcgError::MissingElement{ref path, ..} => path,
+            OpenTcgError::UnexpectedElement{ref path, ..} => path,
+            OpenTcgError::InvalidValue{ref path, ..} => path,
+            OpenTcgError::UnknownCardType{ref path, ..} => path,
+            OpenTcgError::MissingParameter{ref path, ..} => path,
+            OpenTcgError::UnexpectedParameter{ref path, ..} => path,
+            OpenTcgError::MissingParentType{ref path, ..} => path,
+            OpenTcgError::CardTypeCycle{ref path, ..} => path
+        }
+    }
+
+    fn line(&self) -> Option<usize> {
+        match *self {
+            OpenTcgError::Parse{line, ..} => Some(line),
+            OpenTcgError::EmptyElement{line, ..} => line,
+            OpenTcgError::InvalidNumber{line, ..} => line,
+            OpenTcgError::MissingElement{line, ..} => line,
+            OpenTcgError::UnexpectedElement{line, ..} => line,
+            OpenTcgError::InvalidValue{line, ..} => line,
+            OpenTcgError::UnknownCardType{line, ..} => line,
+            OpenTcgError::UnexpectedParameter{line, ..} => line,
+            _ => None
+        }
+    }
+}
+
+impl fmt::Display for OpenTcgError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.path().display())?;
+        if let Some(line) = self.line() {
+            write!(f, ":{}", line)?;
+        }
+        match *self {
+            OpenTcgError::Io{ref error, ..} => write!(f, ": {}", error),
+            OpenTcgError::Parse{ref message, ..} => write!(f, ": {}", message),
+            OpenTcgError::UnexpectedRoot{ref expected, ..} => {
+                if expected.is_empty() {
+                    write!(f, ": missing root element")
+                } else {
+                    write!(f, ": expected root element <{}>", expected)
+                }
+            },
+            OpenTcgError::EmptyElement{ref element, ..} => write!(f, ": <{}> must not be empty", element),
+            OpenTcgError::InvalidNumber{ref element, ref value, ..} =>
+                write!(f, ": <{}> has invalid number \"{}\"", element, value),
+            OpenTcgError::MissingElement{ref element, ..} => write!(f, ": missing <{}>", element),
+            OpenTcgError::UnexpectedElement{ref element, ..} => write!(f, ": unexpected <{}>", element),
+            OpenTcgError::InvalidValue{ref element, ref value, ..} =>
+                write!(f, ": <{}> has invalid value \"{}\"", element, value),
+            OpenTcgError::UnknownCardType{ref card_type, ..} =>
+                write!(f, ": unknown card type \"{}\"", card_type),
+            OpenTcgError::MissingParameter{ref parameter, ..} =>
+                write!(f, ": missing value for parameter \"{}\"", parameter),
+            OpenTcgError::UnexpectedParameter{ref parameter, ..} =>
+                write!(f, ": card type has no parameter \"{}\"", parameter),
+            OpenTcgError::MissingParentType{ref card_type, ref parent, ..} =>
+                write!(f, ": parent \"{}\" of card type \"{}\" is not defined", parent, card_type),
+            OpenTcgError::CardTypeCycle{ref card_type, ..} =>
+                write!(f, ": the parents of card type \"{}\" form a cycle", card_type)
+        }
+    }
+}
+
+impl Error for OpenTcgError {
+    fn description(&self) -> &str {
+        match *self {
+            OpenTcgError::Io{..} => "error reading file",
+            OpenTcgError::Parse{..} => "error parsing file",
+            OpenTcgError::UnexpectedRoot{..} => "unexpected root element",
+            OpenTcgError::EmptyElement{..} => "empty element",
+            OpenTcgError::InvalidNumber{..} => "invalid number",
+            OpenTcgError::MissingElement{..} => "missing element",
+            OpenTcgError::UnexpectedElement{..} => "unexpected element",
+            OpenTcgError::InvalidValue{..} => "invalid value",
+            OpenTcgError::UnknownCardType{..} => "unknown card type",
+            OpenTcgError::MissingParameter{..} => "missing parameter",
+            OpenTcgError::UnexpectedParameter{..} => "unexpected parameter",
+            OpenTcgError::MissingParentType{..} => "missing parent card type",
+            OpenTcgError::CardTypeCycle{..} => "card type cycle"
+        }
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        match *self {
+            OpenTcgError::Io{ref error, ..} => Some(error),
+            _ => None
+        }
+    }
+}
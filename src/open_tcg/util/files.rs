@@ -24,24 +24,100 @@ extern crate sxd_document;
 
 use self::sxd_document::parser;
 use self::sxd_document::Package;
+use self::sxd_document::dom::{Document, Element};
 
+use std::env;
 use std::io::{BufReader, Lines};
 use std::io::prelude::*;
 use std::fs::File;
 use std::path::PathBuf;
 
-pub fn lines_from_file(filename : &PathBuf) -> Lines<BufReader<File>> {
-    let f = File::open(filename).expect("Error reading file");
+use super::error::{OpenTcgError, OpenTcgResult};
+use super::xml;
+
+/// A parsed XML file along with the information needed to report errors in it.
+pub struct XmlFile {
+    pub path : PathBuf,
+    source : String,
+    package : Package
+}
+
+impl XmlFile {
+    /// Parses XML that didn't come directly from a file, such as a network message.
+    /// `path` is only used to identify the source in errors.
+    pub fn new_from_string(path : &PathBuf, source : String) -> OpenTcgResult<XmlFile> {
+        match parser::parse(&source) {
+            Ok(package) => Ok(XmlFile{path : path.clone(), source : source, package : package}),
+            Err((offset, errors)) => Err(OpenTcgError::Parse{path : path.clone(),
+                line : xml::line_of_offset(&source, offset),
+                message : format!("{:?}", errors)})
+        }
+    }
+
+    pub fn as_document(&self) -> Document {
+        self.package.as_document()
+    }
+
+    /// Returns the root element of the document.
+    pub fn root(&self) -> OpenTcgResult<Element> {
+        for child in self.package.as_document().root().children() {
+            if let Some(element) = child.element() {
+                return Ok(element);
+            }
+        }
+        Err(OpenTcgError::UnexpectedRoot{path : self.path.clone(), expected : String::new()})
+    }
+
+    /// Returns the root element of the document, which must be named `expected`.
+    pub fn root_element(&self, expected : &str) -> OpenTcgResult<Element> {
+        match self.root() {
+            Ok(ref element) if element.name().local_part() == expected => Ok(*element),
+            _ => Err(OpenTcgError::UnexpectedRoot{path : self.path.clone(), expected : expected.to_string()})
+        }
+    }
+
+    /// Returns the line on which the given element of this file starts, if it can be found.
+    pub fn line_of(&self, element : &Element) -> Option<usize> {
+        xml::line_of_element(&self.source, element)
+    }
+}
+
+pub fn lines_from_file(filename : &PathBuf) -> OpenTcgResult<Lines<BufReader<File>>> {
+    let f = File::open(filename).map_err(|e| OpenTcgError::Io{path : filename.clone(), error : e})?;
     let reader = BufReader::new(f);
 
-    reader.lines()
+    Ok(reader.lines())
 }
 
-pub fn document_from_file(filename : &PathBuf) -> Package {
-    // TODO: add better/more specific error handling here
+pub fn document_from_file(filename : &PathBuf) -> OpenTcgResult<XmlFile> {
     let mut s = String::new();
-    let mut f = File::open(filename).expect("Error reading file");
-    f.read_to_string(&mut s).expect("Error reading file");
- 
-    parser::parse(&s).expect("error parsing file")
+    let mut f = File::open(filename).map_err(|e| OpenTcgError::Io{path : filename.clone(), error : e})?;
+    f.read_to_string(&mut s).map_err(|e| OpenTcgError::Io{path : filename.clone(), error : e})?;
+
+    XmlFile::new_from_string(filename, s)
+}
+
+/// Directory where OpenTCG keeps the user's data, such as their profile:
+/// `$XDG_DATA_HOME/opentcg`, or `~/.local/share/opentcg` if that isn't set.
+pub fn data_directory() -> PathBuf {
+    let base = match env::var_os("XDG_DATA_HOME") {
+        Some(ref dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => home_directory().join(".local").join("share")
+    };
+    base.join("opentcg")
+}
+
+/// Directory where OpenTCG keeps its settings:
+/// `$XDG_CONFIG_HOME/opentcg`, or `~/.config/opentcg` if that isn't set.
+pub fn config_directory() -> PathBuf {
+    let base = match env::var_os("XDG_CONFIG_HOME") {
+        Some(ref dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => home_directory().join(".config")
+    };
+    base.join("opentcg")
+}
+
+/// The user's home directory, or the current directory if it is unknown.
+pub fn home_directory() -> PathBuf {
+    env::var_os("HOME").map(PathBuf::from).unwrap_or(PathBuf::from("."))
 }
@@ -22,3 +22,5 @@
 
 pub mod files;
 pub mod xml;
+pub mod error;
+pub mod hex;
@@ -21,23 +21,154 @@
 // SOFTWARE.
 
 extern crate sxd_document;
-use self::sxd_document::dom::Element;
+use self::sxd_document::dom::{Document, Element, ChildOfElement};
+
+use std::str::FromStr;
+
+use super::error::{OpenTcgError, OpenTcgResult};
+use super::files::XmlFile;
 
 pub fn read_text_from_element(element : &Element) -> String {
+    read_raw_text_from_element(element).trim().to_string()
+}
+
+/// Reads the text of an element as it was written, including leading and trailing whitespace.
+pub fn read_raw_text_from_element(element : &Element) -> String {
+    // the parser splits text containing entities such as &amp; into several text nodes
     let mut result = String::new();
-    if element.children().len() > 0 {
-        if let Some(text) = element.children()[0].text() {
-            result = text.text().trim().to_string();
+    for child in element.children() {
+        if let Some(text) = child.text() {
+            result.push_str(text.text());
         }
     }
     result
 }
 
-pub fn read_num_from_element(element : &Element) -> u32 {
-    let mut result = 0;
-    if let Some(text) = element.children()[0].text() {
-        result = text.text().trim().to_string()
-            .parse().expect("Number must be a nonnegative integer.");
+/// Returns the first child element of `element` with the given name.
+pub fn find_child<'d>(element : &Element<'d>, name : &str) -> Option<Element<'d>> {
+    for child in element.children() {
+        if let Some(child_element) = child.element() {
+            if child_element.name().local_part() == name {
+                return Some(child_element);
+            }
+        }
+    }
+    None
+}
+
+/// Like `find_child`, but it is an error for the child to be missing.
+pub fn required_child<'d>(element : &Element<'d>, name : &str, file : &XmlFile) -> OpenTcgResult<Element<'d>> {
+    find_child(element, name).ok_or_else(|| OpenTcgError::MissingElement{path : file.path.clone(),
+        element : name.to_string(), line : file.line_of(element)})
+}
+
+/// Creates an error for an element that isn't allowed where it appears.
+pub fn unexpected_element(element : &Element, file : &XmlFile) -> OpenTcgError {
+    OpenTcgError::UnexpectedElement{path : file.path.clone(), element : element.name().local_part().to_string(),
+        line : file.line_of(element)}
+}
+
+/// Appends a child element holding only the given text, e.g. `<Name>text</Name>`.
+pub fn append_text_element<'d>(doc : &Document<'d>, parent : &Element<'d>, name : &str, text : &str) -> Element<'d> {
+    let element = doc.create_element(name);
+    element.append_child(doc.create_text(text));
+    parent.append_child(element);
+    element
+}
+
+pub fn read_num_from_element(element : &Element, file : &XmlFile) -> OpenTcgResult<u32> {
+    read_parsed_from_element(element, file)
+}
+
+pub fn read_int_from_element(element : &Element, file : &XmlFile) -> OpenTcgResult<i32> {
+    read_parsed_from_element(element, file)
+}
+
+/// Reads a boolean written as "true" or "false".
+pub fn read_bool_from_element(element : &Element, file : &XmlFile) -> OpenTcgResult<bool> {
+    match read_text_from_element(element).to_lowercase().as_str() {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(invalid_value(element, file))
+    }
+}
+
+/// Creates an error for an element whose text is not one of the values it accepts.
+pub fn invalid_value(element : &Element, file : &XmlFile) -> OpenTcgError {
+    OpenTcgError::InvalidValue{path : file.path.clone(), element : element.name().local_part().to_string(),
+        line : file.line_of(element), value : read_text_from_element(element)}
+}
+
+pub fn read_parsed_from_element<T : FromStr>(element : &Element, file : &XmlFile) -> OpenTcgResult<T> {
+    let text = read_text_from_element(element);
+    if text.is_empty() {
+        return Err(OpenTcgError::EmptyElement{path : file.path.clone(),
+            element : element.name().local_part().to_string(), line : file.line_of(element)});
+    }
+    text.parse().map_err(|_| OpenTcgError::InvalidNumber{path : file.path.clone(),
+        element : element.name().local_part().to_string(), line : file.line_of(element),
+        value : text.clone()})
+}
+
+/// Converts a byte offset into the given source into a 1-based line number.
+pub fn line_of_offset(source : &str, offset : usize) -> usize {
+    let end = if offset > source.len() { source.len() } else { offset };
+    source.as_bytes()[..end].iter().filter(|&&b| b == b'\n').count() + 1
+}
+
+/// Finds the 1-based line on which an element starts in the source it was parsed from.
+///
+/// The DOM does not keep positions, so this counts the elements with the same
+/// name that precede `element` in document order and then finds the matching
+/// start tag in `source`.
+pub fn line_of_element(source : &str, element : &Element) -> Option<usize> {
+    let name = element.name().local_part();
+    let mut preceding = 0;
+    let mut found = false;
+    for child in element.document().root().children() {
+        if let Some(root) = child.element() {
+            count_preceding(&root, element, name, &mut preceding, &mut found);
+        }
+    }
+    if !found {
+        return None;
+    }
+
+    let tag = format!("<{}", name);
+    let mut start = 0;
+    let mut seen = 0;
+    while let Some(pos) = source[start..].find(&tag) {
+        let offset = start + pos;
+        let next = source[offset + tag.len()..].chars().next();
+        let is_match = match next {
+            Some(c) => c == '>' || c == '/' || c.is_whitespace(),
+            None => false
+        };
+        if is_match {
+            if seen == preceding {
+                return Some(line_of_offset(source, offset));
+            }
+            seen += 1;
+        }
+        start = offset + tag.len();
+    }
+    None
+}
+
+fn count_preceding(current : &Element, target : &Element, name : &str, count : &mut usize, found : &mut bool) {
+    if *found {
+        return;
+    }
+    if current == target {
+        *found = true;
+        return;
+    }
+    if current.name().local_part() == name {
+        *count += 1;
+    }
+    for child in current.children() {
+        if let ChildOfElement::Element(e) = child {
+            count_preceding(&e, target, name, count, found);
+        }
     }
-    result
 }